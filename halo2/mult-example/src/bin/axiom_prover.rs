@@ -1,11 +1,11 @@
 use std::io::{self, Write};
 use halo2_base::{
     halo2_proofs::{
-        plonk::{create_proof, keygen_pk, keygen_vk, verify_proof},
+        plonk::{create_proof, verify_proof},
         halo2curves::bn256::{Bn256, Fr, G1Affine},
         poly::commitment::{Params,ParamsProver},
         poly::kzg::{
-            commitment::{KZGCommitmentScheme,ParamsKZG},
+            commitment::KZGCommitmentScheme,
             multiopen::{ProverSHPLONK, VerifierSHPLONK},
             strategy::SingleStrategy,
         },
@@ -17,20 +17,10 @@ use halo2_base::{
 
 use rand_core::OsRng;
 use mult_example::mult_circ::MyCircuit;
-
-use std::{
-    env::var,
-    fs::File,
-    io::BufReader,
+use axiom_eth::{
+    keystore::{CircuitFingerprint, KeyStore},
+    params::get_srs,
 };
-pub fn read_params(k: u32) -> ParamsKZG<Bn256> {
-    let dir = var("PARAMS_DIR").unwrap_or_else(|_| "./params".to_string());
-    ParamsKZG::<Bn256>::read(&mut BufReader::new(
-        File::open(format!("{dir}/kzg_bn254_{k}.srs").as_str())
-            .expect("Params file does not exist"),
-    ))
-    .unwrap()
-}
 
 /* 
 fn format_inst(instance: Vec<Fr>) -> Option<&[&[&[<KZGCommitmentScheme<Fr>::Scalar]]]>{
@@ -46,11 +36,11 @@ fn main() {
     let k = 11;
 
     let circuit = MyCircuit::<Fr>::default(); 
-    let params = read_params(k);  
+    let params = get_srs(k);  
 
     //let circuit: StandardPlonk<Fr> =  StandardPlonk::from_json("./plonk_input.json") ;
-    let vk = keygen_vk(&params, &circuit).expect("something wrong with verifier key");
-    let pk = keygen_pk(&params, vk, &circuit).expect("something wrong with proving key");    
+    let fingerprint = CircuitFingerprint::new("mult", k);
+    let pk = KeyStore::default().get_or_gen_pk(&params, &circuit, &fingerprint);
     let circuit = MyCircuit::<Fr>::from_json("./mult_in.json") ;
     let instance = circuit.instance();
 
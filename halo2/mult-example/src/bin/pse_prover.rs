@@ -5,7 +5,7 @@ use halo2_proofs::{
     plonk::*,
     poly::{
         kzg::{
-            commitment::{KZGCommitmentScheme,ParamsKZG},
+            commitment::KZGCommitmentScheme,
             multiopen::{ProverSHPLONK, VerifierSHPLONK},
             strategy::SingleStrategy},
         commitment::Params,
@@ -15,20 +15,12 @@ use halo2_proofs::{
 
 use rand_core::OsRng;
 use mult_example::mult_circ::MyCircuit;
-
-use std::{
-    env::var,
-    fs::File,
-    io::BufReader,
+// the shared key store works with the keys of the halo2_base backend, which `MyCircuit` is written for,
+// so the setup is loaded as that backend's `ParamsKZG`
+use axiom_eth::{
+    keystore::{CircuitFingerprint, KeyStore},
+    params::get_srs,
 };
-pub fn read_params(k: u32) -> ParamsKZG<Bn256> {
-    let dir = var("PARAMS_DIR").unwrap_or_else(|_| "./params".to_string());
-    ParamsKZG::<Bn256>::read(&mut BufReader::new(
-        File::open(format!("{dir}/kzg_bn254_{k}.srs").as_str())
-            .expect("Params file does not exist"),
-    ))
-    .unwrap()
-}
 
 fn main() {
 
@@ -36,10 +28,10 @@ fn main() {
     let k = 11;
     //MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
     let circuit = MyCircuit::<Fp>::default(); 
-    let params = read_params(k);  
+    let params = get_srs(k);  
 
-    let vk = keygen_vk(&params, &circuit).expect("something wrong with verifier key");
-    let pk = keygen_pk(&params, vk, &circuit).expect("something wrong with proving key");    
+    let fingerprint = CircuitFingerprint::new("mult", k);
+    let pk = KeyStore::default().get_or_gen_pk(&params, &circuit, &fingerprint);
     let circuit = MyCircuit::from_json("./mult_in.json") ;
 
     let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
@@ -17,7 +17,40 @@ use halo2_base::{
     QuantumCell::{Existing, Witness},
     SKIP_FIRST_PASS,
 };
-use std::fs::File;
+use std::{fmt, fs::File, io};
+
+/// Error from reading the circuit input file.
+#[derive(Debug)]
+pub enum InputError {
+    Io { path: String, source: io::Error },
+    /// `field` is missing or is not valid json for its type.
+    Json { field: String, source: serde_json::Error },
+    MissingField(String),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "can't read {path}: {source}"),
+            Self::Json { field, source } => write!(f, "invalid json for {field}: {source}"),
+            Self::MissingField(field) => write!(f, "missing field {field}"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+fn read_json(path: &str) -> Result<serde_json::Value, InputError> {
+    let file = File::open(path).map_err(|source| InputError::Io { path: path.to_string(), source })?;
+    serde_json::from_reader(io::BufReader::new(file))
+        .map_err(|source| InputError::Json { field: path.to_string(), source })
+}
+
+fn json_field(json: &serde_json::Value, field: &str) -> Result<u64, InputError> {
+    let value = json.get(field).ok_or_else(|| InputError::MissingField(field.to_string()))?;
+    serde_json::from_value(value.clone())
+        .map_err(|source| InputError::Json { field: field.to_string(), source })
+}
 
 #[derive(Clone, Default)]
 pub struct MyCircuit<F> {
@@ -28,17 +61,24 @@ pub struct MyCircuit<F> {
 
 
 impl MyCircuit<Fr> {
+    /// # Panics
+    /// If the input is malformed, see [`MyCircuit::try_from_json`].
     pub fn from_json(infile: &str) -> Self {
-        let witness: serde_json::Value = serde_json::from_reader(File::open(infile).unwrap()).unwrap();
-        let ain: u64 = serde_json::from_value(witness["a"].clone()).unwrap();
-        let bin: u64 = serde_json::from_value(witness["b"].clone()).unwrap();
-        let cin: u64 = serde_json::from_value(witness["c"].clone()).unwrap();
+        Self::try_from_json(infile).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Reads the witnesses `a`, `b`, `c` as integers from the json file `infile`.
+    pub fn try_from_json(infile: &str) -> Result<Self, InputError> {
+        let witness = read_json(infile)?;
+        let ain = json_field(&witness, "a")?;
+        let bin = json_field(&witness, "b")?;
+        let cin = json_field(&witness, "c")?;
 
-        MyCircuit {         
+        Ok(MyCircuit {
             a: Value::known(Fr::from(ain)),
             b: Value::known(Fr::from(bin)),
             c: Value::known(Fr::from(cin)),
-        } 
+        })
     }
 
     pub fn instance(&self) -> Vec<Fr> {
@@ -0,0 +1,78 @@
+use axiom_eth::{
+    block_header::{
+        helpers::{CircuitType, Finality, Sequencer, Task},
+        scheduler::SchedulerConfig,
+    },
+    Network,
+};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(about = "Generates block header chain snarks with a resumable job scheduler")]
+struct Cli {
+    #[arg(long, value_enum, default_value_t = Network::Mainnet)]
+    network: Network,
+    /// Number of initial depth snarks to generate concurrently
+    #[arg(long, default_value_t = 1)]
+    parallelism: usize,
+    /// Number of times a failed job is retried before giving up
+    #[arg(long, default_value_t = 2)]
+    max_retries: usize,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start (or continue) proving the block range `start..=end`
+    Run {
+        #[arg(long)]
+        start: u32,
+        #[arg(long)]
+        end: u32,
+        #[arg(long)]
+        depth: usize,
+        #[arg(long)]
+        initial_depth: usize,
+        /// Perform `evm_rounds + 1` rounds of aggregation on the final merkle snark
+        #[arg(long, conflicts_with = "merkle")]
+        evm_rounds: Option<usize>,
+        /// Produce a single final snark with the full merkle mountain range
+        #[arg(long)]
+        merkle: bool,
+        /// Job state file; defaults to a file next to the snarks in `data/headers`
+        #[arg(long)]
+        state: Option<PathBuf>,
+    },
+    /// Resume a previous run from its job state file
+    Resume {
+        #[arg(long)]
+        state: PathBuf,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut sequencer = Sequencer::new(cli.network);
+    let mut config = SchedulerConfig {
+        parallelism: cli.parallelism,
+        max_retries: cli.max_retries,
+        ..Default::default()
+    };
+    match cli.command {
+        Command::Run { start, end, depth, initial_depth, evm_rounds, merkle, state } => {
+            let finality = match (evm_rounds, merkle) {
+                (Some(round), _) => Finality::Evm(round),
+                (None, true) => Finality::Merkle,
+                (None, false) => Finality::None,
+            };
+            config.state_path = state;
+            let task = Task::new(start, end, CircuitType::new(depth, initial_depth, finality));
+            sequencer.run(task, config);
+        }
+        Command::Resume { state } => {
+            sequencer.resume(state, config);
+        }
+    }
+}
@@ -1,7 +1,6 @@
 use halo2_base::{
     halo2_proofs::{
-        SerdeFormat,
-        plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, VerifyingKey, ProvingKey},
+        plonk::{create_proof, verify_proof, Circuit},
         halo2curves::bn256::{Bn256, Fr, G1Affine},
         poly::commitment::{ParamsProver, Params},
         poly::kzg::{
@@ -14,10 +13,11 @@ use halo2_base::{
             Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
         },
     },
-    utils::{fs::gen_srs},
 };
 use rand_core::OsRng;  
-use axiom_eth::storage::EthBlockStorageCircuit;
+use axiom_eth::{
+    keystore::KeyStore, params::get_srs, sizing::SizingOptions, storage::EthBlockStorageCircuit,
+};
 use std::{
     fs::{self, File},
     io::{BufWriter, Write},
@@ -26,15 +26,20 @@ use std::{
 
 fn main() {
 
-    let k = 17;
-    let params = gen_srs(k);
-
-    let circuit = EthBlockStorageCircuit::<Fr>::default();
-    let vk = keygen_vk(&params, &circuit).unwrap();
-    let pk = keygen_pk(&params, vk, &circuit).unwrap();
+    let circuit = EthBlockStorageCircuit::<Fr>::from_json("diff_block_same_add.json");
+    // size the circuit for the input shape; keygen and proving must use the same params
+    let config = circuit.sized_config_params(&SizingOptions::default());
+    let _config = config.enter();
+    let k = config.degree;
+    let params = get_srs(k);
 
+    // the proving key is only generated the first time, or when the circuit shape changes
+    let pk = KeyStore::default().get_or_gen_pk(
+        &params,
+        &circuit.without_witnesses(),
+        &circuit.fingerprint(&config),
+    );
 
-    let circuit = EthBlockStorageCircuit::<Fr>::from_json("diff_block_same_add.json");
     let instance = circuit.instance();
     // create a proof
     println!("beginning prove step");
@@ -0,0 +1,92 @@
+use axiom_eth::{
+    keystore::KeyStore,
+    params::get_srs,
+    sizing::SizingOptions,
+    storage::{
+        evm::{deploy_and_call, gen_storage_evm_artifacts},
+        EthBlockStorageCircuit, DEFAULT_ACCOUNT_PROOF_MAX_DEPTH, DEFAULT_STORAGE_PROOF_MAX_DEPTH,
+    },
+    util::{read_config, EthConfigParams},
+};
+use clap::Parser;
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use snark_verifier_sdk::halo2::aggregation::AggregationConfigParams;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(about = "Generates the EVM verifier contract and calldata for a storage proof")]
+struct Cli {
+    /// Storage proof input, in the same format as for `single_storage_proof`
+    #[arg(long)]
+    input: String,
+    /// Pad the queried slots to this many, so that one verifier contract serves any number of slots up to it
+    #[arg(long)]
+    max_slots: Option<usize>,
+    /// Max depth of the account proof, part of the circuit shape
+    #[arg(long, default_value_t = DEFAULT_ACCOUNT_PROOF_MAX_DEPTH)]
+    acct_max_depth: usize,
+    /// Max depth of the storage proofs, part of the circuit shape
+    #[arg(long, default_value_t = DEFAULT_STORAGE_PROOF_MAX_DEPTH)]
+    storage_max_depth: usize,
+    /// Configuration of the storage circuit. If not given, the smallest configuration that fits the input is
+    /// computed and written to `storage.config.json` in the output directory
+    #[arg(long)]
+    storage_config: Option<String>,
+    /// Configuration of the aggregation circuit that is verified on chain
+    #[arg(long, default_value = "configs/storage_evm.json")]
+    evm_config: String,
+    /// Directory to write `storage.yul`, `storage.bytecode` and `storage.calldata` to
+    #[arg(long, default_value = "data/storage")]
+    out: PathBuf,
+    /// Also verify the calldata with the generated contract in an embedded EVM
+    #[arg(long)]
+    verify: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mut circuit = EthBlockStorageCircuit::<Fr>::from_json(&cli.input)
+        .with_max_depths(cli.acct_max_depth, cli.storage_max_depth)
+        .unwrap_or_else(|e| panic!("{e}"));
+    if let Some(max_slots) = cli.max_slots {
+        circuit = circuit.with_max_slots(max_slots).unwrap_or_else(|e| panic!("{e}"));
+    }
+    std::fs::create_dir_all(&cli.out).expect("could not create output directory");
+    let storage_config = match &cli.storage_config {
+        Some(path) => EthConfigParams::from_path(path),
+        None => {
+            let config = circuit.sized_config_params(&SizingOptions::default());
+            let path = cli.out.join("storage.config.json");
+            std::fs::write(&path, serde_json::to_string_pretty(&config).unwrap())
+                .expect("could not write storage config");
+            println!("Sized storage circuit to {config:?}, written to {path:?}");
+            config
+        }
+    };
+    let storage_params = get_srs(storage_config.degree);
+    let evm_config: AggregationConfigParams = read_config(&cli.evm_config);
+    let evm_params = get_srs(evm_config.degree);
+
+    let yul_path = cli.out.join("storage.yul");
+    let mut rng = ChaCha20Rng::from_entropy();
+    let artifacts = gen_storage_evm_artifacts(
+        &storage_params,
+        &storage_config,
+        &evm_params,
+        &evm_config,
+        circuit,
+        &KeyStore::default(),
+        Some(&yul_path),
+        &mut rng,
+    );
+    artifacts.write(&cli.out).expect("could not write artifacts");
+    println!("Wrote verifier and calldata to {:?}", cli.out);
+
+    if cli.verify {
+        let outcome = deploy_and_call(artifacts.deployment_code, artifacts.calldata);
+        assert!(!outcome.reverted, "verifier contract rejected the proof");
+        println!("Proof verified in EVM, gas used: {}", outcome.gas_used);
+    }
+}
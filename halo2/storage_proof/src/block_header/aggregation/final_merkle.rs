@@ -12,9 +12,12 @@ use halo2_base::{
 };
 
 use crate::{
-    keccak::{KeccakChip, KeccakConfig},
+    keccak::{configure_keccak, load_keccak_aux_tables, KeccakChip, KeccakConfig, KeccakParams},
     rlp::rlc::{RlcChip, RlcConfig},
-    util::{bytes_be_to_u128, get_merkle_mountain_range, num_to_bytes_be, NUM_BYTES_IN_U128},
+    util::{
+        bytes_be_to_u128, circuit_params, get_merkle_mountain_range, num_to_bytes_be, read_config,
+        with_circuit_params, NUM_BYTES_IN_U128,
+    },
 };
 use itertools::Itertools;
 use rand::Rng;
@@ -23,14 +26,11 @@ use snark_verifier_sdk::{
     halo2::aggregation::{AggregationConfig, AggregationConfigParams},
     CircuitExt, Snark, LIMBS,
 };
-use std::{
-    env::{set_var, var},
-    fs::File,
-};
+use std::path::Path;
 
 use super::EthBlockHeaderChainAggregationCircuit;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AggregationWithKeccakConfigParams {
     pub aggregation: AggregationConfigParams,
     pub num_rlc_columns: usize,
@@ -38,18 +38,28 @@ pub struct AggregationWithKeccakConfigParams {
     pub keccak_rows_per_round: usize,
 }
 impl AggregationWithKeccakConfigParams {
-    pub fn get() -> Self {
-        let path = var("FINAL_AGGREGATION_CONFIG").expect("FINAL_AGGREGATION_CONFIG not set");
-        serde_json::from_reader(
-            File::open(&path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
-        )
-        .unwrap()
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        read_config(path)
+    }
+
+    pub fn keccak_params(&self) -> KeccakParams {
+        KeccakParams {
+            degree: self.aggregation.degree,
+            rows_per_round: self.keccak_rows_per_round,
+            unusable_rows: self.unusable_rows,
+        }
+    }
+
+    /// Runs `f` with `self` as the params used by `EthBlockHeaderChainFinalAggregationCircuit::configure`.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_circuit_params(self, f)
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct AggregationWithKeccakConfig {
     pub keccak: KeccakConfig<Fr>,
+    pub keccak_params: KeccakParams,
     pub rlc: RlcConfig<Fr>,
     pub aggregation: AggregationConfig,
 }
@@ -60,16 +70,14 @@ impl AggregationWithKeccakConfig {
         params: AggregationWithKeccakConfigParams,
     ) -> Self {
         let degree = params.aggregation.degree;
+        let keccak_params = params.keccak_params();
         let mut aggregation = AggregationConfig::configure(meta, params.aggregation);
-        set_var("KECCAK_DEGREE", degree.to_string());
-        set_var("KECCAK_ROWS", params.keccak_rows_per_round.to_string());
-        set_var("UNUSABLE_ROWS", params.unusable_rows.to_string());
         let rlc = RlcConfig::configure(meta, params.num_rlc_columns, 1);
-        let keccak = KeccakConfig::new(meta, rlc.gamma);
+        let keccak = configure_keccak(meta, rlc.gamma, keccak_params);
         #[cfg(feature = "display")]
         println!("Unusable rows: {}", meta.minimum_rows());
         aggregation.base_field_config.range.gate.max_rows = (1 << degree) - meta.minimum_rows();
-        Self { keccak, aggregation, rlc }
+        Self { keccak, keccak_params, aggregation, rlc }
     }
 
     pub fn gate(&self) -> &FlexGateConfig<Fr> {
@@ -134,7 +142,8 @@ impl Circuit<Fr> for EthBlockHeaderChainFinalAggregationCircuit {
     }
 
     fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
-        let params = AggregationWithKeccakConfigParams::get();
+        let params = circuit_params::<AggregationWithKeccakConfigParams>()
+            .expect("configure must be called inside `AggregationWithKeccakConfigParams::scope`");
         AggregationWithKeccakConfig::configure(meta, params)
     }
 
@@ -152,7 +161,8 @@ impl Circuit<Fr> for EthBlockHeaderChainFinalAggregationCircuit {
             self.0.initial_depth
         ));
         config.range().load_lookup_table(&mut layouter).expect("load range lookup table");
-        config.keccak.load_aux_tables(&mut layouter).expect("load keccak lookup table");
+        load_keccak_aux_tables(&config.keccak, config.keccak_params, &mut layouter)
+            .expect("load keccak lookup table");
         let gamma = layouter.get_challenge(config.rlc.gamma);
         let mut first_pass = halo2_base::SKIP_FIRST_PASS;
         let mut instances = Vec::new();
@@ -200,7 +210,8 @@ impl Circuit<Fr> for EthBlockHeaderChainFinalAggregationCircuit {
                         .collect_vec();
 
                     let mut rlc_chip = RlcChip::new(config.rlc.clone(), gamma);
-                    let mut keccak_chip = KeccakChip::new(config.keccak.clone());
+                    let mut keccak_chip =
+                        KeccakChip::new(config.keccak.clone(), config.keccak_params);
                     let new_mmr = keccak_chip.merkle_mountain_range(
                         ctx,
                         config.gate(),
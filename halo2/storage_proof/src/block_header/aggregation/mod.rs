@@ -1,5 +1,5 @@
 use super::EthBlockHeaderChainInstance;
-use crate::Field;
+use crate::{util::circuit_params, Field};
 #[cfg(feature = "display")]
 use ark_std::{end_timer, start_timer};
 use halo2_base::{
@@ -7,7 +7,7 @@ use halo2_base::{
     halo2_proofs::{
         circuit::{Layouter, Region, SimpleFloorPlanner, Value},
         halo2curves::bn256::{Bn256, Fr},
-        plonk::{Circuit, ConstraintSystem, Error},
+        plonk::{Circuit, ConstraintSystem, Error, Selector},
         poly::kzg::commitment::ParamsKZG,
     },
     utils::{PrimeField, ScalarField},
@@ -19,7 +19,8 @@ use rand::Rng;
 use snark_verifier::pcs::kzg::{Bdfg21, Kzg};
 use snark_verifier_sdk::{
     halo2::aggregation::{
-        aggregate, flatten_accumulator, AggregationCircuit, AggregationConfig, Halo2Loader,
+        aggregate, flatten_accumulator, AggregationCircuit, AggregationConfig,
+        AggregationConfigParams, Halo2Loader, PublicAggregationCircuit,
     },
     CircuitExt, Snark, LIMBS,
 };
@@ -28,6 +29,55 @@ use std::rc::Rc;
 mod final_merkle;
 pub use final_merkle::*;
 
+/// Configures an aggregation circuit with the `AggregationConfigParams` of the enclosing
+/// `util::with_circuit_params` scope.
+pub fn configure_aggregation(meta: &mut ConstraintSystem<Fr>) -> AggregationConfig {
+    let params = circuit_params::<AggregationConfigParams>().expect(
+        "configure must be called inside `with_circuit_params` with `AggregationConfigParams`",
+    );
+    AggregationConfig::configure(meta, params)
+}
+
+/// `PublicAggregationCircuit` whose `configure` reads its params from the enclosing `util::with_circuit_params`
+/// scope instead of the `VERIFY_CONFIG` environment variable.
+#[derive(Clone)]
+pub struct PublicAggregationCircuitWithParams(pub PublicAggregationCircuit);
+
+impl Circuit<Fr> for PublicAggregationCircuitWithParams {
+    type Config = AggregationConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self(self.0.without_witnesses())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        configure_aggregation(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fr>) -> Result<(), Error> {
+        self.0.synthesize(config, layouter)
+    }
+}
+
+impl CircuitExt<Fr> for PublicAggregationCircuitWithParams {
+    fn num_instance(&self) -> Vec<usize> {
+        self.0.num_instance()
+    }
+
+    fn instances(&self) -> Vec<Vec<Fr>> {
+        self.0.instances()
+    }
+
+    fn accumulator_indices() -> Option<Vec<(usize, usize)>> {
+        PublicAggregationCircuit::accumulator_indices()
+    }
+
+    fn selectors(config: &Self::Config) -> Vec<Selector> {
+        PublicAggregationCircuit::selectors(config)
+    }
+}
+
 #[derive(Clone)]
 pub struct EthBlockHeaderChainAggregationCircuit {
     // aggregation circuit with `instances` the accumulator (two G1 points) for delayed pairing verification
@@ -165,7 +215,7 @@ impl Circuit<Fr> for EthBlockHeaderChainAggregationCircuit {
     }
 
     fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
-        AggregationCircuit::configure(meta)
+        configure_aggregation(meta)
     }
 
     fn synthesize(
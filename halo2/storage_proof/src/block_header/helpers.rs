@@ -1,13 +1,15 @@
 use super::{
     aggregation::{
         AggregationWithKeccakConfigParams, EthBlockHeaderChainAggregationCircuit,
-        EthBlockHeaderChainFinalAggregationCircuit,
+        EthBlockHeaderChainFinalAggregationCircuit, PublicAggregationCircuitWithParams,
     },
     EthBlockHeaderChainCircuit,
 };
 use crate::{
+    keystore::{CircuitFingerprint, KeyStore},
+    params::get_srs,
     providers::{GOERLI_PROVIDER_URL, MAINNET_PROVIDER_URL},
-    util::EthConfigParams,
+    util::{read_config, with_circuit_params, EthConfigParams},
     Field, Network,
 };
 use core::cmp::min;
@@ -18,26 +20,21 @@ use halo2_base::{
         plonk::ProvingKey,
         poly::kzg::commitment::ParamsKZG,
     },
-    utils::{fs::gen_srs, PrimeField},
+    utils::PrimeField,
 };
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 use snark_verifier_sdk::{
-    gen_pk,
     halo2::{
-        aggregation::{load_verify_circuit_degree, PublicAggregationCircuit},
+        aggregation::{AggregationConfigParams, PublicAggregationCircuit},
         gen_snark_shplonk, read_snark,
     },
     CircuitExt, Snark, LIMBS,
 };
-use std::{
-    collections::HashMap,
-    env::{set_var, var},
-    path::Path,
-    vec,
-};
+use std::{collections::HashMap, env::var, path::Path, vec};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Finality {
     /// Produces as many snarks as needed to fit the entire block number range, without any final processing.
     None,
@@ -50,7 +47,7 @@ pub enum Finality {
     Evm(usize),
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CircuitType {
     pub depth: usize,
     pub initial_depth: usize,
@@ -97,9 +94,18 @@ impl CircuitType {
     pub fn pkey_name(&self, network: Network) -> String {
         format!("{}{}.pkey", self.fname_prefix(network), self.fname_suffix())
     }
+
+    /// Key store fingerprint of the circuit of this type.
+    pub fn fingerprint(&self, network: Network, k: u32) -> CircuitFingerprint {
+        let name = format!("headers_{}_{}{}", self.depth, self.initial_depth, self.fname_suffix());
+        CircuitFingerprint::new(name, k)
+            .network(network)
+            .config(self)
+            .max_depths([self.depth, self.initial_depth])
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Task {
     pub start: u32,
     pub end: u32,
@@ -125,17 +131,94 @@ impl Task {
         assert!(self.end - self.start < 1 << self.circuit_type.depth);
         read_snark(self.snark_name(network))
     }
+
+    /// Path of the job state file used by the scheduler when `self` is the top-level task.
+    pub fn job_state_name(&self, network: Network) -> String {
+        format!(
+            "{}_{:06x}_{:06x}{}.jobs.json",
+            self.circuit_type.fname_prefix(network),
+            self.start,
+            self.end,
+            self.circuit_type.fname_suffix()
+        )
+    }
+
+    /// The tasks whose snarks must exist before a circuit for `self` can be created.
+    /// This is the same split that `Sequencer::get_circuit` performs; initial depth tasks have no dependencies.
+    pub fn dependencies(&self) -> Vec<Task> {
+        let CircuitType { depth, initial_depth, .. } = self.circuit_type;
+        if depth == initial_depth {
+            return vec![];
+        }
+        let prev_type = self.circuit_type.prev();
+        let prev_depth = prev_type.depth;
+        (self.start..=self.end)
+            .step_by(1 << prev_depth)
+            .map(|i| Task::new(i, min(self.end, i + (1 << prev_depth) - 1), prev_type))
+            .collect()
+    }
 }
 
 pub enum AnyCircuit {
     Initial(EthBlockHeaderChainCircuit<Fr>),
     Intermediate(EthBlockHeaderChainAggregationCircuit),
     Final(EthBlockHeaderChainFinalAggregationCircuit),
-    ForEvm(PublicAggregationCircuit),
+    ForEvm(PublicAggregationCircuitWithParams),
+}
+
+/// Configuration params of a circuit type, as read from `configs/headers`.
+#[derive(Clone, Debug)]
+pub enum CircuitConfig {
+    Header(EthConfigParams),
+    Aggregation(AggregationConfigParams),
+    Final(AggregationWithKeccakConfigParams),
+}
+
+impl CircuitConfig {
+    /// Reads the config of `circuit_type` for `network` from `configs/headers`.
+    pub fn load(network: Network, circuit_type: CircuitType) -> Self {
+        let CircuitType { depth, initial_depth, finality } = circuit_type;
+        if depth == initial_depth {
+            return Self::Header(EthConfigParams::from_path(format!(
+                "configs/headers/{network}_{depth}.json"
+            )));
+        }
+        let fname_prefix = format!("configs/headers/{network}_{depth}_{initial_depth}");
+        match finality {
+            Finality::None => Self::Aggregation(read_config(format!("{fname_prefix}.json"))),
+            Finality::Merkle => Self::Final(AggregationWithKeccakConfigParams::from_path(format!(
+                "{fname_prefix}_final.json"
+            ))),
+            Finality::Evm(round) => {
+                Self::Aggregation(read_config(format!("{fname_prefix}_for_evm_{round}.json")))
+            }
+        }
+    }
+
+    pub fn degree(&self) -> u32 {
+        match self {
+            Self::Header(params) => params.degree,
+            Self::Aggregation(params) => params.degree,
+            Self::Final(params) => params.aggregation.degree,
+        }
+    }
+
+    /// Runs `f` with these params available to the `configure` of the corresponding circuit.
+    /// Keygen, reading keys and proving must all happen inside the scope.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self {
+            Self::Header(params) => params.scope(f),
+            Self::Aggregation(params) => with_circuit_params(params, f),
+            Self::Final(params) => params.scope(f),
+        }
+    }
 }
 
 pub struct Sequencer {
+    /// Proving keys loaded in this session; they are persisted in `keystore`.
     pub pkeys: HashMap<CircuitType, ProvingKey<G1Affine>>,
+    pub keystore: KeyStore,
+    pub configs: HashMap<CircuitType, CircuitConfig>,
     pub params_k: HashMap<CircuitType, u32>,
     pub params: HashMap<u32, ParamsKZG<Bn256>>,
     pub rng: ChaCha20Rng,
@@ -155,6 +238,8 @@ impl Sequencer {
 
         Sequencer {
             pkeys: HashMap::new(),
+            keystore: KeyStore::default(),
+            configs: HashMap::new(),
             params_k: HashMap::new(),
             params: HashMap::new(),
             provider,
@@ -163,34 +248,15 @@ impl Sequencer {
         }
     }
 
+    /// Loads the config of `circuit_type` and the trusted setup for its degree, returning the degree.
     pub fn get_params(&mut self, circuit_type: CircuitType) -> u32 {
         let network = self.network;
-        let CircuitType { depth, initial_depth, finality } = circuit_type;
-        let fname_prefix = if depth == initial_depth {
-            format!("configs/headers/{network}_{depth}")
-        } else {
-            format!("configs/headers/{network}_{depth}_{initial_depth}")
-        };
-        let k = if depth == initial_depth {
-            set_var("BLOCK_HEADER_CONFIG", format!("{fname_prefix}.json"));
-            EthConfigParams::get_header().degree
-        } else {
-            match finality {
-                Finality::None => {
-                    set_var("VERIFY_CONFIG", format!("{fname_prefix}.json"));
-                    load_verify_circuit_degree()
-                }
-                Finality::Merkle => {
-                    set_var("FINAL_AGGREGATION_CONFIG", format!("{fname_prefix}_final.json"));
-                    AggregationWithKeccakConfigParams::get().aggregation.degree
-                }
-                Finality::Evm(round) => {
-                    set_var("VERIFY_CONFIG", format!("{fname_prefix}_for_evm_{round}.json"));
-                    load_verify_circuit_degree()
-                }
-            }
-        };
-        self.params.entry(k).or_insert_with(|| gen_srs(k));
+        let config = self
+            .configs
+            .entry(circuit_type)
+            .or_insert_with(|| CircuitConfig::load(network, circuit_type));
+        let k = config.degree();
+        self.params.entry(k).or_insert_with(|| get_srs(k));
         self.params_k.insert(circuit_type, k);
         k
     }
@@ -201,7 +267,6 @@ impl Sequencer {
         let CircuitType { depth, initial_depth, finality } = circuit_type;
         assert!(end - start < 1 << depth);
         if depth == initial_depth {
-            // set environmental vars
             self.get_params(circuit_type);
             let circuit = EthBlockHeaderChainCircuit::from_provider(
                 &self.provider,
@@ -212,15 +277,8 @@ impl Sequencer {
             );
             AnyCircuit::Initial(circuit)
         } else {
-            let prev_type = circuit_type.prev();
-            let mut snarks: Vec<Snark>;
-            let prev_depth = prev_type.depth;
-            snarks = (start..=end)
-                .step_by(1 << prev_depth)
-                .map(|i| {
-                    self.get_snark(Task::new(i, min(end, i + (1 << prev_depth) - 1), prev_type))
-                })
-                .collect();
+            let mut snarks: Vec<Snark> =
+                task.dependencies().into_iter().map(|dep| self.get_snark(dep)).collect();
             if (finality == Finality::None || finality == Finality::Merkle) && snarks.len() != 2 {
                 snarks.push(snarks[0].clone());
             }
@@ -252,7 +310,7 @@ impl Sequencer {
                 }
                 Finality::Evm(_) => {
                     let circuit = PublicAggregationCircuit::new(params, snarks, true, &mut rng);
-                    AnyCircuit::ForEvm(circuit)
+                    AnyCircuit::ForEvm(PublicAggregationCircuitWithParams(circuit))
                 }
             }
         }
@@ -266,35 +324,47 @@ impl Sequencer {
         }
         let circuit = self.get_circuit(task);
         let circuit_type = task.circuit_type;
-        let params = &self.params[&self.params_k[&circuit_type]];
-        let pk_name = circuit_type.pkey_name(network);
-        let pk_path = Some(Path::new(&pk_name));
-        let pk = self.pkeys.entry(circuit_type).or_insert_with(|| {
-            // as you can see we do the same thing for each circuit, but because `Circuit` is
-            // not an object-safe trait we can't put it in a `Box`
-            match &circuit {
-                AnyCircuit::Initial(circuit) => gen_pk(params, circuit, pk_path),
-                AnyCircuit::Intermediate(circuit) => gen_pk(params, circuit, pk_path),
-                AnyCircuit::Final(circuit) => gen_pk(params, circuit, pk_path),
-                AnyCircuit::ForEvm(circuit) => gen_pk(params, circuit, pk_path),
-            }
-        });
-        let snark_path = Some(task.snark_name(network));
+        let k = self.params_k[&circuit_type];
+        let params = &self.params[&k];
+        let keystore = &self.keystore;
+        let pkeys = &mut self.pkeys;
         let mut rng = self.rng.clone();
-        match circuit {
-            AnyCircuit::Initial(circuit) => {
-                gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
-            }
-            AnyCircuit::Intermediate(circuit) => {
-                gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
-            }
-            AnyCircuit::Final(circuit) => {
-                gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
-            }
-            AnyCircuit::ForEvm(circuit) => {
-                gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
+        self.configs[&circuit_type].scope(|| {
+            let pk = pkeys.entry(circuit_type).or_insert_with(|| {
+                let fingerprint = circuit_type.fingerprint(network, k);
+                // as you can see we do the same thing for each circuit, but because `Circuit` is
+                // not an object-safe trait we can't put it in a `Box`
+                match &circuit {
+                    AnyCircuit::Initial(circuit) => {
+                        keystore.get_or_gen_pk(params, circuit, &fingerprint)
+                    }
+                    AnyCircuit::Intermediate(circuit) => {
+                        keystore.get_or_gen_pk(params, circuit, &fingerprint)
+                    }
+                    AnyCircuit::Final(circuit) => {
+                        keystore.get_or_gen_pk(params, circuit, &fingerprint)
+                    }
+                    AnyCircuit::ForEvm(circuit) => {
+                        keystore.get_or_gen_pk(params, circuit, &fingerprint)
+                    }
+                }
+            });
+            let snark_path = Some(task.snark_name(network));
+            match circuit {
+                AnyCircuit::Initial(circuit) => {
+                    gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
+                }
+                AnyCircuit::Intermediate(circuit) => {
+                    gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
+                }
+                AnyCircuit::Final(circuit) => {
+                    gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
+                }
+                AnyCircuit::ForEvm(circuit) => {
+                    gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path)
+                }
             }
-        }
+        })
     }
 
     #[cfg(feature = "evm")]
@@ -335,32 +405,38 @@ impl Sequencer {
         };
         use std::fs;
 
-        let params = &self.params[&self.params_k[&circuit_type]];
-        let pk_name = circuit_type.pkey_name(self.network);
-        let pk_path = Some(Path::new(&pk_name));
-        let pk =
-            self.pkeys.entry(circuit_type).or_insert_with(|| gen_pk(params, &circuit, pk_path));
-        let instances = circuit.instances();
+        let network = self.network;
+        let k = self.params_k[&circuit_type];
+        let params = &self.params[&k];
+        let keystore = &self.keystore;
+        let pkeys = &mut self.pkeys;
         let mut rng = self.rng.clone();
-        let proof = gen_evm_proof_shplonk(params, pk, circuit, instances.clone(), &mut rng);
-        let calldata = encode_calldata(&instances, &proof);
-        fs::write(path, hex::encode(&calldata)).expect("write calldata should not fail");
-
-        if generate_smart_contract {
-            let num_instances = instances[0].len();
-            let deployment_code = gen_evm_verifier_shplonk::<ConcreteCircuit>(
-                params,
-                pk.get_vk(),
-                vec![num_instances],
-                Some(Path::new(&format!(
-                    "data/headers/{}_{}_{}.yul",
-                    self.network, circuit_type.depth, circuit_type.initial_depth
-                ))),
-            );
-
-            evm_verify(deployment_code, instances, proof);
-        }
-        calldata
+        self.configs[&circuit_type].scope(|| {
+            let fingerprint = circuit_type.fingerprint(network, k);
+            let pk = pkeys
+                .entry(circuit_type)
+                .or_insert_with(|| keystore.get_or_gen_pk(params, &circuit, &fingerprint));
+            let instances = circuit.instances();
+            let proof = gen_evm_proof_shplonk(params, pk, circuit, instances.clone(), &mut rng);
+            let calldata = encode_calldata(&instances, &proof);
+            fs::write(path, hex::encode(&calldata)).expect("write calldata should not fail");
+
+            if generate_smart_contract {
+                let num_instances = instances[0].len();
+                let deployment_code = gen_evm_verifier_shplonk::<ConcreteCircuit>(
+                    params,
+                    pk.get_vk(),
+                    vec![num_instances],
+                    Some(Path::new(&format!(
+                        "data/headers/{}_{}_{}.yul",
+                        network, circuit_type.depth, circuit_type.initial_depth
+                    ))),
+                );
+
+                evm_verify(deployment_code, instances, proof);
+            }
+            calldata
+        })
     }
 }
 
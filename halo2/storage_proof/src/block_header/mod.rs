@@ -7,6 +7,7 @@ use crate::{
         rlc::{RlcFixedTrace, RlcTrace, RLC_PHASE},
         RlpArrayTraceWitness, RlpFieldTrace,
     },
+    sizing,
     util::{bytes_be_var_to_fixed, decode_field_to_h256},
     EthChip, EthConfig,
 };
@@ -37,24 +38,34 @@ use serde::{Deserialize, Serialize};
 pub mod aggregation;
 #[cfg(all(feature = "aggregation", feature = "providers"))]
 pub mod helpers;
+#[cfg(all(feature = "aggregation", feature = "providers"))]
+pub mod scheduler;
 #[cfg(test)]
 mod tests;
 
 const MAINNET_EXTRA_DATA_MAX_BYTES: usize = 32;
 const MAINNET_EXTRA_DATA_RLP_MAX_BYTES: usize = MAINNET_EXTRA_DATA_MAX_BYTES + 1;
+// withdrawalsRoot (Shanghai), blobGasUsed, excessBlobGas, parentBeaconBlockRoot (Dencun)
+const POST_LONDON_FIELDS_RLP_MAX_BYTES: usize = 33 + 9 + 9 + 33;
 pub const MAINNET_BLOCK_HEADER_RLP_MAX_BYTES: usize =
-    1 + 2 + 521 + MAINNET_EXTRA_DATA_RLP_MAX_BYTES;
+    1 + 2 + 521 + MAINNET_EXTRA_DATA_RLP_MAX_BYTES + POST_LONDON_FIELDS_RLP_MAX_BYTES;
 const GOERLI_EXTRA_DATA_MAX_BYTES: usize = 97;
 const GOERLI_EXTRA_DATA_RLP_MAX_BYTES: usize = GOERLI_EXTRA_DATA_MAX_BYTES + 1;
-pub const GOERLI_BLOCK_HEADER_RLP_MAX_BYTES: usize = 1 + 2 + 521 + GOERLI_EXTRA_DATA_RLP_MAX_BYTES;
+pub const GOERLI_BLOCK_HEADER_RLP_MAX_BYTES: usize =
+    1 + 2 + 521 + GOERLI_EXTRA_DATA_RLP_MAX_BYTES + POST_LONDON_FIELDS_RLP_MAX_BYTES;
 const BLOCK_HEADER_RLP_MIN_BYTES: usize = 479;
 
-const NUM_BLOCK_HEADER_FIELDS: usize = 16;
-const MAINNET_HEADER_FIELDS_MAX_BYTES: [usize; NUM_BLOCK_HEADER_FIELDS] =
-    [32, 32, 20, 32, 32, 32, 256, 7, 4, 4, 4, 4, MAINNET_EXTRA_DATA_MAX_BYTES, 32, 8, 6];
-const GOERLI_HEADER_FIELDS_MAX_BYTES: [usize; NUM_BLOCK_HEADER_FIELDS] =
-    [32, 32, 20, 32, 32, 32, 256, 7, 4, 4, 4, 4, GOERLI_EXTRA_DATA_MAX_BYTES, 32, 8, 6];
+const NUM_BLOCK_HEADER_FIELDS: usize = 20;
+const MAINNET_HEADER_FIELDS_MAX_BYTES: [usize; NUM_BLOCK_HEADER_FIELDS] = [
+    32, 32, 20, 32, 32, 32, 256, 7, 4, 4, 4, 4, MAINNET_EXTRA_DATA_MAX_BYTES, 32, 8, 6, 32, 8, 8, 32,
+];
+const GOERLI_HEADER_FIELDS_MAX_BYTES: [usize; NUM_BLOCK_HEADER_FIELDS] = [
+    32, 32, 20, 32, 32, 32, 256, 7, 4, 4, 4, 4, GOERLI_EXTRA_DATA_MAX_BYTES, 32, 8, 6, 32, 8, 8, 32,
+];
 pub const BLOCK_NUMBER_MAX_BYTES: usize = MAINNET_HEADER_FIELDS_MAX_BYTES[8];
+pub const TIMESTAMP_MAX_BYTES: usize = MAINNET_HEADER_FIELDS_MAX_BYTES[11];
+/// Index of `parentBeaconBlockRoot` (EIP-4788) in the header RLP list.
+pub const PARENT_BEACON_BLOCK_ROOT_IDX: usize = 19;
 
 // Field        Type        Size (bytes) RLP size (bytes) RLP size (bits)
 // parentHash	256 bits	32	33	264
@@ -73,6 +84,10 @@ pub const BLOCK_NUMBER_MAX_BYTES: usize = MAINNET_HEADER_FIELDS_MAX_BYTES[8];
 // mixHash	256 bits	32	33	264
 // nonce	64 bits	8	9	72
 // basefee (post-1559)	big int scalar	variable	<= 6	<= 48
+// withdrawalsRoot (post-Shanghai)	256 bits	32	33	264
+// blobGasUsed (post-Dencun)	big int scalar	variable	<= 9	<= 72
+// excessBlobGas (post-Dencun)	big int scalar	variable	<= 9	<= 72
+// parentBeaconBlockRoot (post-Dencun)	256 bits	32	33	264
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct EthBlockHeaderTrace<'v, F: Field> {
@@ -94,6 +109,11 @@ pub struct EthBlockHeaderTrace<'v, F: Field> {
     pub mix_hash: RlpFieldTrace<'v, F>,
     pub nonce: RlpFieldTrace<'v, F>,
     pub basefee: Option<RlpFieldTrace<'v, F>>,
+    // The fields below are empty (`field_trace.len = 0`) in headers from before the fork that added them
+    pub withdrawals_root: Option<RlpFieldTrace<'v, F>>,
+    pub blob_gas_used: Option<RlpFieldTrace<'v, F>>,
+    pub excess_blob_gas: Option<RlpFieldTrace<'v, F>>,
+    pub parent_beacon_block_root: Option<RlpFieldTrace<'v, F>>,
 
     pub block_hash: RlcFixedTrace<'v, F>,
 
@@ -106,6 +126,26 @@ pub struct EthBlockHeaderTraceWitness<'v, F: Field> {
     pub block_hash_query_idx: usize,
 }
 
+impl<'v, F: Field> EthBlockHeaderTraceWitness<'v, F> {
+    /// The bytes of `parentBeaconBlockRoot` (EIP-4788). Constrains the field to have 32 bytes, i.e. the header to
+    /// be from after Dencun.
+    pub fn parent_beacon_block_root(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> &[AssignedValue<'v, F>] {
+        let field = &self.rlp_witness.field_witness[PARENT_BEACON_BLOCK_ROOT_IDX];
+        gate.assert_is_const(ctx, &field.field_len, gate.get_field_element(32));
+        &field.field_cells
+    }
+}
+
+/// The `parentBeaconBlockRoot` of an RLP encoded block header, if the header is from after Dencun.
+pub fn parent_beacon_block_root(block_header: &[u8]) -> Option<H256> {
+    let root: Vec<u8> = rlp::Rlp::new(block_header).val_at(PARENT_BEACON_BLOCK_ROOT_IDX).ok()?;
+    (root.len() == 32).then(|| H256::from_slice(&root))
+}
+
 pub trait EthBlockHeaderChip<'v, F: Field> {
     /// Takes the variable length RLP encoded block header, padded with 0s to the maximum possible block header RLP length, and outputs the decomposition into block header fields.
     ///
@@ -141,6 +181,10 @@ pub trait EthBlockHeaderChip<'v, F: Field> {
     /// decomposes each header into it's fields.
     /// `headers[0]` is the earliest block.
     ///
+    /// Every header is assigned and hashed on its own, including the dummy headers that pad a chain to
+    /// `2^max_depth`: which headers are dummies depends on `num_blocks`, a witness, while the keccak queries must be
+    /// the same for every witness.
+    ///
     /// This is the preparation step that computes the witnesses. This MUST be done in `FirstPhase`.
     /// The accompanying `decompose_block_header_chain_phase1` must be called in `SecondPhase` to constrain the RLCs associated to the RLP decoding.
     fn decompose_block_header_chain_phase0(
@@ -195,8 +239,10 @@ impl<'v, F: Field> EthBlockHeaderChip<'v, F> for EthChip<'v, F> {
             ctx,
             block_header.iter().map(|byte| Value::known(F::from(*byte as u64))),
         );
-        let rlp_witness =
-            self.rlp().decompose_rlp_array_phase0(ctx, block_header_assigned, max_field_lens, true); // `is_variable_len = true` because RLP can have either 15 or 16 fields, depending on whether block is pre-London or not
+        // `is_variable_len = true` because RLP can have 15 to 20 fields, depending on the fork of the block
+        let rlp_witness = self.mpt.rlp.with_strict(|rlp| {
+            rlp.decompose_rlp_array_phase0(ctx, block_header_assigned, max_field_lens, true)
+        });
 
         let block_hash_query_idx = self.mpt.keccak.keccak_var_len(
             ctx,
@@ -218,6 +264,11 @@ impl<'v, F: Field> EthBlockHeaderChip<'v, F> for EthChip<'v, F> {
         let mut trace = self.mpt.rlp.decompose_rlp_array_phase1(ctx, witness.rlp_witness, true);
         let block_hash = self.keccak().var_len_rlcs[witness.block_hash_query_idx].1.clone();
 
+        // Fields added after London, see `EthBlockHeaderTrace`
+        let parent_beacon_block_root = trace.field_trace.pop();
+        let excess_blob_gas = trace.field_trace.pop();
+        let blob_gas_used = trace.field_trace.pop();
+        let withdrawals_root = trace.field_trace.pop();
         // Base fee per unit gas only after London
         let basefee = trace.field_trace.pop();
         let [parent_hash, ommers_hash, beneficiary, state_root, transactions_root, receipts_root, logs_bloom, difficulty, number, gas_limit, gas_used, timestamp, extra_data, mix_hash, nonce]: [RlpFieldTrace<F>; 15] =
@@ -240,6 +291,10 @@ impl<'v, F: Field> EthBlockHeaderChip<'v, F> for EthChip<'v, F> {
             mix_hash,
             nonce,
             basefee,
+            withdrawals_root,
+            blob_gas_used,
+            excess_blob_gas,
+            parent_beacon_block_root,
             block_hash,
             len_trace: trace.len_trace,
         }
@@ -461,7 +516,8 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderChainCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let params = EthConfigParams::get_header();
+        let params = EthConfigParams::current()
+            .expect("configure must be called inside `EthConfigParams::scope`");
         EthConfig::configure(meta, params, 0)
     }
 
@@ -475,7 +531,7 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderChainCircuit<F> {
 
         let gamma = layouter.get_challenge(config.rlc().gamma);
         config.range().load_lookup_table(&mut layouter).expect("load range lookup table");
-        config.keccak().load_aux_tables(&mut layouter).expect("load keccak lookup tables");
+        config.load_keccak_aux_tables(&mut layouter).expect("load keccak lookup tables");
         let instance_column = config.instance;
 
         let mut first_pass = SKIP_FIRST_PASS;
@@ -567,6 +623,7 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderChainCircuit<F> {
                         &block_chain_trace,
                         &num_blocks_minus_one,
                     );
+                    sizing::record_phase(ctx);
                     chip.range().finalize(ctx);
 
                     instances.extend(
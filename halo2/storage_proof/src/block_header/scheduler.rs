@@ -0,0 +1,426 @@
+//! Resumable scheduling of the header chain proving pipeline.
+//!
+//! A top-level [`Task`] is expanded into a DAG of all the tasks whose snarks are needed to prove it.
+//! The status of every task is persisted to a JSON job state file after each change, so that a crashed
+//! (e.g., OOM-killed) run can be resumed without losing the snarks that were already produced.
+//! Tasks at the initial depth have no dependencies and can be proven in parallel.
+use super::{
+    helpers::{CircuitType, Sequencer, Task},
+    EthBlockHeaderChainCircuit,
+};
+use crate::Network;
+use ethers_providers::{Http, Provider};
+use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use snark_verifier_sdk::{halo2::gen_snark_shplonk, Snark};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Pending,
+    /// The job was started but has not finished. If this is read from a state file, the previous run crashed.
+    Running,
+    Done,
+    /// The job failed more than the allowed number of retries.
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub task: Task,
+    /// Indices into `JobState::jobs` of the jobs this job depends on.
+    pub deps: Vec<usize>,
+    pub status: JobStatus,
+    pub attempts: usize,
+    pub last_error: Option<String>,
+}
+
+/// Persisted state of a proving run. `jobs` is topologically sorted: every job comes after all of its dependencies,
+/// and the last job is the top-level task.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobState {
+    pub network: Network,
+    pub root: Task,
+    pub jobs: Vec<Job>,
+}
+
+impl JobState {
+    /// Builds the task DAG for `root`. Tasks are deduplicated, so a task shared by several parents appears once.
+    pub fn new(network: Network, root: Task) -> Self {
+        fn visit(task: Task, jobs: &mut Vec<Job>, index: &mut HashMap<Task, usize>) -> usize {
+            if let Some(&idx) = index.get(&task) {
+                return idx;
+            }
+            let deps = task.dependencies().into_iter().map(|dep| visit(dep, jobs, index)).collect();
+            jobs.push(Job {
+                task,
+                deps,
+                status: JobStatus::Pending,
+                attempts: 0,
+                last_error: None,
+            });
+            index.insert(task, jobs.len() - 1);
+            jobs.len() - 1
+        }
+        let mut jobs = vec![];
+        visit(root, &mut jobs, &mut HashMap::new());
+        Self { network, root, jobs }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let f = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(f))?)
+    }
+
+    /// Writes to a temporary file first and then renames, so a crash mid-write never corrupts the state file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), self)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Reconciles the state with the snarks on disk: jobs left `Running` by a crash are reset, `Failed` jobs get a
+    /// fresh set of retries, and any job whose snark file exists is marked `Done` (and vice versa).
+    pub fn reconcile(&mut self) {
+        self.reconcile_in(Path::new(""));
+    }
+
+    /// [`Self::reconcile`] with snark file names resolved relative to `dir`.
+    fn reconcile_in(&mut self, dir: &Path) {
+        let network = self.network;
+        for job in self.jobs.iter_mut() {
+            let on_disk = dir.join(job.task.snark_name(network)).exists();
+            job.status = match (job.status, on_disk) {
+                (_, true) => JobStatus::Done,
+                (JobStatus::Failed, false) => {
+                    job.attempts = 0;
+                    JobStatus::Pending
+                }
+                (_, false) => JobStatus::Pending,
+            };
+        }
+    }
+
+    /// Pending jobs whose dependencies are all done.
+    pub fn ready(&self) -> Vec<usize> {
+        (0..self.jobs.len())
+            .filter(|&i| {
+                self.jobs[i].status == JobStatus::Pending
+                    && self.jobs[i].deps.iter().all(|&d| self.jobs[d].status == JobStatus::Done)
+            })
+            .collect()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.jobs.last().map(|job| job.status == JobStatus::Done).unwrap_or(true)
+    }
+
+    pub fn failed(&self) -> Vec<&Job> {
+        self.jobs.iter().filter(|job| job.status == JobStatus::Failed).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SchedulerConfig {
+    /// Maximum number of initial depth snarks generated concurrently. Each one holds a full circuit and prover
+    /// state in memory, so this should be sized to the available RAM rather than the number of cores.
+    pub parallelism: usize,
+    /// Number of times a failed job is retried before the run is aborted.
+    pub max_retries: usize,
+    /// Location of the job state file. Defaults to `Task::job_state_name` of the top-level task.
+    pub state_path: Option<PathBuf>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { parallelism: 1, max_retries: 2, state_path: None }
+    }
+}
+
+fn panic_message(err: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = err.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = err.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl Sequencer {
+    /// Generates the snark for `task`, persisting progress to a job state file.
+    /// If a state file for `task` already exists, the run resumes from it.
+    pub fn run(&mut self, task: Task, config: SchedulerConfig) -> Snark {
+        let path =
+            config.state_path.clone().unwrap_or_else(|| task.job_state_name(self.network).into());
+        let state = match JobState::load(&path) {
+            Ok(state) => {
+                assert_eq!(state.root, task, "job state file {path:?} belongs to a different task");
+                assert_eq!(
+                    state.network, self.network,
+                    "job state file is for network {}",
+                    state.network
+                );
+                state
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => JobState::new(self.network, task),
+            // never start over on top of an unreadable state file: the next save would overwrite its progress
+            Err(e) => panic!("could not read job state file {path:?}: {e}"),
+        };
+        self.execute(state, path, config)
+    }
+
+    /// Resumes the run recorded in the job state file at `path`.
+    pub fn resume(&mut self, path: impl AsRef<Path>, config: SchedulerConfig) -> Snark {
+        let path = path.as_ref().to_path_buf();
+        let state = JobState::load(&path)
+            .unwrap_or_else(|e| panic!("could not read job state file {path:?}: {e}"));
+        assert_eq!(state.network, self.network, "job state file is for network {}", state.network);
+        self.execute(state, path, config)
+    }
+
+    fn execute(&mut self, mut state: JobState, path: PathBuf, config: SchedulerConfig) -> Snark {
+        assert!(config.parallelism > 0);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(config.parallelism).build().unwrap();
+        state.reconcile();
+        state.save(&path).expect("write job state file should not fail");
+
+        while !state.is_done() {
+            let ready = state.ready();
+            if ready.is_empty() {
+                let failed = state
+                    .failed()
+                    .iter()
+                    .map(|job| {
+                        format!("{:?}: {}", job.task, job.last_error.as_deref().unwrap_or(""))
+                    })
+                    .collect::<Vec<_>>();
+                panic!("job run is stuck, failed jobs:\n{}", failed.join("\n"));
+            }
+            let (leaves, others): (Vec<usize>, Vec<usize>) = ready.into_iter().partition(|&i| {
+                let circuit_type = state.jobs[i].task.circuit_type;
+                circuit_type.depth == circuit_type.initial_depth
+            });
+            if !leaves.is_empty() {
+                self.execute_leaves(&mut state, &path, &leaves, &config, &pool);
+            } else {
+                // aggregation jobs are proven one at a time: they are few and each already saturates the machine
+                self.execute_one(&mut state, &path, others[0], &config);
+            }
+        }
+        state.jobs.last().unwrap().task.read_snark(self.network).expect("root snark should exist")
+    }
+
+    fn execute_one(
+        &mut self,
+        state: &mut JobState,
+        path: &Path,
+        idx: usize,
+        config: &SchedulerConfig,
+    ) {
+        let task = state.jobs[idx].task;
+        state.jobs[idx].status = JobStatus::Running;
+        state.save(path).expect("write job state file should not fail");
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.get_snark(task);
+        }));
+        record_result(&mut state.jobs[idx], result.map_err(panic_message), config);
+        state.save(path).expect("write job state file should not fail");
+    }
+
+    fn execute_leaves(
+        &mut self,
+        state: &mut JobState,
+        path: &Path,
+        leaves: &[usize],
+        config: &SchedulerConfig,
+        pool: &rayon::ThreadPool,
+    ) {
+        let circuit_type = state.jobs[leaves[0]].task.circuit_type;
+        debug_assert!(leaves.iter().all(|&i| state.jobs[i].task.circuit_type == circuit_type));
+        // the proving key is generated from the first circuit, so that job runs alone
+        if !self.pkeys.contains_key(&circuit_type) {
+            return self.execute_one(state, path, leaves[0], config);
+        }
+        for &i in leaves {
+            state.jobs[i].status = JobStatus::Running;
+        }
+        state.save(path).expect("write job state file should not fail");
+
+        // every leaf gets its own prover randomness, drawn before the parallel section
+        let seeds = leaves.iter().map(|_| self.rng.gen::<[u8; 32]>()).collect::<Vec<_>>();
+        let network = self.network;
+        let params = &self.params[&self.params_k[&circuit_type]];
+        let pk = &self.pkeys[&circuit_type];
+        let circuit_config = &self.configs[&circuit_type];
+        let provider = &self.provider;
+        let tasks = leaves.iter().map(|&i| state.jobs[i].task).zip(seeds).collect::<Vec<_>>();
+        let results: Vec<Result<(), String>> = pool.install(|| {
+            tasks
+                .par_iter()
+                .map(|(task, seed)| {
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        let circuit = leaf_circuit(provider, network, *task);
+                        let mut rng = ChaCha20Rng::from_seed(*seed);
+                        let snark_path = Some(task.snark_name(network));
+                        // circuit params are thread local, so each worker enters the scope itself
+                        circuit_config
+                            .scope(|| gen_snark_shplonk(params, pk, circuit, &mut rng, snark_path));
+                    }))
+                    .map_err(panic_message)
+                })
+                .collect()
+        });
+        for (&i, result) in leaves.iter().zip(results) {
+            record_result(&mut state.jobs[i], result, config);
+        }
+        state.save(path).expect("write job state file should not fail");
+    }
+}
+
+fn leaf_circuit(
+    provider: &Provider<Http>,
+    network: Network,
+    task: Task,
+) -> EthBlockHeaderChainCircuit<Fr> {
+    let CircuitType { depth, .. } = task.circuit_type;
+    let num_blocks = task.end - task.start + 1;
+    EthBlockHeaderChainCircuit::from_provider(provider, network, task.start, num_blocks, depth)
+}
+
+fn record_result(job: &mut Job, result: Result<(), String>, config: &SchedulerConfig) {
+    job.attempts += 1;
+    match result {
+        Ok(()) => {
+            job.status = JobStatus::Done;
+            job.last_error = None;
+        }
+        Err(e) => {
+            job.status = if job.attempts > config.max_retries {
+                JobStatus::Failed
+            } else {
+                JobStatus::Pending
+            };
+            job.last_error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_header::helpers::Finality;
+
+    const NETWORK: Network = Network::Mainnet;
+
+    // depth 3 root over blocks 0..=7, aggregating two depth 2 tasks of two depth 1 leaves each:
+    // jobs are [0, 1], [2, 3], [0, 3], [4, 5], [6, 7], [4, 7], root
+    fn state() -> JobState {
+        JobState::new(NETWORK, Task::new(0, 7, CircuitType::new(3, 1, Finality::None)))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("axiom-eth-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch_snark(dir: &Path, task: Task) {
+        let path = dir.join(task.snark_name(NETWORK));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap();
+    }
+
+    #[test]
+    fn test_job_state_dag() {
+        let mut state = state();
+        let spans = state.jobs.iter().map(|job| (job.task.start, job.task.end)).collect::<Vec<_>>();
+        assert_eq!(spans, [(0, 1), (2, 3), (0, 3), (4, 5), (6, 7), (4, 7), (0, 7)]);
+        assert_eq!(state.jobs[2].deps, [0, 1]);
+        assert_eq!(state.jobs[5].deps, [3, 4]);
+        assert_eq!(state.jobs[6].deps, [2, 5]);
+        assert_eq!(state.jobs[6].task, state.root);
+
+        assert_eq!(state.ready(), [0, 1, 3, 4]);
+        state.jobs[0].status = JobStatus::Done;
+        state.jobs[1].status = JobStatus::Running;
+        assert_eq!(state.ready(), [3, 4]);
+        state.jobs[1].status = JobStatus::Done;
+        assert_eq!(state.ready(), [2, 3, 4]);
+        assert!(!state.is_done());
+
+        for job in state.jobs.iter_mut() {
+            job.status = JobStatus::Done;
+        }
+        assert!(state.ready().is_empty());
+        assert!(state.is_done());
+    }
+
+    #[test]
+    fn test_record_result_retries() {
+        let config = SchedulerConfig { max_retries: 1, ..Default::default() };
+        let mut state = state();
+        record_result(&mut state.jobs[0], Err("oom".to_string()), &config);
+        assert_eq!(state.jobs[0].status, JobStatus::Pending);
+        assert_eq!(state.ready(), [0, 1, 3, 4]);
+        record_result(&mut state.jobs[0], Err("oom again".to_string()), &config);
+        assert_eq!(state.jobs[0].status, JobStatus::Failed);
+        assert_eq!(state.jobs[0].attempts, 2);
+        assert_eq!(state.ready(), [1, 3, 4]);
+        assert_eq!(state.failed().len(), 1);
+        assert_eq!(state.failed()[0].last_error.as_deref(), Some("oom again"));
+
+        record_result(&mut state.jobs[1], Ok(()), &config);
+        assert_eq!(state.jobs[1].status, JobStatus::Done);
+        assert_eq!(state.jobs[1].last_error, None);
+    }
+
+    #[test]
+    fn test_reconcile() {
+        use JobStatus::{Done, Pending};
+        let dir = temp_dir("reconcile");
+        let mut state = state();
+        touch_snark(&dir, state.jobs[0].task);
+        touch_snark(&dir, state.jobs[1].task);
+        // crashed while proving [0, 3]
+        state.jobs[2].status = JobStatus::Running;
+        // gave up on [4, 5]
+        state.jobs[3].status = JobStatus::Failed;
+        state.jobs[3].attempts = 3;
+        // marked done, but the snark file was deleted
+        state.jobs[4].status = JobStatus::Done;
+
+        state.reconcile_in(&dir);
+        let statuses = state.jobs.iter().map(|job| job.status).collect::<Vec<_>>();
+        assert_eq!(statuses, [Done, Done, Pending, Pending, Pending, Pending, Pending]);
+        assert_eq!(state.jobs[3].attempts, 0);
+        assert_eq!(state.ready(), [2, 3, 4]);
+
+        // a snark for the root finishes the run no matter what the other jobs say
+        touch_snark(&dir, state.root);
+        state.reconcile_in(&dir);
+        assert!(state.is_done());
+
+        let path = dir.join(state.root.job_state_name(NETWORK));
+        state.save(&path).unwrap();
+        let loaded = JobState::load(&path).unwrap();
+        assert_eq!(loaded.root, state.root);
+        assert_eq!(loaded.jobs.len(), state.jobs.len());
+        assert!(loaded.is_done());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
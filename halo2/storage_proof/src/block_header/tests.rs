@@ -22,7 +22,7 @@ use halo2_base::{
 use hex::FromHex;
 use rand_core::OsRng;
 use serde::Deserialize;
-use std::{env::set_var, fs::File, marker::PhantomData};
+use std::{fs::File, marker::PhantomData};
 
 #[derive(Clone, Debug)]
 struct EthBlockHeaderTestCircuit<F> {
@@ -44,7 +44,8 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderTestCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let params = EthConfigParams::get_header();
+        let params =
+            EthConfigParams::current().expect("test circuit must be configured inside a scope");
         EthConfig::configure(meta, params, 0)
     }
 
@@ -55,7 +56,7 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderTestCircuit<F> {
     ) -> Result<(), Error> {
         let witness_time = start_timer!(|| "witness gen");
         config.range().load_lookup_table(&mut layouter).expect("load range lookup table");
-        config.keccak().load_aux_tables(&mut layouter).expect("load keccak lookup tables");
+        config.load_keccak_aux_tables(&mut layouter).expect("load keccak lookup tables");
         let gamma = layouter.get_challenge(config.rlc().gamma);
 
         let mut first_pass = SKIP_FIRST_PASS;
@@ -107,8 +108,8 @@ impl<F: Field + PrimeField> Circuit<F> for EthBlockHeaderTestCircuit<F> {
 
 #[test]
 pub fn test_one_mainnet_header_mock() {
-    set_var("BLOCK_HEADER_CONFIG", "configs/tests/one_block.json");
-    let params = EthConfigParams::get_header();
+    let params = EthConfigParams::from_path("configs/tests/one_block.json");
+    let _params = params.enter();
     let k = params.degree;
     let input_hex = "f90201a0d7519abd494a823b2c9c28908eaf250fe4a6287d747f1cc53a5a193b6533a549a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347944675c7e5baafbffbca748158becba61ef3b0a263a025000d51f040ee5c473fed74eda9ace87d55a35187b11bcde6f5176025c395bfa0a5800a6de6d28d7425ff72714af2af769b9f8f9e1baf56fb42f793fbb40fde07a056e1062a3dc63791e8a8496837606b14062da70ee69178cea97d6eeb5047550cb9010000236420014dc00423903000840002280080282100004704018340c0241c20011211400426000f900001d8088000011006020002ce98bc00c0000020c9a02040000688040200348c3a0082b81402002814922008085d008008200802802c4000130000101703124801400400018008a6108002020420144011200070020bc0202681810804221304004800088600300000040463614a000e200201c00611c0008e800b014081608010a0218a0b410010082000428209080200f50260a00840006700100f40a000000400000448301008c4a00341040e343500800d06250020010215200c008018002c88350404000bc5000a8000210c00724a0d0a4010210a448083eee2468401c9c3808343107884633899e780a07980d8d1f15474c9185e4d1cef5f207167735009daad2eb6af6da37ffba213c28800000000000000008501e08469e60000000000000000000000000000000000000000000000000000000000000000000000000000000000";
     let input_bytes: Vec<u8> = Vec::from_hex(input_hex).unwrap();
@@ -123,8 +124,8 @@ pub fn test_one_mainnet_header_mock() {
 
 #[test]
 pub fn test_one_mainnet_header_before_london_mock() {
-    set_var("BLOCK_HEADER_CONFIG", "configs/tests/one_block.json");
-    let params = EthConfigParams::get_header();
+    let params = EthConfigParams::from_path("configs/tests/one_block.json");
+    let _params = params.enter();
     let k = params.degree;
     let input_hex = "f90221a0b8b861952bca93c10bc7c38f9ef5c4e047beae539cfe46fa456c78893d916927a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347940501b62d81a3f072f1d393d2f74013bab8d36d5ca01fd1d6a626d5d72d433b776c0c348f0cab03d13c68ba39ca4a6d6f109032de34a0418c7fdf567a5989a727ea0fe6054008ecf4953aaf56c28f7f197f6e443f05c0a05f79bcb9839eb480350b541377d04c5088fc4bab6952ed27cb94c70dd6736d73b9010081029040054830208119a218064a503c384490dc2014a414e3148820851856c05008e643a88a4a0002242e1a702d8a516244220a18cd0121a13a20882930000e471369c142ad4323475013088accb068824a002cc35021640860a448405a904001094c200a6081d0420feb02802c2e090a121403213d2640c100503510300364e43020f55943142815080595b145040045890021412545119b9002891cfe41011a704100ca97641210002a3b22c10f24853849048420100465c361880421593000021022c90800008800750e546464068cc40290108c48741899114af9c52801403da6800c02000c6ea270992068b45618c46f1254d7601d4411104e41d00a0787074abe0f14de3383765fdd837a121d8379cbd7845cda8ef39fde830203088f5061726974792d457468657265756d86312e33332e30826c69a09d41f9f64af4ebd672dec132507a12a4c85c1a514f47969dbd9c2b5e9d7d214e882b8a10229542325400000000000000000000";
     let input_bytes: Vec<u8> = Vec::from_hex(input_hex).unwrap();
@@ -139,8 +140,8 @@ pub fn test_one_mainnet_header_before_london_mock() {
 
 #[test]
 pub fn test_one_mainnet_header_prover() -> Result<(), Box<dyn std::error::Error>> {
-    set_var("BLOCK_HEADER_CONFIG", "configs/tests/one_block.json");
-    let params = EthConfigParams::get_header();
+    let params = EthConfigParams::from_path("configs/tests/one_block.json");
+    let _params = params.enter();
     let k = params.degree;
     let input_hex = "f90201a0d7519abd494a823b2c9c28908eaf250fe4a6287d747f1cc53a5a193b6533a549a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347944675c7e5baafbffbca748158becba61ef3b0a263a025000d51f040ee5c473fed74eda9ace87d55a35187b11bcde6f5176025c395bfa0a5800a6de6d28d7425ff72714af2af769b9f8f9e1baf56fb42f793fbb40fde07a056e1062a3dc63791e8a8496837606b14062da70ee69178cea97d6eeb5047550cb9010000236420014dc00423903000840002280080282100004704018340c0241c20011211400426000f900001d8088000011006020002ce98bc00c0000020c9a02040000688040200348c3a0082b81402002814922008085d008008200802802c4000130000101703124801400400018008a6108002020420144011200070020bc0202681810804221304004800088600300000040463614a000e200201c00611c0008e800b014081608010a0218a0b410010082000428209080200f50260a00840006700100f40a000000400000448301008c4a00341040e343500800d06250020010215200c008018002c88350404000bc5000a8000210c00724a0d0a4010210a448083eee2468401c9c3808343107884633899e780a07980d8d1f15474c9185e4d1cef5f207167735009daad2eb6af6da37ffba213c28800000000000000008501e08469e60000000000000000000000000000000000000000000000000000000000000000000000000000000000";
     let input_bytes: Vec<u8> = Vec::from_hex(input_hex).unwrap();
@@ -243,8 +244,8 @@ fn get_default_goerli_header_chain_circuit() -> EthBlockHeaderChainCircuit<Fr> {
 
 #[test]
 pub fn test_multi_goerli_header_mock() {
-    set_var("BLOCK_HEADER_CONFIG", "configs/tests/multi_block.json");
-    let config = EthConfigParams::get_header();
+    let config = EthConfigParams::from_path("configs/tests/multi_block.json");
+    let _params = config.enter();
     let k = config.degree;
 
     let circuit = get_default_goerli_header_chain_circuit();
@@ -255,8 +256,8 @@ pub fn test_multi_goerli_header_mock() {
 
 #[test]
 pub fn test_multi_goerli_header_prover() {
-    set_var("BLOCK_HEADER_CONFIG", "configs/tests/multi_block.json");
-    let config = EthConfigParams::get_header();
+    let config = EthConfigParams::from_path("configs/tests/multi_block.json");
+    let _params = config.enter();
     let k = config.degree;
     let proof_circuit = get_default_goerli_header_chain_circuit();
     let circuit = proof_circuit.without_witnesses();
@@ -0,0 +1,268 @@
+//! Constrained manipulation of byte arrays of variable length, such as calldata or log data: substrings at a
+//! witness offset, concatenation, equality and prefix checks. Like RLP decoding, the bytes are witnessed in
+//! `FirstPhase` and constrained with RLC in `SecondPhase`.
+use crate::{
+    halo2_proofs::circuit::Value,
+    rlp::{
+        rlc::{rlc_constrain_equal, rlc_is_equal, RlcChip, RlcTrace, RLC_PHASE},
+        witness_subarray, RlpConfig,
+    },
+};
+use halo2_base::{
+    gates::{flex_gate::FlexGateConfig, range::RangeConfig, GateInstructions, RangeInstructions},
+    utils::{bit_length, ScalarField},
+    AssignedValue, Context,
+    QuantumCell::{Constant, Existing},
+};
+
+#[cfg(test)]
+mod tests;
+
+pub type AssignedBytes<'v, F> = Vec<AssignedValue<'v, F>>;
+
+/// The bytes `bytes[..len]`. The cells of `bytes` beyond `len` may be anything.
+#[derive(Clone, Debug)]
+pub struct VarBytes<'v, F: ScalarField> {
+    pub bytes: AssignedBytes<'v, F>,
+    pub len: AssignedValue<'v, F>,
+}
+
+impl<'v, F: ScalarField> VarBytes<'v, F> {
+    pub fn new(bytes: AssignedBytes<'v, F>, len: AssignedValue<'v, F>) -> Self {
+        Self { bytes, len }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The values of `bytes[..len]`.
+    pub fn value(&self) -> Value<Vec<F>> {
+        let values = self.bytes.iter().fold(
+            Value::known(Vec::with_capacity(self.bytes.len())),
+            |acc, byte| {
+                acc.zip(byte.value()).map(|(mut acc, byte)| {
+                    acc.push(*byte);
+                    acc
+                })
+            },
+        );
+        values.zip(self.len.value()).map(|(mut values, len)| {
+            values.truncate(len.get_lower_32() as usize);
+            values
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SubstringWitness<'v, F: ScalarField> {
+    pub bytes: VarBytes<'v, F>,
+    pub start: AssignedValue<'v, F>,
+    end: AssignedValue<'v, F>,
+    /// `bytes.bytes[start..start + len]`, followed by `0`s
+    pub substring: VarBytes<'v, F>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConcatWitness<'v, F: ScalarField> {
+    pub parts: Vec<VarBytes<'v, F>>,
+    /// The concatenation of `parts`, followed by `0`s
+    pub concat: VarBytes<'v, F>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BytesChip<'g, F: ScalarField> {
+    pub rlc: RlcChip<'g, F>,
+    pub range: RangeConfig<F>,
+}
+
+impl<'g, F: ScalarField> BytesChip<'g, F> {
+    pub fn new(config: RlpConfig<F>, gamma: Value<F>) -> BytesChip<'g, F> {
+        let rlc = RlcChip::new(config.rlc, gamma);
+        Self { rlc, range: config.range }
+    }
+
+    pub fn gate(&self) -> &FlexGateConfig<F> {
+        self.range.gate()
+    }
+
+    pub fn rlc(&self) -> &RlcChip<'g, F> {
+        &self.rlc
+    }
+
+    pub fn get_challenge(&mut self, ctx: &mut Context<F>) {
+        self.rlc.get_challenge(ctx);
+    }
+
+    /// Compute and assign witnesses for the substring of `bytes` of length `len` starting at `start`, where the
+    /// substring has length at most `max_len`. Constrains `start + len <= bytes.len` and `len <= max_len`.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn substring_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        bytes: VarBytes<'v, F>,
+        start: AssignedValue<'v, F>,
+        len: AssignedValue<'v, F>,
+        max_len: usize,
+    ) -> SubstringWitness<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 0);
+        debug_assert!(max_len <= bytes.max_len());
+
+        let bits = bit_length(bytes.max_len() as u64);
+        self.range.range_check(ctx, &start, bits);
+        self.range.check_less_than_safe(ctx, &len, (max_len + 1) as u64);
+        let end = self.gate().add(ctx, Existing(&start), Existing(&len));
+        // end <= bytes.len
+        let len_plus_one = self.gate().add(ctx, Existing(&bytes.len), Constant(F::one()));
+        self.range.check_less_than(ctx, Existing(&end), Existing(&len_plus_one), bits + 1);
+        let substring =
+            witness_subarray(ctx, self.gate(), &bytes.bytes, start.value(), len.value(), max_len);
+        SubstringWitness { bytes, start, end, substring: VarBytes::new(substring, len) }
+    }
+
+    /// Use RLC to constrain the substring witness. This MUST be done in `SecondPhase`.
+    ///
+    /// Returns the RLC of the substring.
+    pub fn substring_phase1<'v>(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: SubstringWitness<'v, F>,
+    ) -> RlcTrace<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), RLC_PHASE);
+        let SubstringWitness { bytes, start, end, substring } = witness;
+        let max_len = bytes.max_len();
+        self.rlc.load_rlc_cache(ctx, self.range.gate(), bit_length(max_len as u64));
+
+        // bytes[..end] = bytes[..start] || substring
+        let prefix_rlc = self.rlc.compute_rlc(ctx, self.gate(), bytes.bytes.clone(), start);
+        let end_rlc = self.rlc.compute_rlc(ctx, self.gate(), bytes.bytes, end);
+        let substring_rlc = self.rlc.compute_rlc(ctx, self.gate(), substring.bytes, substring.len);
+        self.rlc.constrain_rlc_concat(
+            ctx,
+            self.gate(),
+            [
+                (&prefix_rlc.rlc_val, &prefix_rlc.len, max_len),
+                (&substring_rlc.rlc_val, &substring_rlc.len, substring_rlc.max_len),
+            ],
+            (&end_rlc.rlc_val, &end_rlc.len),
+        );
+        substring_rlc
+    }
+
+    /// Compute and assign witnesses for the concatenation of `parts`. Assumes `part.len <= part.max_len()` for
+    /// each part.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn concat_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        parts: Vec<VarBytes<'v, F>>,
+    ) -> ConcatWitness<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 0);
+        assert!(!parts.is_empty());
+
+        let max_len: usize = parts.iter().map(VarBytes::max_len).sum();
+        let len = self.gate().sum(ctx, parts.iter().map(|part| Existing(&part.len)));
+        let value = parts.iter().fold(Value::known(Vec::with_capacity(max_len)), |acc, part| {
+            acc.zip(part.value()).map(|(mut acc, part)| {
+                acc.extend(part);
+                acc
+            })
+        });
+        let bytes = self.gate().assign_witnesses(
+            ctx,
+            (0..max_len).map(|idx| {
+                value.as_ref().map(|value| value.get(idx).copied().unwrap_or_else(F::zero))
+            }),
+        );
+        ConcatWitness { parts, concat: VarBytes::new(bytes, len) }
+    }
+
+    /// Use RLC to constrain the concatenation witness. This MUST be done in `SecondPhase`.
+    ///
+    /// Returns the RLC of the concatenation.
+    pub fn concat_phase1<'v>(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: ConcatWitness<'v, F>,
+    ) -> RlcTrace<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), RLC_PHASE);
+        let ConcatWitness { parts, concat } = witness;
+        self.rlc.load_rlc_cache(ctx, self.range.gate(), bit_length(concat.max_len() as u64));
+
+        let part_rlcs: Vec<_> = parts
+            .into_iter()
+            .map(|part| self.rlc.compute_rlc(ctx, self.gate(), part.bytes, part.len))
+            .collect();
+        let concat_rlc = self.rlc.compute_rlc(ctx, self.gate(), concat.bytes, concat.len);
+        self.rlc.constrain_rlc_concat(
+            ctx,
+            self.gate(),
+            part_rlcs.iter().map(|rlc| (&rlc.rlc_val, &rlc.len, rlc.max_len)),
+            (&concat_rlc.rlc_val, &concat_rlc.len),
+        );
+        concat_rlc
+    }
+
+    /// Returns the RLC of `bytes`. This MUST be done in `SecondPhase`.
+    ///
+    /// Assumes `bytes.len <= bytes.max_len()`.
+    pub fn rlc_phase1<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        bytes: &VarBytes<'v, F>,
+    ) -> RlcTrace<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), RLC_PHASE);
+        self.rlc.compute_rlc(ctx, self.gate(), bytes.bytes.clone(), bytes.len.clone())
+    }
+
+    /// Returns `1` if `a` and `b` are equal, `0` otherwise. This MUST be done in `SecondPhase`.
+    ///
+    /// Assumes `a.len <= a.max_len()` and `b.len <= b.max_len()`.
+    pub fn is_equal_phase1<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        a: &VarBytes<'v, F>,
+        b: &VarBytes<'v, F>,
+    ) -> AssignedValue<'v, F> {
+        let a_rlc = self.rlc_phase1(ctx, a);
+        let b_rlc = self.rlc_phase1(ctx, b);
+        rlc_is_equal(ctx, self.gate(), &a_rlc, &b_rlc)
+    }
+
+    /// Constrains `a` and `b` to be equal. This MUST be done in `SecondPhase`.
+    ///
+    /// Assumes `a.len <= a.max_len()` and `b.len <= b.max_len()`.
+    pub fn constrain_equal_phase1<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        a: &VarBytes<'v, F>,
+        b: &VarBytes<'v, F>,
+    ) {
+        let a_rlc = self.rlc_phase1(ctx, a);
+        let b_rlc = self.rlc_phase1(ctx, b);
+        rlc_constrain_equal(ctx, &a_rlc, &b_rlc);
+    }
+
+    /// Returns `1` if `prefix` is a prefix of `bytes`, `0` otherwise. This MUST be done in `SecondPhase`.
+    ///
+    /// Assumes `prefix.len <= prefix.max_len()` and `bytes.len <= bytes.max_len()`.
+    pub fn is_prefix_phase1<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        prefix: &VarBytes<'v, F>,
+        bytes: &VarBytes<'v, F>,
+    ) -> AssignedValue<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), RLC_PHASE);
+        let bits = bit_length(prefix.max_len().max(bytes.max_len()) as u64 + 1);
+        let len_plus_one = self.gate().add(ctx, Existing(&bytes.len), Constant(F::one()));
+        let is_short =
+            self.range.is_less_than(ctx, Existing(&prefix.len), Existing(&len_plus_one), bits);
+        // compare with the empty prefix of `bytes` if `prefix` is longer than `bytes`
+        let len = self.gate().mul(ctx, Existing(&prefix.len), Existing(&is_short));
+        let bytes_prefix = VarBytes::new(bytes.bytes.clone(), len);
+        let is_equal = self.is_equal_phase1(ctx, prefix, &bytes_prefix);
+        self.gate().and(ctx, Existing(&is_short), Existing(&is_equal))
+    }
+}
@@ -0,0 +1,179 @@
+use super::*;
+use crate::halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner},
+    dev::MockProver,
+    halo2curves::bn256::Fr,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use halo2_base::{ContextParams, SKIP_FIRST_PASS};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::marker::PhantomData;
+
+const DEGREE: u32 = 16;
+const MAX_LEN: usize = 128;
+const MAX_SUBSTRING_LEN: usize = 32;
+const TAIL: [u8; 2] = [0xde, 0xad];
+
+/// A witness to overwrite after `FirstPhase`, to check that `SecondPhase` catches it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tamper {
+    None,
+    Substring,
+    Concat,
+}
+
+/// Takes the substring `bytes[start..start + sub_len]` of calldata `bytes`, appends `TAIL` to it, and checks
+/// both against their native values. Also checks prefixes of `bytes`.
+#[derive(Clone, Debug)]
+pub struct BytesTestCircuit<F> {
+    bytes: Vec<u8>,
+    start: usize,
+    sub_len: usize,
+    tamper: Tamper,
+    _marker: PhantomData<F>,
+}
+
+fn assign_var_bytes<'v, F: ScalarField>(
+    chip: &BytesChip<F>,
+    ctx: &mut Context<'v, F>,
+    bytes: &[u8],
+    max_len: usize,
+) -> VarBytes<'v, F> {
+    let mut padded = bytes.to_vec();
+    padded.resize(max_len, 0);
+    let padded = chip
+        .gate()
+        .assign_witnesses(ctx, padded.iter().map(|byte| Value::known(F::from(*byte as u64))));
+    let len = chip.gate().load_witness(ctx, Value::known(F::from(bytes.len() as u64)));
+    VarBytes::new(padded, len)
+}
+
+impl<F: ScalarField> Circuit<F> for BytesTestCircuit<F> {
+    type Config = RlpConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        RlpConfig::configure(meta, 1, &[1, 1], &[1], 1, 8, 0, DEGREE as usize)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.range.load_lookup_table(&mut layouter).expect("load lookup table should not fail");
+
+        let gamma = config.rlc.gamma;
+        let mut chip = BytesChip::new(config, layouter.get_challenge(gamma));
+
+        let mut first_pass = SKIP_FIRST_PASS;
+        layouter.assign_region(
+            || "bytes test",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let mut aux = Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: chip.gate().max_rows,
+                        num_context_ids: 2,
+                        fixed_columns: chip.gate().constants.clone(),
+                    },
+                );
+                let ctx = &mut aux;
+
+                let bytes = assign_var_bytes(&chip, ctx, &self.bytes, MAX_LEN);
+                let [start, sub_len] = [self.start, self.sub_len]
+                    .map(|x| chip.gate().load_witness(ctx, Value::known(F::from(x as u64))));
+                let mut substring =
+                    chip.substring_phase0(ctx, bytes.clone(), start, sub_len, MAX_SUBSTRING_LEN);
+                if self.tamper == Tamper::Substring {
+                    // shift the substring by one byte
+                    let shifted = &self.bytes[self.start + 1..self.start + 1 + self.sub_len];
+                    substring.substring.bytes =
+                        assign_var_bytes(&chip, ctx, shifted, MAX_SUBSTRING_LEN).bytes;
+                }
+                let tail = assign_var_bytes(&chip, ctx, &TAIL, TAIL.len());
+                let mut concat = chip.concat_phase0(ctx, vec![substring.substring.clone(), tail]);
+                if self.tamper == Tamper::Concat {
+                    // drop the first byte of the tail
+                    let len = concat.concat.len.clone();
+                    concat.concat.len = chip.gate().sub(ctx, Existing(&len), Constant(F::one()));
+                }
+
+                let native_substring = &self.bytes[self.start..self.start + self.sub_len];
+                let expected_substring =
+                    assign_var_bytes(&chip, ctx, native_substring, MAX_SUBSTRING_LEN);
+                let expected_concat = assign_var_bytes(
+                    &chip,
+                    ctx,
+                    &[native_substring, &TAIL].concat(),
+                    MAX_SUBSTRING_LEN + TAIL.len(),
+                );
+                let selector = assign_var_bytes(&chip, ctx, &self.bytes[..4], 4);
+                let not_selector = assign_var_bytes(&chip, ctx, &[0xff; 4], 4);
+                let too_long =
+                    assign_var_bytes(&chip, ctx, &[&self.bytes[..], &[0]].concat(), MAX_LEN + 1);
+
+                chip.range.finalize(ctx);
+                ctx.next_phase();
+
+                chip.get_challenge(ctx);
+                let substring_bytes = substring.substring.clone();
+                let concat_bytes = concat.concat.clone();
+                chip.substring_phase1(ctx, substring);
+                chip.concat_phase1(ctx, concat);
+                if self.tamper == Tamper::None {
+                    chip.constrain_equal_phase1(ctx, &substring_bytes, &expected_substring);
+                    chip.constrain_equal_phase1(ctx, &concat_bytes, &expected_concat);
+                }
+
+                let checks = [
+                    (chip.is_prefix_phase1(ctx, &selector, &bytes), true),
+                    (chip.is_prefix_phase1(ctx, &bytes, &bytes), true),
+                    (chip.is_prefix_phase1(ctx, &not_selector, &bytes), false),
+                    (chip.is_prefix_phase1(ctx, &too_long, &bytes), false),
+                    (chip.is_equal_phase1(ctx, &substring_bytes, &concat_bytes), false),
+                ];
+                for (check, expected) in checks {
+                    check.value().map(|check| assert_eq!(*check, F::from(expected as u64)));
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+fn bytes_circuit(tamper: Tamper) -> BytesTestCircuit<Fr> {
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    let bytes: Vec<u8> = (0..100).map(|_| rng.gen_range(1..=255)).collect();
+    BytesTestCircuit { bytes, start: 36, sub_len: 32, tamper, _marker: PhantomData }
+}
+
+#[test]
+pub fn test_mock_bytes() {
+    let circuit = bytes_circuit(Tamper::None);
+    MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+
+    // substrings at the ends of the bytes, and an empty one
+    for (start, sub_len) in [(0, 4), (68, 32), (100, 0)] {
+        let circuit = BytesTestCircuit { start, sub_len, ..bytes_circuit(Tamper::None) };
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+}
+
+#[test]
+pub fn test_mock_bytes_tampered() {
+    for tamper in [Tamper::Substring, Tamper::Concat] {
+        let circuit = bytes_circuit(tamper);
+        let prover = MockProver::run(DEGREE, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err(), "{tamper:?} should fail");
+    }
+}
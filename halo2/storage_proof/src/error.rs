@@ -0,0 +1,167 @@
+//! Errors for malformed circuit inputs, so that input loading and circuit construction can report which field or
+//! proof node is wrong instead of panicking.
+use std::{fmt, io};
+
+/// Why an MPT proof cannot be assigned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MPTProofError {
+    /// The proof has no nodes, it must at least contain the leaf.
+    Empty,
+    /// The proof has more nodes than the circuit's max depth.
+    TooDeep { depth: usize, max_depth: usize },
+    /// The value is longer than the circuit's max value length.
+    ValueTooLong { len: usize, max_len: usize },
+    /// Node `node` of the proof, counted from the root, is malformed.
+    InvalidNode { node: usize, reason: String },
+    /// Node `node` of the proof, counted from the root, does not prove the claimed key and value.
+    Mismatch { node: usize, reason: String },
+    /// A batch of proofs has more distinct nodes above the leaves than the circuit's max number of nodes.
+    TooManyNodes { nodes: usize, max_nodes: usize },
+}
+
+impl fmt::Display for MPTProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "proof has no nodes"),
+            Self::TooDeep { depth, max_depth } => {
+                write!(
+                    f,
+                    "proof has {depth} nodes, more than the max depth {max_depth}; \
+                     set the max depth to at least {depth}"
+                )
+            }
+            Self::ValueTooLong { len, max_len } => {
+                write!(f, "value has {len} bytes, more than the max {max_len} bytes")
+            }
+            Self::InvalidNode { node, reason } | Self::Mismatch { node, reason } => {
+                write!(f, "node {node}: {reason}")
+            }
+            Self::TooManyNodes { nodes, max_nodes } => {
+                write!(
+                    f,
+                    "proofs have {nodes} distinct nodes, more than the max {max_nodes}; \
+                     set the max nodes to at least {nodes}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MPTProofError {}
+
+/// Error from loading circuit inputs.
+#[derive(Debug)]
+pub enum InputError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    /// `field` is missing or is not valid json for its type.
+    Json {
+        field: String,
+        source: serde_json::Error,
+    },
+    /// `field` is missing, e.g. `block.mixHash` of a pre-merge header returned without it.
+    MissingField(String),
+    /// `field` is present but malformed, e.g. a header that is not valid RLP.
+    InvalidField {
+        field: String,
+        reason: String,
+    },
+    /// `field` is a JSON-RPC error response instead of a result.
+    Rpc {
+        field: String,
+        message: String,
+    },
+    /// The input file has a schema version this crate cannot read.
+    UnsupportedVersion {
+        version: u64,
+        supported: u64,
+    },
+    /// The block header RLP is longer than the circuit supports.
+    HeaderTooLong {
+        len: usize,
+        max_len: usize,
+    },
+    /// `field` does not match the value committed to by the block header or the proofs.
+    Mismatch {
+        field: String,
+        reason: String,
+    },
+    /// The input queries more slots than the circuit has room for.
+    TooManySlots {
+        num_slots: usize,
+        max_slots: usize,
+    },
+    /// The MPT proof `proof`, e.g. `storageProof[1]`, cannot be assigned or does not verify.
+    Proof {
+        proof: String,
+        source: MPTProofError,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "could not read {path}: {source}"),
+            Self::Json { field, source } => write!(f, "invalid field `{field}`: {source}"),
+            Self::MissingField(field) => write!(f, "missing field `{field}`"),
+            Self::InvalidField { field, reason } => write!(f, "invalid field `{field}`: {reason}"),
+            Self::Rpc { field, message } => write!(f, "`{field}` is a JSON-RPC error: {message}"),
+            Self::UnsupportedVersion { version, supported } => {
+                write!(f, "input schema version {version} is not supported, expected {supported}")
+            }
+            Self::HeaderTooLong { len, max_len } => {
+                write!(f, "block header RLP has {len} bytes, more than the max {max_len} bytes")
+            }
+            Self::Mismatch { field, reason } => write!(f, "`{field}` does not verify: {reason}"),
+            Self::TooManySlots { num_slots, max_slots } => {
+                write!(f, "input queries {num_slots} slots, more than the max {max_slots} slots")
+            }
+            Self::Proof { proof, source } => write!(f, "invalid proof `{proof}`: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Json { source, .. } => Some(source),
+            Self::Proof { source, .. } => Some(source),
+            Self::MissingField(_)
+            | Self::InvalidField { .. }
+            | Self::Rpc { .. }
+            | Self::UnsupportedVersion { .. }
+            | Self::HeaderTooLong { .. }
+            | Self::Mismatch { .. }
+            | Self::TooManySlots { .. } => None,
+        }
+    }
+}
+
+impl InputError {
+    pub fn json(field: impl Into<String>, source: serde_json::Error) -> Self {
+        Self::Json { field: field.into(), source }
+    }
+
+    pub fn proof(proof: impl Into<String>, source: MPTProofError) -> Self {
+        Self::Proof { proof: proof.into(), source }
+    }
+}
+
+/// Reads `path` as json.
+pub fn read_json(path: &str) -> Result<serde_json::Value, InputError> {
+    let file = std::fs::File::open(path)
+        .map_err(|source| InputError::Io { path: path.to_string(), source })?;
+    serde_json::from_reader(io::BufReader::new(file)).map_err(|e| InputError::json(path, e))
+}
+
+/// Deserializes `json[field]`, reporting `field` if it is missing or invalid.
+pub fn json_field<T: serde::de::DeserializeOwned>(
+    json: &serde_json::Value,
+    field: &str,
+) -> Result<T, InputError> {
+    let value = json.get(field).ok_or_else(|| InputError::MissingField(field.to_string()))?;
+    T::deserialize(value).map_err(|e| InputError::json(field, e))
+}
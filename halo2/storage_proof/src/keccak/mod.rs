@@ -1,6 +1,10 @@
 use crate::{
-    halo2_proofs::circuit::Region,
+    halo2_proofs::{
+        circuit::{Layouter, Region},
+        plonk::{Challenge, ConstraintSystem, Error},
+    },
     rlp::rlc::{RlcChip, RlcFixedTrace, RlcTrace},
+    sizing::MIN_KECCAK_ROWS_PER_ROUND,
 };
 use core::iter::once;
 use ethers_core::utils::keccak256;
@@ -12,18 +16,190 @@ use halo2_base::{
     QuantumCell::{Constant, Existing},
 };
 use itertools::Itertools;
-use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use lazy_static::lazy_static;
+use rayon::{
+    prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env::{remove_var, set_var, var},
+    fmt,
+    sync::Mutex,
+};
 pub(crate) use zkevm_keccak::KeccakConfig;
 use zkevm_keccak::{
-    keccak_packed_multi::{
-        get_num_keccak_f, get_num_rows_per_round, keccak_phase0, multi_keccak_phase1, KeccakRow,
+    keccak_packed_multi::{get_num_keccak_f, keccak_phase0, multi_keccak_phase1, KeccakRow},
+    util::{
+        eth_types::Field, NUM_BYTES_TO_SQUEEZE, NUM_ROUNDS, NUM_WORDS_TO_ABSORB,
+        NUM_WORDS_TO_SQUEEZE, RATE,
     },
-    util::{eth_types::Field, NUM_BYTES_TO_SQUEEZE, NUM_ROUNDS, NUM_WORDS_TO_SQUEEZE, RATE},
 };
 
 #[cfg(test)]
 mod tests;
 
+const KECCAK_ENV_VARS: [&str; 3] = ["KECCAK_DEGREE", "KECCAK_ROWS", "UNUSABLE_ROWS"];
+/// Rounds of the keccak table that hold no permutation: the dummy first round, so that the initial data is
+/// absorbed, and the rounds after the last permutation that its absorb gates query.
+const KECCAK_EXTRA_ROUNDS: usize = 1 + NUM_WORDS_TO_ABSORB;
+
+/// Rows of the keccak table used by `num_keccak_f` permutations with `rows_per_round` rows per round.
+pub fn keccak_table_rows(num_keccak_f: usize, rows_per_round: usize) -> usize {
+    rows_per_round * (KECCAK_EXTRA_ROUNDS + (NUM_ROUNDS + 1) * num_keccak_f)
+}
+
+/// The most rows per round with which `num_keccak_f` permutations fit in `usable_rows` rows.
+pub fn max_keccak_rows_per_round(num_keccak_f: usize, usable_rows: usize) -> usize {
+    usable_rows / (KECCAK_EXTRA_ROUNDS + (NUM_ROUNDS + 1) * num_keccak_f)
+}
+
+lazy_static! {
+    static ref KECCAK_ENV_LOCK: Mutex<()> = Mutex::new(());
+    /// Generates keccak witnesses inside [`KeccakParams::with_env`]. Its workers only ever run keccak witness
+    /// generation, never a task that calls `with_env` again, so they cannot deadlock on the environment lock.
+    static ref KECCAK_WITNESS_POOL: ThreadPool =
+        ThreadPoolBuilder::new().thread_name(|i| format!("keccak-witness-{i}")).build().unwrap();
+}
+
+/// Parameters of the keccak table.
+///
+/// The crate passes these explicitly everywhere: `EthConfigParams::keccak_params` to [`configure_keccak`] and
+/// [`load_keccak_aux_tables`], and the params stored in [`KeccakChip`] to witness generation. `zkevm_keccak`
+/// (v0.2.2) however has no way to take them as arguments and reads the environment variables `KECCAK_DEGREE`,
+/// `KECCAK_ROWS` and `UNUSABLE_ROWS` instead, so every call into it goes through [`KeccakParams::with_env`]. That
+/// sets the variables only for the duration of the call and holds a process-wide lock meanwhile, which orders the
+/// calls made through this crate and nothing else. `set_var` is not thread safe: any other thread that reads or
+/// writes the environment meanwhile, in this crate's dependencies or in the caller, races with it. Until
+/// `zkevm_keccak` takes the params as arguments, configure, generate keys and prove only while no other thread uses
+/// the environment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeccakParams {
+    /// log2 of the number of rows in the circuit
+    pub degree: u32,
+    pub rows_per_round: usize,
+    pub unusable_rows: usize,
+}
+
+impl KeccakParams {
+    /// Runs `f` with the keccak environment variables set to `self`, restoring their previous values afterwards.
+    ///
+    /// The lock is not reentrant: `f` must not call `with_env` again, also not indirectly through rayon work
+    /// stealing, so `f` should only use parallel iterators inside `KECCAK_WITNESS_POOL`.
+    pub fn with_env<R>(&self, f: impl FnOnce() -> R) -> R {
+        // a panic while holding the lock leaves the environment restored by the guard below, so poisoning is benign
+        let _lock = KECCAK_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let _env = EnvGuard(KECCAK_ENV_VARS.map(|key| (key, var(key).ok())));
+        set_var("KECCAK_DEGREE", self.degree.to_string());
+        set_var("KECCAK_ROWS", self.rows_per_round.to_string());
+        set_var("UNUSABLE_ROWS", self.unusable_rows.to_string());
+        f()
+    }
+
+    /// Rows of the circuit not reserved for blinding, `2^degree - unusable_rows`.
+    pub fn usable_rows(&self) -> usize {
+        (1usize << self.degree).saturating_sub(self.unusable_rows)
+    }
+
+    /// The most keccak_f permutations the keccak table has room for.
+    pub fn max_keccak_f(&self) -> usize {
+        (self.usable_rows() / self.rows_per_round).saturating_sub(KECCAK_EXTRA_ROUNDS)
+            / (NUM_ROUNDS + 1)
+    }
+
+    /// The keccak table usage of `num_keccak_f` permutations.
+    pub fn usage(&self, num_keccak_f: usize) -> KeccakUsage {
+        KeccakUsage {
+            num_keccak_f,
+            max_keccak_f: self.max_keccak_f(),
+            rows: keccak_table_rows(num_keccak_f, self.rows_per_round),
+            usable_rows: self.usable_rows(),
+        }
+    }
+}
+
+/// Keccak table usage of the queries of a [`KeccakChip`], see [`KeccakChip::usage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeccakUsage {
+    /// Number of keccak_f permutations the queries need
+    pub num_keccak_f: usize,
+    /// Number of keccak_f permutations the table has room for
+    pub max_keccak_f: usize,
+    /// Rows of the table the queries use, see [`keccak_table_rows`]
+    pub rows: usize,
+    /// Rows available to the table, see [`KeccakParams::usable_rows`]
+    pub usable_rows: usize,
+}
+
+impl KeccakUsage {
+    pub fn fits(&self) -> bool {
+        self.num_keccak_f <= self.max_keccak_f
+    }
+}
+
+/// The queries of a [`KeccakChip`] need more keccak_f permutations than its table has room for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeccakCapacityError {
+    pub params: KeccakParams,
+    pub usage: KeccakUsage,
+}
+
+impl fmt::Display for KeccakCapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { params, usage } = self;
+        write!(
+            f,
+            "keccak queries need {} keccak_f permutations ({} rows), but the keccak table has room for {} \
+             ({} usable rows with degree {}, {} rows per round and {} unusable rows); ",
+            usage.num_keccak_f,
+            usage.rows,
+            usage.max_keccak_f,
+            usage.usable_rows,
+            params.degree,
+            params.rows_per_round,
+            params.unusable_rows
+        )?;
+        let rows_per_round = max_keccak_rows_per_round(usage.num_keccak_f, usage.usable_rows);
+        if rows_per_round >= MIN_KECCAK_ROWS_PER_ROUND {
+            write!(f, "set the keccak rows per round to at most {rows_per_round}, or ")?;
+        }
+        write!(f, "increase the degree, e.g. with `sizing::size_circuit`")
+    }
+}
+
+impl std::error::Error for KeccakCapacityError {}
+
+struct EnvGuard([(&'static str, Option<String>); 3]);
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (key, val) in self.0.iter() {
+            match val {
+                Some(val) => set_var(key, val),
+                None => remove_var(key),
+            }
+        }
+    }
+}
+
+/// Configures the keccak table with `params`.
+pub fn configure_keccak<F: Field>(
+    meta: &mut ConstraintSystem<F>,
+    challenge: Challenge,
+    params: KeccakParams,
+) -> KeccakConfig<F> {
+    params.with_env(|| KeccakConfig::new(meta, challenge))
+}
+
+/// Loads the lookup tables of a keccak table configured with `params`.
+pub fn load_keccak_aux_tables<F: Field>(
+    config: &KeccakConfig<F>,
+    params: KeccakParams,
+    layouter: &mut impl Layouter<F>,
+) -> Result<(), Error> {
+    params.with_env(|| config.load_aux_tables(layouter))
+}
+
 #[derive(Clone, Debug)]
 pub struct KeccakFixedLenQuery<'v, F: Field> {
     pub input_bytes: Vec<u8>,
@@ -51,7 +227,7 @@ pub struct KeccakVarLenQuery<'v, F: Field> {
 #[derive(Clone, Debug)]
 pub struct KeccakChip<'v, F: Field> {
     pub config: KeccakConfig<F>,
-    num_rows_per_round: usize,
+    pub params: KeccakParams,
     // available only in `FirstPhase`
     pub var_len_queries: Vec<KeccakVarLenQuery<'v, F>>,
     pub fixed_len_queries: Vec<KeccakFixedLenQuery<'v, F>>,
@@ -62,11 +238,10 @@ pub struct KeccakChip<'v, F: Field> {
 }
 
 impl<'v, F: Field> KeccakChip<'v, F> {
-    pub fn new(config: KeccakConfig<F>) -> Self {
-        let num_rows_per_round = get_num_rows_per_round();
+    pub fn new(config: KeccakConfig<F>, params: KeccakParams) -> Self {
         Self {
             config,
-            num_rows_per_round,
+            params,
             var_len_queries: vec![],
             fixed_len_queries: vec![],
             fixed_len_rlcs: vec![],
@@ -84,6 +259,9 @@ impl<'v, F: Field> KeccakChip<'v, F> {
     ///
     /// Assumes that `input_bytes` coincides with the values of `bytes_assigned` as bytes,
     /// if provided (`bytes` is used for faster witness generation).
+    ///
+    /// If a query with the same input cells was already made, returns its index instead of adding a query, so
+    /// hashing the same assigned bytes again costs no keccak rows.
     pub fn keccak_fixed_len(
         &mut self,
         ctx: &mut Context<'_, F>,
@@ -91,6 +269,13 @@ impl<'v, F: Field> KeccakChip<'v, F> {
         input_assigned: Vec<AssignedValue<'v, F>>,
         input_bytes: Option<Vec<u8>>,
     ) -> usize {
+        if let Some(idx) = self
+            .fixed_len_queries
+            .iter()
+            .position(|q| same_cells(&q.input_assigned, &input_assigned))
+        {
+            return idx;
+        }
         let bytes = input_bytes.unwrap_or_else(|| get_bytes(&input_assigned[..]));
         debug_assert_eq!(bytes.len(), input_assigned.len());
 
@@ -120,6 +305,10 @@ impl<'v, F: Field> KeccakChip<'v, F> {
     ///
     /// Constrains `min_len <= len <= bytes.len()`.
     ///
+    /// If a query with the same input cells and length cell, and a `min_len` at least as large, was already made,
+    /// returns its index instead of adding a query or range checking `len` again, see
+    /// [`KeccakChip::keccak_fixed_len`].
+    ///
     /// Returns output in bytes.
     pub fn keccak_var_len(
         &mut self,
@@ -130,6 +319,14 @@ impl<'v, F: Field> KeccakChip<'v, F> {
         len: AssignedValue<'v, F>,
         min_len: usize,
     ) -> usize {
+        // the query already constrains `q.min_bytes <= len <= input_assigned.len()`
+        if let Some(idx) = self.var_len_queries.iter().position(|q| {
+            q.length.cell() == len.cell()
+                && q.min_bytes >= min_len
+                && same_cells(&q.input_assigned, &input_assigned)
+        }) {
+            return idx;
+        }
         let bytes = input_bytes.unwrap_or_else(|| get_bytes(&input_assigned[..]));
         let max_len = input_assigned.len();
 
@@ -272,15 +469,48 @@ impl<'v, F: Field> KeccakChip<'v, F> {
             .collect()
     }
 
-    /// Do this at the end of `FirstPhase` and then call `assign_phase1` in `SecondPhase`.
-    pub fn assign_phase0(&mut self, region: &mut Region<'_, F>) {
-        let capacity: usize = self
-            .fixed_len_queries
+    /// The number of keccak permutations the queries need room for in the keccak table.
+    pub fn capacity(&self) -> usize {
+        self.fixed_len_queries
             .iter()
             .map(|q| q.input_assigned.len())
             .chain(self.var_len_queries.iter().map(|q| q.max_bytes))
             .map(get_num_keccak_f)
-            .sum();
+            .sum()
+    }
+
+    /// The permutations the queries need versus the room in the keccak table.
+    pub fn usage(&self) -> KeccakUsage {
+        self.params.usage(self.capacity())
+    }
+
+    /// Fails if the queries do not fit in the keccak table, which [`KeccakChip::assign_phase0`] would otherwise
+    /// only report as a failure to assign a row.
+    pub fn check_capacity(&self) -> Result<KeccakUsage, KeccakCapacityError> {
+        let usage = self.usage();
+        if usage.fits() {
+            Ok(usage)
+        } else {
+            Err(KeccakCapacityError { params: self.params, usage })
+        }
+    }
+
+    /// Do this at the end of `FirstPhase` and then call `assign_phase1` in `SecondPhase`.
+    ///
+    /// # Panics
+    /// If the queries do not fit in the keccak table, see [`KeccakChip::try_assign_phase0`].
+    pub fn assign_phase0(&mut self, region: &mut Region<'_, F>) {
+        self.try_assign_phase0(region).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`KeccakChip::assign_phase0`], but checks the capacity of the keccak table first and returns an
+    /// error, without assigning anything, if the queries do not fit.
+    pub fn try_assign_phase0(
+        &mut self,
+        region: &mut Region<'_, F>,
+    ) -> Result<(), KeccakCapacityError> {
+        self.check_capacity()?;
+        let capacity = self.capacity();
         let unused_capacity: usize = self
             .var_len_queries
             .iter()
@@ -292,51 +522,60 @@ impl<'v, F: Field> KeccakChip<'v, F> {
 
         // Dummy first rows so that the initial data is absorbed
         // The initial data doesn't really matter, `is_final` just needs to be disabled.
-        for (idx, row) in KeccakRow::dummy_rows(self.num_rows_per_round).iter().enumerate() {
+        for (idx, row) in KeccakRow::dummy_rows(self.params.rows_per_round).iter().enumerate() {
             self.config.set_row(region, idx, row);
         }
-        num_rows_used += self.num_rows_per_round;
-
-        // Generate witnesses for the fixed length queries first since there's no issue of selection
-        let artifacts_fixed = self
-            .fixed_len_queries
-            .par_iter()
-            .map(|query| {
-                let num_keccak_f = get_num_keccak_f(query.input_bytes.len());
-                let mut squeeze_digests = Vec::with_capacity(num_keccak_f);
-                let mut rows =
-                    Vec::with_capacity(num_keccak_f * (NUM_ROUNDS + 1) * self.num_rows_per_round);
-                keccak_phase0(&mut rows, &mut squeeze_digests, &query.input_bytes);
-                (rows, squeeze_digests)
-            })
-            .collect::<Vec<_>>();
-        // Generate witnesses for the variable length queries
-        let artifacts_var = self
-            .var_len_queries
-            .par_iter()
-            .map(|query| {
-                let num_keccak_f = get_num_keccak_f(query.num_bytes);
-                let mut squeeze_digests = Vec::with_capacity(num_keccak_f);
-                let mut rows =
-                    Vec::with_capacity(num_keccak_f * (NUM_ROUNDS + 1) * self.num_rows_per_round);
-                keccak_phase0(
-                    &mut rows,
-                    &mut squeeze_digests,
-                    &query.input_bytes[..query.num_bytes],
-                );
-                (rows, squeeze_digests)
+        num_rows_used += self.params.rows_per_round;
+
+        // `keccak_phase0` reads the keccak params from the environment. The witnesses are generated in a dedicated
+        // thread pool: a worker of the global pool blocked inside `with_env` could steal a task that calls
+        // `with_env` itself and deadlock, since the environment lock is not reentrant.
+        let rows_per_round = self.params.rows_per_round;
+        let (artifacts_fixed, artifacts_var, artifacts_extra) = self.params.with_env(|| {
+            KECCAK_WITNESS_POOL.install(|| {
+                // Generate witnesses for the fixed length queries first since there's no issue of selection
+                let artifacts_fixed = self
+                    .fixed_len_queries
+                    .par_iter()
+                    .map(|query| {
+                        let num_keccak_f = get_num_keccak_f(query.input_bytes.len());
+                        let mut squeeze_digests = Vec::with_capacity(num_keccak_f);
+                        let mut rows =
+                            Vec::with_capacity(num_keccak_f * (NUM_ROUNDS + 1) * rows_per_round);
+                        keccak_phase0(&mut rows, &mut squeeze_digests, &query.input_bytes);
+                        (rows, squeeze_digests)
+                    })
+                    .collect::<Vec<_>>();
+                // Generate witnesses for the variable length queries
+                let artifacts_var = self
+                    .var_len_queries
+                    .par_iter()
+                    .map(|query| {
+                        let num_keccak_f = get_num_keccak_f(query.num_bytes);
+                        let mut squeeze_digests = Vec::with_capacity(num_keccak_f);
+                        let mut rows =
+                            Vec::with_capacity(num_keccak_f * (NUM_ROUNDS + 1) * rows_per_round);
+                        keccak_phase0(
+                            &mut rows,
+                            &mut squeeze_digests,
+                            &query.input_bytes[..query.num_bytes],
+                        );
+                        (rows, squeeze_digests)
+                    })
+                    .collect::<Vec<_>>();
+                // Generate extra witnesses to fill up keccak table up to `capacity`
+                let artifacts_extra = (0..unused_capacity)
+                    .into_par_iter()
+                    .map(|_| {
+                        let mut squeeze_digests = Vec::with_capacity(1);
+                        let mut rows = Vec::with_capacity((NUM_ROUNDS + 1) * rows_per_round);
+                        keccak_phase0(&mut rows, &mut squeeze_digests, &[]);
+                        (rows, squeeze_digests)
+                    })
+                    .collect::<Vec<_>>();
+                (artifacts_fixed, artifacts_var, artifacts_extra)
             })
-            .collect::<Vec<_>>();
-        // Generate extra witnesses to fill up keccak table up to `capacity`
-        let artifacts_extra = (0..unused_capacity)
-            .into_par_iter()
-            .map(|_| {
-                let mut squeeze_digests = Vec::with_capacity(1);
-                let mut rows = Vec::with_capacity((NUM_ROUNDS + 1) * self.num_rows_per_round);
-                keccak_phase0(&mut rows, &mut squeeze_digests, &[]);
-                (rows, squeeze_digests)
-            })
-            .collect::<Vec<_>>();
+        });
 
         for (rows, squeezes) in artifacts_fixed
             .into_iter()
@@ -350,6 +589,7 @@ impl<'v, F: Field> KeccakChip<'v, F> {
             squeeze_digests.extend(squeezes);
         }
         self.squeeze_digests = squeeze_digests;
+        Ok(())
     }
 
     // pass in RLCs in case they are computed elsewhere in a different chip
@@ -365,16 +605,19 @@ impl<'v, F: Field> KeccakChip<'v, F> {
         let gate = range.gate();
         let keccak_table = &self.config.keccak_table;
         // the input and output rlcs in the keccak table
-        let (input_rlcs, output_rlcs) = multi_keccak_phase1(
-            &mut ctx.region,
-            keccak_table,
-            self.fixed_len_queries
-                .iter()
-                .map(|q| &q.input_bytes[..])
-                .chain(self.var_len_queries.iter().map(|q| &q.input_bytes[..q.num_bytes])),
-            rlc.gamma,
-            self.squeeze_digests.drain(..).collect(),
-        );
+        let squeeze_digests = self.squeeze_digests.drain(..).collect();
+        let (input_rlcs, output_rlcs) = self.params.with_env(|| {
+            multi_keccak_phase1(
+                &mut ctx.region,
+                keccak_table,
+                self.fixed_len_queries
+                    .iter()
+                    .map(|q| &q.input_bytes[..])
+                    .chain(self.var_len_queries.iter().map(|q| &q.input_bytes[..q.num_bytes])),
+                rlc.gamma,
+                squeeze_digests,
+            )
+        });
 
         let mut min_keccak_f = 0;
         let mut max_keccak_f = 0;
@@ -479,15 +722,18 @@ impl<'v, F: Field> KeccakChip<'v, F> {
 
     #[cfg(feature = "display")]
     pub fn print_stats(ctx: &Context<F>, num_keccak_f: usize) {
-        use zkevm_keccak::util::NUM_WORDS_TO_ABSORB;
-
         println!("Number of keccak_f permutations: {num_keccak_f}");
-        let rows_per_round =
-            ctx.max_rows / (num_keccak_f * (NUM_ROUNDS + 1) + 1 + NUM_WORDS_TO_ABSORB);
+        let rows_per_round = max_keccak_rows_per_round(num_keccak_f, ctx.max_rows);
         println!("Optimal keccak rows per round: {rows_per_round}");
     }
 }
 
+/// Whether `a` and `b` are the same cells, not just cells with the same values. Deduplicating queries by cell
+/// keeps the keccak table the same for any witness, so it does not change the verifying key.
+fn same_cells<F: ScalarField>(a: &[AssignedValue<F>], b: &[AssignedValue<F>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.cell() == b.cell())
+}
+
 // convert field values to u8:
 pub fn get_bytes<F: ScalarField>(bytes_assigned: &[AssignedValue<F>]) -> Vec<u8> {
     bytes_assigned
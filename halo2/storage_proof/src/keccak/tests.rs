@@ -42,11 +42,30 @@ pub struct TestKeccakConfig<F: Field> {
     range: RangeConfig<F>,
     rlc: RlcConfig<F>,
     keccak: KeccakConfig<F>,
+    keccak_params: KeccakParams,
+}
+
+/// A query of [`KeccakCircuit`] on the cells assigned to `inputs[idx]`.
+#[derive(Clone, Copy, Debug)]
+pub enum KeccakQuery {
+    Fixed(usize),
+    Var(usize),
 }
 
 #[derive(Clone, Debug)]
 pub struct KeccakCircuit {
     inputs: Vec<Vec<u8>>,
+    queries: Vec<KeccakQuery>,
+    /// The query index each query must return, if given.
+    expected_indices: Option<Vec<usize>>,
+}
+
+impl KeccakCircuit {
+    /// Makes a variable length query of each input.
+    pub fn new(inputs: Vec<Vec<u8>>) -> Self {
+        let queries = (0..inputs.len()).map(KeccakQuery::Var).collect();
+        Self { inputs, queries, expected_indices: None }
+    }
 }
 
 impl<F: Field> Circuit<F> for KeccakCircuit {
@@ -54,7 +73,10 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self { inputs: self.inputs.iter().map(|input| vec![0u8; input.len()]).collect() }
+        Self {
+            inputs: self.inputs.iter().map(|input| vec![0u8; input.len()]).collect(),
+            ..self.clone()
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -66,6 +88,17 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
             var("KECCAK_DEGREE").unwrap_or_else(|_| "14".to_string()).parse().unwrap();
         let num_rlc_columns: usize =
             var("NUM_RLC").unwrap_or_else(|_| "1".to_string()).parse().unwrap();
+        let keccak_params = KeccakParams {
+            degree: degree as u32,
+            rows_per_round: var("KECCAK_ROWS")
+                .unwrap_or_else(|_| "25".to_string())
+                .parse()
+                .unwrap(),
+            unusable_rows: var("UNUSABLE_ROWS")
+                .unwrap_or_else(|_| "109".to_string())
+                .parse()
+                .unwrap(),
+        };
         let mut range = RangeConfig::configure(
             meta,
             RangeStrategy::Vertical,
@@ -78,13 +111,13 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
         );
         let rlc = RlcConfig::configure(meta, num_rlc_columns, 1);
         log::info!("unusable rows before keccak: {}", meta.minimum_rows());
-        let keccak = KeccakConfig::new(meta, rlc.gamma);
+        let keccak = configure_keccak(meta, rlc.gamma, keccak_params);
         println!("unusable rows after keccak: {}", meta.minimum_rows());
 
         let num_rows = (1 << degree) - meta.minimum_rows();
         range.gate.max_rows = num_rows;
 
-        TestKeccakConfig { range, rlc, keccak }
+        TestKeccakConfig { range, rlc, keccak, keccak_params }
     }
 
     fn synthesize(
@@ -95,7 +128,8 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
         let witness_time = start_timer!(|| "time witness gen");
 
         config.range.load_lookup_table(&mut layouter).expect("load range lookup table");
-        config.keccak.load_aux_tables(&mut layouter).expect("load keccak lookup tables");
+        load_keccak_aux_tables(&config.keccak, config.keccak_params, &mut layouter)
+            .expect("load keccak lookup tables");
         let mut first_pass = SKIP_FIRST_PASS;
         layouter
             .assign_region(
@@ -117,13 +151,14 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
                     let ctx = &mut aux;
 
                     let mut rlc_chip = RlcChip::new(config.rlc.clone(), Value::unknown());
-                    let mut keccak_chip = KeccakChip::new(config.keccak.clone());
+                    let mut keccak_chip =
+                        KeccakChip::new(config.keccak.clone(), config.keccak_params);
 
+                    let mut assigned = Vec::with_capacity(self.inputs.len());
                     for (_idx, input) in self.inputs.iter().enumerate() {
-                        let bytes = input.to_vec();
                         let bytes_assigned = config.range.gate.assign_witnesses(
                             ctx,
-                            bytes.iter().map(|byte| Value::known(F::from(*byte as u64))),
+                            input.iter().map(|byte| Value::known(F::from(*byte as u64))),
                         );
                         // append some extra bytes to test variable length (don't do this for bench since it'll mess up the capacity)
                         // let zero = config.range.gate.load_zero(ctx);
@@ -133,15 +168,31 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
                             .range
                             .gate
                             .load_witness(ctx, Value::known(F::from(input.len() as u64)));
+                        assigned.push((bytes_assigned, len));
+                    }
 
-                        let _hash = keccak_chip.keccak_var_len(
-                            ctx,
-                            &config.range,
-                            bytes_assigned,
-                            Some(bytes),
-                            len,
-                            0,
-                        );
+                    let mut indices = Vec::with_capacity(self.queries.len());
+                    for query in &self.queries {
+                        let idx = match *query {
+                            KeccakQuery::Fixed(idx) => keccak_chip.keccak_fixed_len(
+                                ctx,
+                                &config.range.gate,
+                                assigned[idx].0.clone(),
+                                Some(self.inputs[idx].clone()),
+                            ),
+                            KeccakQuery::Var(idx) => keccak_chip.keccak_var_len(
+                                ctx,
+                                &config.range,
+                                assigned[idx].0.clone(),
+                                Some(self.inputs[idx].clone()),
+                                assigned[idx].1.clone(),
+                                0,
+                            ),
+                        };
+                        indices.push(idx);
+                    }
+                    if let Some(expected_indices) = &self.expected_indices {
+                        assert_eq!(&indices, expected_indices);
                     }
                     keccak_chip.assign_phase0(&mut ctx.region);
                     config.range.finalize(ctx);
@@ -167,6 +218,25 @@ impl<F: Field> Circuit<F> for KeccakCircuit {
     }
 }
 
+#[test]
+pub fn test_keccak_dedup() {
+    // the same values in two sets of cells: the queries of the second set are not deduplicated
+    let input = (0u8..200).collect_vec();
+    let circuit = KeccakCircuit {
+        inputs: vec![input.clone(), input],
+        queries: vec![
+            KeccakQuery::Fixed(0),
+            KeccakQuery::Fixed(0),
+            KeccakQuery::Fixed(1),
+            KeccakQuery::Var(0),
+            KeccakQuery::Var(0),
+            KeccakQuery::Var(1),
+        ],
+        expected_indices: Some(vec![0, 0, 1, 0, 0, 1]),
+    };
+    MockProver::<Fr>::run(14, &circuit, vec![]).unwrap().assert_satisfied();
+}
+
 /// Cmdline: NUM_ADVICE=1 KECCAK_ROWS=25 KECCAK_DEGREE=14 RUST_LOG=info cargo test -- --nocapture test_keccak
 #[test]
 pub fn test_keccak() {
@@ -180,12 +250,33 @@ pub fn test_keccak() {
         (0u8..136).collect::<Vec<_>>(),
         (0u8..200).collect::<Vec<_>>(),
     ];
-    let circuit = KeccakCircuit { inputs };
+    let circuit = KeccakCircuit::new(inputs);
 
     let prover = MockProver::<Fr>::run(k, &circuit, vec![]).unwrap();
     prover.assert_satisfied();
 }
 
+#[test]
+pub fn test_keccak_capacity() {
+    let params = KeccakParams { degree: 14, rows_per_round: 25, unusable_rows: 109 };
+    // (2^14 - 109) / 25 = 651 rounds, of which 18 hold no permutation
+    assert_eq!(params.max_keccak_f(), 25);
+    assert_eq!(
+        params.max_keccak_f(),
+        params.with_env(|| get_keccak_capacity(params.usable_rows()))
+    );
+    let usage = params.usage(25);
+    assert!(usage.fits() && usage.rows <= usage.usable_rows);
+
+    let usage = params.usage(26);
+    assert!(!usage.fits() && usage.rows > usage.usable_rows);
+    let rows_per_round = max_keccak_rows_per_round(usage.num_keccak_f, usage.usable_rows);
+    assert_eq!(rows_per_round, 24);
+    assert!(KeccakParams { rows_per_round, ..params }.usage(26).fits());
+    let err = KeccakCapacityError { params, usage }.to_string();
+    assert!(err.contains("need 26 keccak_f permutations") && err.contains("at most 24"), "{err}");
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct KeccakBenchConfig {
     degree: usize,
@@ -229,7 +320,7 @@ fn bench_keccak() {
 
         // the inputs can be different lengths, but they must be known _fixed_ lengths
         // use as many keccak_f as possible
-        let circuit = KeccakCircuit { inputs: vec![vec![0; 135]; capacity] };
+        let circuit = KeccakCircuit::new(vec![vec![0; 135]; capacity]);
 
         // MockProver::<Fr>::run(k, &circuit, vec![]).unwrap().assert_satisfied();
 
@@ -240,7 +331,7 @@ fn bench_keccak() {
         let inputs = (0..capacity)
             .map(|_| (0..135).map(|_| rand::random::<u8>()).collect_vec())
             .collect_vec();
-        let proof_circuit = KeccakCircuit { inputs };
+        let proof_circuit = KeccakCircuit::new(inputs);
 
         // create a proof
         let proof_time = start_timer!(|| "Create proof SHPLONK");
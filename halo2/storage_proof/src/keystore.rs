@@ -0,0 +1,247 @@
+//! On-disk store for proving and verifying keys.
+//!
+//! Keys are stored under a [`CircuitFingerprint`], which identifies the shape of a circuit by its parameters
+//! (config params, `k`, network, max depths, ...). Each key is written alongside a small metadata file containing
+//! the full fingerprint, including digests of the verifying key and of the trusted setup the keys were made with.
+//! The verifying key commits to the constraint system, the fixed columns and the copy constraints, so any code
+//! change to the circuit, in `configure` or in `synthesize`, changes it. A key whose metadata does not match the
+//! circuit and setup it is requested for is considered stale and is regenerated.
+use crate::Network;
+use ethers_core::utils::keccak256;
+use halo2_base::halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
+    poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+/// Format used for all keys in the store. Keys are only ever read back by this crate on the same machine
+/// architecture, so we skip the (slow) curve point validity checks on load.
+pub const KEY_SERDE_FORMAT: SerdeFormat = SerdeFormat::RawBytesUnchecked;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitFingerprint {
+    /// Human readable circuit name, used as the file name prefix.
+    pub name: String,
+    pub k: u32,
+    pub network: Option<Network>,
+    /// Circuit configuration parameters, e.g. `EthConfigParams`.
+    pub config: serde_json::Value,
+    pub max_depths: Vec<usize>,
+    /// keccak256 of the trusted setup, see [`srs_digest`]. Set by the key store.
+    pub srs_digest: String,
+    /// Transcript representation of the verifying key, which hashes the constraint system together with the
+    /// commitments to the fixed and permutation columns. Set by the key store.
+    /// This catches code changes to the circuit that are not reflected in the other fields.
+    pub vk_digest: String,
+}
+
+impl CircuitFingerprint {
+    /// Creates a fingerprint with the given name and `k`; the key digests are filled in by the [`KeyStore`]
+    /// once it has the setup and the verifying key.
+    pub fn new(name: impl Into<String>, k: u32) -> Self {
+        Self {
+            name: name.into(),
+            k,
+            network: None,
+            config: serde_json::Value::Null,
+            max_depths: vec![],
+            srs_digest: String::new(),
+            vk_digest: String::new(),
+        }
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn config(mut self, config: &impl Serialize) -> Self {
+        self.config = serde_json::to_value(config).expect("config should serialize to json");
+        self
+    }
+
+    pub fn max_depths(mut self, max_depths: impl IntoIterator<Item = usize>) -> Self {
+        self.max_depths = max_depths.into_iter().collect();
+        self
+    }
+
+    /// The fingerprint of keys made with `params` that have verifying key `vk`.
+    pub fn keys(mut self, params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>) -> Self {
+        self.srs_digest = srs_digest(params);
+        self.vk_digest = format!("{:?}", vk.transcript_repr());
+        self
+    }
+
+    /// Identifier of the circuit shape, excluding the key digests: a circuit whose code or setup changed maps to
+    /// the same id, so the old key is found and detected as stale rather than silently left behind.
+    pub fn id(&self) -> String {
+        let mut shape = self.clone();
+        shape.srs_digest.clear();
+        shape.vk_digest.clear();
+        let digest = keccak256(serde_json::to_vec(&shape).unwrap());
+        format!("{}_{}", self.name, hex_encode(&digest[..8]))
+    }
+}
+
+/// keccak256 of the points of the setup that a proof is checked against: `g[0]`, `g2` and `s_g2`. Keys made with
+/// a `gen_srs` setup thus never pass as keys for the ceremony setup of the same `k`, and vice versa.
+pub fn srs_digest(params: &ParamsKZG<Bn256>) -> String {
+    let points = format!("{:?}{:?}{:?}", params.get_g()[0], params.g2(), params.s_g2());
+    hex_encode(&keccak256(points.as_bytes()))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyStore {
+    pub dir: PathBuf,
+}
+
+impl Default for KeyStore {
+    fn default() -> Self {
+        Self::new("data/keys")
+    }
+}
+
+impl KeyStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn pk_path(&self, fingerprint: &CircuitFingerprint) -> PathBuf {
+        self.dir.join(format!("{}.pk", fingerprint.id()))
+    }
+
+    pub fn vk_path(&self, fingerprint: &CircuitFingerprint) -> PathBuf {
+        self.dir.join(format!("{}.vk", fingerprint.id()))
+    }
+
+    pub fn meta_path(&self, fingerprint: &CircuitFingerprint) -> PathBuf {
+        self.dir.join(format!("{}.json", fingerprint.id()))
+    }
+
+    /// Returns true if keys for `fingerprint` were written by this store and the circuit has not changed since.
+    pub fn is_fresh(&self, fingerprint: &CircuitFingerprint) -> bool {
+        let stored: Option<CircuitFingerprint> = File::open(self.meta_path(fingerprint))
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok());
+        match stored {
+            Some(stored) if &stored == fingerprint => true,
+            Some(stored) => {
+                log_stale(fingerprint, &stored);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records that the keys currently on disk for `fingerprint` match it. Call after writing the keys.
+    pub fn mark_fresh(&self, fingerprint: &CircuitFingerprint) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let f = File::create(self.meta_path(fingerprint))?;
+        serde_json::to_writer_pretty(BufWriter::new(f), fingerprint)?;
+        Ok(())
+    }
+
+    pub fn read_pk<C: Circuit<Fr>>(
+        &self,
+        fingerprint: &CircuitFingerprint,
+    ) -> io::Result<ProvingKey<G1Affine>> {
+        if !self.is_fresh(fingerprint) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no fresh proving key in store"));
+        }
+        let f = File::open(self.pk_path(fingerprint))?;
+        ProvingKey::read::<_, C>(&mut BufReader::with_capacity(BUFFER_SIZE, f), KEY_SERDE_FORMAT)
+    }
+
+    pub fn read_vk<C: Circuit<Fr>>(
+        &self,
+        fingerprint: &CircuitFingerprint,
+    ) -> io::Result<VerifyingKey<G1Affine>> {
+        if !self.is_fresh(fingerprint) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no fresh verifying key in store"));
+        }
+        let f = File::open(self.vk_path(fingerprint))?;
+        VerifyingKey::read::<_, C>(&mut BufReader::with_capacity(BUFFER_SIZE, f), KEY_SERDE_FORMAT)
+    }
+
+    /// Writes both the proving key and its verifying key.
+    pub fn write_pk(
+        &self,
+        fingerprint: &CircuitFingerprint,
+        pk: &ProvingKey<G1Affine>,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let f = File::create(self.pk_path(fingerprint))?;
+        pk.write(&mut BufWriter::with_capacity(BUFFER_SIZE, f), KEY_SERDE_FORMAT)?;
+        let f = File::create(self.vk_path(fingerprint))?;
+        pk.get_vk().write(&mut BufWriter::with_capacity(BUFFER_SIZE, f), KEY_SERDE_FORMAT)?;
+        self.mark_fresh(fingerprint)
+    }
+
+    /// Loads the proving key for `fingerprint` if a fresh one exists, otherwise generates it from `circuit`
+    /// and writes it to the store.
+    ///
+    /// The verifying key is always generated from `circuit` and `params`, which is much cheaper than the proving
+    /// key, to tell whether the stored keys are fresh.
+    pub fn get_or_gen_pk<C: Circuit<Fr>>(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        circuit: &C,
+        fingerprint: &CircuitFingerprint,
+    ) -> ProvingKey<G1Affine> {
+        let vk = keygen_vk(params, circuit).expect("keygen_vk should not fail");
+        let fingerprint = fingerprint.clone().keys(params, &vk);
+        if let Ok(pk) = self.read_pk::<C>(&fingerprint) {
+            return pk;
+        }
+        let pk = keygen_pk(params, vk, circuit).expect("keygen_pk should not fail");
+        self.write_pk(&fingerprint, &pk).expect("writing proving key should not fail");
+        pk
+    }
+
+    /// Generates the verifying key of `circuit` and writes it to the store, unless fresh keys are stored already.
+    /// The verifying key is only written if there is no proving key, to avoid marking a stale proving key as
+    /// fresh.
+    pub fn get_or_gen_vk<C: Circuit<Fr>>(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        circuit: &C,
+        fingerprint: &CircuitFingerprint,
+    ) -> VerifyingKey<G1Affine> {
+        let vk = keygen_vk(params, circuit).expect("keygen_vk should not fail");
+        let fingerprint = &fingerprint.clone().keys(params, &vk);
+        if !self.is_fresh(fingerprint) && !Path::new(&self.pk_path(fingerprint)).exists() {
+            let write = || -> io::Result<()> {
+                fs::create_dir_all(&self.dir)?;
+                let f = File::create(self.vk_path(fingerprint))?;
+                vk.write(&mut BufWriter::with_capacity(BUFFER_SIZE, f), KEY_SERDE_FORMAT)?;
+                self.mark_fresh(fingerprint)
+            };
+            write().expect("writing verifying key should not fail");
+        }
+        vk
+    }
+}
+
+fn log_stale(fingerprint: &CircuitFingerprint, stored: &CircuitFingerprint) {
+    let reason = if stored.srs_digest != fingerprint.srs_digest {
+        "the keys were made with a different trusted setup"
+    } else if stored.vk_digest != fingerprint.vk_digest {
+        "the circuit changed"
+    } else {
+        "the circuit parameters changed"
+    };
+    log::warn!("Stale keys for {} found in key store: {reason}. Regenerating.", fingerprint.id());
+}
@@ -1,9 +1,16 @@
 #![feature(int_log)]
 
 pub mod block_header;
+pub mod bytes;
+pub mod error;
 pub mod keccak;
+pub mod keystore;
 pub mod mpt;
+pub mod params;
 pub mod rlp;
+pub mod sha256;
+pub mod sizing;
+pub mod ssz;
 pub mod storage;
 pub mod util;
 
@@ -18,18 +25,20 @@ use halo2_base::{
     gates::{flex_gate::FlexGateConfig, range::RangeConfig},
     halo2_proofs::{
         self,
-        circuit::Value,
-        plonk::{Column, ConstraintSystem, Instance},
+        circuit::{Layouter, Value},
+        plonk::{Column, ConstraintSystem, Error, Instance},
     },
     Context,
 };
 use keccak::KeccakChip;
 use mpt::{MPTChip, MPTConfig};
+use serde::{Deserialize, Serialize};
 use util::EthConfigParams;
 pub use zkevm_keccak::util::eth_types::Field;
 use zkevm_keccak::KeccakConfig;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum Network {
     Mainnet,
@@ -79,6 +88,9 @@ impl<F: Field> EthConfig<F> {
     pub fn keccak(&self) -> &KeccakConfig<F> {
         &self.mpt.keccak
     }
+    pub fn load_keccak_aux_tables(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.mpt.load_keccak_aux_tables(layouter)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -118,7 +130,9 @@ impl<'v, F: Field> EthChip<'v, F> {
     /// Generates and assign witnesses for keccak.
     /// Assign cells to range check to special advice columns with lookup enabled.
     pub fn assign_phase0(&mut self, ctx: &mut Context<F>) {
+        sizing::record_keccak(self.keccak());
         self.mpt.keccak.assign_phase0(&mut ctx.region);
+        sizing::record_phase(ctx);
         self.range().finalize(ctx);
     }
 
@@ -0,0 +1,317 @@
+//! Inclusion proofs of several keys in the same trie, with the nodes shared between the proofs parsed and hashed
+//! once. Each proof references the nodes on its path by their index in a table of distinct nodes, so the keccak
+//! queries scale with the number of distinct nodes rather than the number of keys times the depth.
+use super::{
+    max_ext_lens, BranchTraceWitness, ExtensionTraceWitness, MPTChip, MPTFixedKeyInput,
+    MPTFixedKeyProof, MPTFixedKeyProofWitness, MPTNode, MPTPathNode, DUMMY_BRANCH, MAX_BRANCH_LENS,
+};
+use crate::{error::MPTProofError, halo2_proofs::circuit::Value, Field};
+use halo2_base::{
+    gates::{GateInstructions, RangeInstructions},
+    AssignedValue, Context,
+    QuantumCell::Existing,
+};
+use itertools::Itertools;
+use rlp::Rlp;
+use std::{cmp::max, collections::HashMap, iter::repeat};
+
+/// Inclusion proofs of keys in the same trie, to be checked together.
+#[derive(Clone, Debug)]
+pub struct MPTBatchInput {
+    /// The proofs, all with the same root and max lengths
+    pub proofs: Vec<MPTFixedKeyInput>,
+    /// The max number of distinct nodes above the leaves, over all proofs. This is part of the circuit shape.
+    pub max_nodes: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct MPTBatchProof<'v, F: Field> {
+    /// The distinct nodes above the leaves, padded with dummy branches to the max number of nodes
+    pub nodes: Vec<MPTNode<'v, F>>,
+    /// The proof of each key, with no `nodes`
+    pub proofs: Vec<MPTFixedKeyProof<'v, F>>,
+    /// For each proof, the index in `nodes` of each node on its path above the leaf, padded to `max_depth - 1`
+    pub node_idxs: Vec<Vec<AssignedValue<'v, F>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MPTBatchWitness<'v, F: Field> {
+    pub node_types: Vec<AssignedValue<'v, F>>,
+    pub exts_parsed: Vec<ExtensionTraceWitness<'v, F>>,
+    pub branches_parsed: Vec<BranchTraceWitness<'v, F>>,
+    pub proofs: Vec<MPTFixedKeyProofWitness<'v, F>>,
+    pub node_idxs: Vec<Vec<AssignedValue<'v, F>>>,
+}
+
+impl MPTBatchInput {
+    /// A batch with room for exactly the distinct nodes of `proofs`.
+    pub fn new(proofs: Vec<MPTFixedKeyInput>) -> Self {
+        let mut batch = Self { proofs, max_nodes: 0 };
+        batch.max_nodes = batch.min_max_nodes();
+        batch
+    }
+
+    /// The distinct nodes above the leaves, in order of first appearance, and for each proof the index of each of
+    /// its nodes above the leaf.
+    fn node_table(&self) -> (Vec<&[u8]>, Vec<Vec<usize>>) {
+        let mut nodes = vec![];
+        let mut node_idx = HashMap::new();
+        let node_idxs = self
+            .proofs
+            .iter()
+            .map(|pf| {
+                let above_leaf = &pf.proof[..pf.proof.len().saturating_sub(1)];
+                above_leaf
+                    .iter()
+                    .map(|node| {
+                        *node_idx.entry(&node[..]).or_insert_with(|| {
+                            nodes.push(&node[..]);
+                            nodes.len() - 1
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        (nodes, node_idxs)
+    }
+
+    /// The number of distinct nodes above the leaves, and at least 1 so that padding nodes have a node to point to.
+    pub fn min_max_nodes(&self) -> usize {
+        max(self.node_table().0.len(), 1)
+    }
+
+    /// Checks that every proof can be assigned, that the proofs share the same root and max lengths, and that
+    /// their distinct nodes fit `max_nodes`.
+    pub fn validate(&self) -> Result<(), MPTProofError> {
+        for pf in &self.proofs {
+            pf.validate()?;
+        }
+        if let Some(first) = self.proofs.first() {
+            for pf in &self.proofs[1..] {
+                if pf.root_hash != first.root_hash {
+                    return Err(MPTProofError::Mismatch {
+                        node: 0,
+                        reason: format!(
+                            "proof of key {:?} has root {:?}, expected {:?}",
+                            pf.path, pf.root_hash, first.root_hash
+                        ),
+                    });
+                }
+                if (pf.value_max_byte_len, pf.max_depth)
+                    != (first.value_max_byte_len, first.max_depth)
+                {
+                    return Err(MPTProofError::Mismatch {
+                        node: 0,
+                        reason: format!("proof of key {:?} has different max lengths", pf.path),
+                    });
+                }
+            }
+        }
+        let nodes = self.min_max_nodes();
+        if nodes > self.max_nodes {
+            return Err(MPTProofError::TooManyNodes { nodes, max_nodes: self.max_nodes });
+        }
+        Ok(())
+    }
+
+    /// Verifies every proof, see [`MPTFixedKeyInput::verify_native`]. Returns the first mismatch.
+    pub fn verify_native(&self) -> Result<(), MPTProofError> {
+        self.validate()?;
+        self.proofs.iter().try_for_each(MPTFixedKeyInput::verify_native)
+    }
+
+    pub fn assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> MPTBatchProof<'v, F> {
+        if let Err(e) = self.validate() {
+            panic!("{e}");
+        }
+        let (_, max_ext_bytes) = max_ext_lens(32);
+        let max_node_bytes = max(max_ext_bytes, MAX_BRANCH_LENS.1);
+        let (nodes, node_idxs) = self.node_table();
+        let mut table = nodes
+            .into_iter()
+            .map(|node| {
+                let mut node = node.to_vec();
+                let is_ext = Rlp::new(&node).item_count().unwrap() == 2;
+                node.resize(max_node_bytes, 0);
+                (node, is_ext)
+            })
+            .collect_vec();
+        let mut dummy_branch = DUMMY_BRANCH.clone();
+        dummy_branch.resize(max_node_bytes, 0);
+        table.resize(self.max_nodes, (dummy_branch, false));
+        // the nodes past the depth of a proof are parsed as a branch, like the dummy nodes of a single proof
+        let padding_idx = table.iter().position(|(_, is_ext)| !is_ext).unwrap_or(0);
+
+        let nodes = table
+            .into_iter()
+            .map(|(node_bytes, is_ext)| {
+                let rlp_bytes = gate.assign_witnesses(
+                    ctx,
+                    node_bytes.iter().map(|x| Value::known(F::from(*x as u64))),
+                );
+                let node_type = gate.load_witness(ctx, Value::known(F::from(is_ext)));
+                MPTNode { rlp_bytes, node_type }
+            })
+            .collect();
+        let (proofs, node_idxs) = self
+            .proofs
+            .iter()
+            .zip(node_idxs)
+            .map(|(pf, mut idxs)| {
+                let proof = pf.assign_with_nodes(ctx, gate, false);
+                idxs.resize(pf.max_depth - 1, padding_idx);
+                let idxs = gate.assign_witnesses(
+                    ctx,
+                    idxs.into_iter().map(|idx| Value::known(F::from(idx as u64))),
+                );
+                (proof, idxs)
+            })
+            .unzip();
+        MPTBatchProof { nodes, proofs, node_idxs }
+    }
+
+    /// Same as [`MPTBatchInput::assign`], but returns an error instead of panicking if a proof is invalid.
+    pub fn try_assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> Result<MPTBatchProof<'v, F>, MPTProofError> {
+        self.validate()?;
+        Ok(self.assign(ctx, gate))
+    }
+}
+
+impl<'v, F: Field> MPTBatchProof<'v, F> {
+    /// Assigns a batch of unknown values with the same shape as the batch assigned by [`MPTBatchInput::assign`]
+    /// for `num_proofs` proofs with the given max lengths, for keygen.
+    pub fn assign_unknown(
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+        num_proofs: usize,
+        value_max_byte_len: usize,
+        max_depth: usize,
+        max_nodes: usize,
+    ) -> Self {
+        let (_, max_ext_bytes) = max_ext_lens(32);
+        let max_node_bytes = max(max_ext_bytes, MAX_BRANCH_LENS.1);
+
+        // assign in the same order as `MPTBatchInput::assign`
+        let nodes = (0..max_nodes)
+            .map(|_| {
+                let rlp_bytes =
+                    gate.assign_witnesses(ctx, repeat(Value::unknown()).take(max_node_bytes));
+                let node_type = gate.load_witness(ctx, Value::unknown());
+                MPTNode { rlp_bytes, node_type }
+            })
+            .collect();
+        let (proofs, node_idxs) = (0..num_proofs)
+            .map(|_| {
+                let proof = MPTFixedKeyProof::assign_unknown_with_nodes(
+                    ctx,
+                    gate,
+                    value_max_byte_len,
+                    max_depth,
+                    false,
+                );
+                let idxs = gate.assign_witnesses(ctx, repeat(Value::unknown()).take(max_depth - 1));
+                (proof, idxs)
+            })
+            .unzip();
+        MPTBatchProof { nodes, proofs, node_idxs }
+    }
+}
+
+impl<'v, F: Field> MPTChip<'v, F> {
+    /// Parses and hashes the distinct nodes of the batch once, and parses the leaf and key fragments of each
+    /// proof. All proofs are constrained to have the same root.
+    pub fn parse_mpt_batch_inclusion_fixed_key_phase0(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        batch: MPTBatchProof<'v, F>,
+        key_byte_len: usize,
+        value_max_byte_len: usize,
+        max_depth: usize,
+    ) -> MPTBatchWitness<'v, F> {
+        let MPTBatchProof { nodes, proofs, node_idxs } = batch;
+        let (exts_parsed, branches_parsed) = self.parse_nodes_phase0(ctx, &nodes, key_byte_len);
+        for idx in node_idxs.iter().flatten() {
+            self.range().check_less_than_safe(ctx, idx, nodes.len() as u64);
+        }
+        if let Some(first) = proofs.first() {
+            for proof in &proofs[1..] {
+                for (root_byte, first_byte) in
+                    proof.root_hash_bytes.iter().zip(first.root_hash_bytes.iter())
+                {
+                    ctx.constrain_equal(root_byte, first_byte);
+                }
+            }
+        }
+        let proofs = proofs
+            .into_iter()
+            .map(|proof| {
+                debug_assert_eq!(proof.key_byte_len, key_byte_len);
+                debug_assert_eq!(proof.value_max_byte_len, value_max_byte_len);
+                debug_assert_eq!(proof.max_depth, max_depth);
+                self.parse_key_path_phase0(ctx, proof)
+            })
+            .collect();
+        MPTBatchWitness {
+            node_types: nodes.into_iter().map(|node| node.node_type).collect(),
+            exts_parsed,
+            branches_parsed,
+            proofs,
+            node_idxs,
+        }
+    }
+
+    /// Checks the inclusion of every proof of the batch, reading the nodes on its path from the parsed distinct
+    /// nodes by index.
+    pub fn parse_mpt_batch_inclusion_fixed_key_phase1(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: MPTBatchWitness<'v, F>,
+    ) {
+        debug_assert_eq!(ctx.current_phase(), 1);
+        let MPTBatchWitness { node_types, exts_parsed, branches_parsed, proofs, node_idxs } =
+            witness;
+        let exts_parsed =
+            exts_parsed.into_iter().map(|x| self.parse_ext_phase1(ctx, x)).collect_vec();
+        let branches_parsed = branches_parsed
+            .into_iter()
+            .map(|x| self.parse_nonterminal_branch_phase1(ctx, x))
+            .collect_vec();
+        let table = node_types
+            .iter()
+            .zip(exts_parsed.iter())
+            .zip(branches_parsed.iter())
+            .map(|((node_type, ext_parsed), branch_parsed)| {
+                self.path_node(ctx, node_type, ext_parsed, branch_parsed)
+            })
+            .collect_vec();
+        let table_cells = table.iter().map(MPTPathNode::cells).collect_vec();
+
+        for (proof, idxs) in proofs.into_iter().zip(node_idxs) {
+            let path_nodes = idxs
+                .iter()
+                .map(|idx| {
+                    let indicator = self.gate().idx_to_indicator(ctx, Existing(idx), table.len());
+                    let cells = (0..table_cells[0].len())
+                        .map(|i| {
+                            self.gate().inner_product(
+                                ctx,
+                                table_cells.iter().map(|cells| Existing(cells[i])),
+                                indicator.iter().map(Existing),
+                            )
+                        })
+                        .collect();
+                    MPTPathNode::from_cells(cells)
+                })
+                .collect();
+            self.parse_key_path_phase1(ctx, proof, path_nodes);
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{
-    halo2_proofs::{circuit::Value, plonk::ConstraintSystem},
-    keccak::{KeccakChip, KeccakConfig},
+    error::MPTProofError,
+    halo2_proofs::{
+        circuit::{Layouter, Value},
+        plonk::{ConstraintSystem, Error},
+    },
+    keccak::{configure_keccak, load_keccak_aux_tables, KeccakChip, KeccakConfig, KeccakParams},
     rlp::{
         max_rlp_len_len,
         rlc::{
@@ -22,7 +26,18 @@ use halo2_base::{
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use rlp::Rlp;
-use std::{cmp::max, env::set_var, iter::once};
+use std::{
+    cmp::max,
+    iter::{once, repeat},
+    mem,
+};
+
+pub use crate::bytes::AssignedBytes;
+
+pub mod batch;
+pub mod native;
+pub mod transition;
+pub mod trie;
 
 #[cfg(test)]
 mod tests;
@@ -69,7 +84,6 @@ pub struct BranchTraceWitness<'v, F: Field> {
 }
 
 // helper types for readability
-pub type AssignedBytes<'v, F> = Vec<AssignedValue<'v, F>>;
 pub type AssignedNibbles<'v, F> = Vec<AssignedValue<'v, F>>;
 
 #[derive(Clone, Debug)]
@@ -123,6 +137,51 @@ pub struct MPTFixedKeyProofWitness<'v, F: Field> {
     pub key_hexs: AssignedNibbles<'v, F>,
 }
 
+/// A node on the path of a key, reduced to what the inclusion check uses, so that the node can be parsed once for
+/// several keys.
+#[derive(Clone, Debug)]
+struct MPTPathNode<'v, F: Field> {
+    node_type: AssignedValue<'v, F>,
+    /// The reference to this node in its parent
+    node_hash: RlcVarLen<'v, F>,
+    /// The encoded path, if the node is an extension
+    ext_key_path: RlcVarLen<'v, F>,
+    /// The reference to the child, if the node is an extension
+    ext_ref: RlcVarLen<'v, F>,
+    /// The references to the 16 children and the value, if the node is a branch
+    branch_refs: Vec<RlcVarLen<'v, F>>,
+}
+
+impl<'v, F: Field> MPTPathNode<'v, F> {
+    /// All cells of the node, in the order read by [`MPTPathNode::from_cells`].
+    fn cells(&self) -> Vec<&AssignedValue<'v, F>> {
+        let rlcs = [&self.node_hash, &self.ext_key_path, &self.ext_ref]
+            .into_iter()
+            .chain(self.branch_refs.iter());
+        once(&self.node_type).chain(rlcs.flat_map(|rlc| [&rlc.rlc_val, &rlc.len])).collect()
+    }
+
+    fn from_cells(cells: Vec<AssignedValue<'v, F>>) -> Self {
+        let mut cells = cells.into_iter();
+        let node_type = cells.next().unwrap();
+        let mut rlcs = cells.tuples().map(|(rlc_val, len)| RlcVarLen { rlc_val, len });
+        let node_hash = rlcs.next().unwrap();
+        let ext_key_path = rlcs.next().unwrap();
+        let ext_ref = rlcs.next().unwrap();
+        let branch_refs = rlcs.collect();
+        Self { node_type, node_hash, ext_key_path, ext_ref, branch_refs }
+    }
+}
+
+/// The nodes parsed by [`MPTChip::parse_mpt_inclusion_fixed_key_phase1`], for chips that constrain the contents
+/// of the proof beyond inclusion.
+#[derive(Clone, Debug)]
+pub struct MPTFixedKeyProofTrace<'v, F: Field> {
+    pub leaf_parsed: LeafTrace<'v, F>,
+    pub exts_parsed: Vec<ExtensionTrace<'v, F>>,
+    pub branches_parsed: Vec<BranchTrace<'v, F>>,
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct MPTVarKeyProof<'v, F: Field> {
@@ -161,6 +220,7 @@ pub fn max_leaf_lens(max_key_bytes: usize, max_value_bytes: usize) -> (Vec<usize
 }
 
 pub fn max_ext_lens(max_key_bytes: usize) -> (Vec<usize>, usize) {
+    // a hash, or an embedded node of at most 31 bytes
     let max_node_ref_bytes = 32;
     let max_encoded_path_bytes = max_key_bytes + 1;
     let max_encoded_path_rlp_bytes =
@@ -190,6 +250,7 @@ lazy_static! {
 pub struct MPTConfig<F: Field> {
     pub rlp: RlpConfig<F>,
     pub keccak: KeccakConfig<F>,
+    pub keccak_params: KeccakParams,
 }
 
 impl<F: Field> MPTConfig<F> {
@@ -209,14 +270,16 @@ impl<F: Field> MPTConfig<F> {
             context_id,
             degree as usize,
         );
-        set_var("KECCAK_DEGREE", degree.to_string());
-        set_var("KECCAK_ROWS", params.keccak_rows_per_round.to_string());
-        set_var("UNUSABLE_ROWS", params.unusable_rows.to_string());
-        let keccak = KeccakConfig::new(meta, rlp.rlc.gamma);
+        let keccak_params = params.keccak_params();
+        let keccak = configure_keccak(meta, rlp.rlc.gamma, keccak_params);
         #[cfg(feature = "display")]
         println!("Unusable rows: {}", meta.minimum_rows());
         rlp.range.gate.max_rows = (1 << degree) - meta.minimum_rows();
-        Self { rlp, keccak }
+        Self { rlp, keccak, keccak_params }
+    }
+
+    pub fn load_keccak_aux_tables(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        load_keccak_aux_tables(&self.keccak, self.keccak_params, layouter)
     }
 }
 
@@ -228,7 +291,10 @@ pub struct MPTChip<'v, F: Field> {
 
 impl<'v, F: Field> MPTChip<'v, F> {
     pub fn new(config: MPTConfig<F>, gamma: Value<F>) -> MPTChip<'v, F> {
-        Self { rlp: RlpChip::new(config.rlp, gamma), keccak: KeccakChip::new(config.keccak) }
+        Self {
+            rlp: RlpChip::new(config.rlp, gamma),
+            keccak: KeccakChip::new(config.keccak, config.keccak_params),
+        }
     }
 
     pub fn gate(&self) -> &FlexGateConfig<F> {
@@ -327,8 +393,9 @@ impl<'v, F: Field> MPTChip<'v, F> {
         let (max_field_bytes, max_leaf_bytes) = max_leaf_lens(max_key_bytes, max_value_bytes);
         debug_assert_eq!(leaf_bytes.len(), max_leaf_bytes);
 
-        let rlp_witness =
-            self.rlp.decompose_rlp_array_phase0(ctx, leaf_bytes, &max_field_bytes, false);
+        let rlp_witness = self.rlp.with_strict(|rlp| {
+            rlp.decompose_rlp_array_phase0(ctx, leaf_bytes, &max_field_bytes, false)
+        });
         // TODO: remove unnecessary clones by using lifetimes better
         let leaf_hash_query_idx =
             self.mpt_hash_phase0(ctx, rlp_witness.rlp_array.clone(), rlp_witness.rlp_len.clone());
@@ -358,8 +425,14 @@ impl<'v, F: Field> MPTChip<'v, F> {
         let max_ext_bytes = max(max_ext_bytes, max_branch_bytes);
         debug_assert_eq!(ext_bytes.len(), max_ext_bytes);
 
-        let rlp_witness =
-            self.rlp.decompose_rlp_array_phase0(ctx, ext_bytes, &max_field_bytes, false);
+        // the node reference is a hash, or the child node itself if it is shorter than 32 bytes
+        let rlp_witness = self.rlp.decompose_rlp_array_with_embedded_lists_phase0(
+            ctx,
+            ext_bytes,
+            &max_field_bytes,
+            &[false, true],
+            false,
+        );
         // TODO: remove unnecessary clones by using lifetimes better
         let ext_hash_query_idx =
             self.mpt_hash_phase0(ctx, rlp_witness.rlp_array.clone(), rlp_witness.rlp_len.clone());
@@ -388,8 +461,15 @@ impl<'v, F: Field> MPTChip<'v, F> {
         let max_branch_bytes = max(max_ext_bytes, max_branch_bytes);
         assert_eq!(branch_bytes.len(), max_branch_bytes);
 
-        let rlp_witness =
-            self.rlp.decompose_rlp_array_phase0(ctx, branch_bytes, &max_field_bytes, false);
+        // the 16 node references may be embedded nodes, the value may not
+        let embedded_list_fields = [[true; 16].as_slice(), &[false]].concat();
+        let rlp_witness = self.rlp.decompose_rlp_array_with_embedded_lists_phase0(
+            ctx,
+            branch_bytes,
+            &max_field_bytes,
+            &embedded_list_fields,
+            false,
+        );
         let branch_hash_query_idx =
             self.mpt_hash_phase0(ctx, rlp_witness.rlp_array.clone(), rlp_witness.rlp_len.clone());
         BranchTraceWitness { rlp_witness, branch_hash_query_idx, max_branch_bytes }
@@ -432,6 +512,33 @@ impl<'v, F: Field> MPTChip<'v, F> {
         debug_assert_eq!(proof.value_bytes.len(), value_max_byte_len);
         debug_assert_eq!(proof.root_hash_bytes.len(), 32);
 
+        let (exts_parsed, branches_parsed) =
+            self.parse_nodes_phase0(ctx, &proof.nodes, key_byte_len);
+        MPTFixedKeyProofWitness {
+            exts_parsed,
+            branches_parsed,
+            ..self.parse_key_path_phase0(ctx, proof)
+        }
+    }
+
+    /// Checks that the RLP of `nodes` consists of bytes and that node_types[idx] in {0, 1}.
+    fn check_nodes_phase0(&self, ctx: &mut Context<'v, F>, nodes: &[MPTNode<'v, F>]) {
+        for byte in nodes.iter().flat_map(|node| node.rlp_bytes.iter()) {
+            self.range().range_check(ctx, byte, 8);
+        }
+        for node in nodes {
+            self.gate().assert_bit(ctx, &node.node_type);
+        }
+    }
+
+    /// Parses `nodes` both as extensions and as branches, with the other type replaced by a dummy node, and hashes
+    /// them.
+    fn parse_nodes_phase0(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        nodes: &[MPTNode<'v, F>],
+        key_byte_len: usize,
+    ) -> (Vec<ExtensionTraceWitness<'v, F>>, Vec<BranchTraceWitness<'v, F>>) {
         let ext_max_byte_len = Self::ext_max_byte_len(key_byte_len);
         let branch_max_byte_len = Self::branch_max_byte_len();
         let node_max_byte_len = max(ext_max_byte_len, branch_max_byte_len);
@@ -445,56 +552,15 @@ impl<'v, F: Field> MPTChip<'v, F> {
         let dummy_branch: Vec<_> =
             dummy_branch.into_iter().map(|b| Constant(F::from(b as u64))).collect();
 
-        /* Validate inputs, check that:
-         * all inputs are bytes
-         * node_types[idx] in {0, 1}
-         * key_frag_is_odd[idx] in {0, 1}
-         * key_frag_hexes are hexs
-         * 0 < depth <= max_depth
-         * 0 <= value_byte_len <= value_max_byte_len
-         * 0 <= key_frag_byte_len[idx] <= key_byte_len + 1
-         */
-        for byte in proof
-            .key_bytes
-            .iter()
-            .chain(proof.value_bytes.iter())
-            .chain(proof.root_hash_bytes.iter())
-            .chain(proof.leaf_bytes.iter())
-            .chain(proof.nodes.iter().flat_map(|node| node.rlp_bytes.iter()))
-        {
-            self.range().range_check(ctx, byte, 8);
-        }
-        for bit in proof
-            .nodes
-            .iter()
-            .map(|node| &node.node_type)
-            .chain(proof.key_frag.iter().map(|frag| &frag.is_odd))
-        {
-            self.gate().assert_bit(ctx, bit);
-        }
-        for nibble in proof.key_frag.iter().flat_map(|frag| frag.nibbles.iter()) {
-            self.range().range_check(ctx, nibble, 4);
-        }
-        self.range().check_less_than_safe(ctx, &proof.depth, proof.max_depth as u64 + 1);
-        self.range().check_less_than_safe(
-            ctx,
-            &proof.value_byte_len,
-            proof.value_max_byte_len as u64 + 1,
-        );
-        for frag_len in proof.key_frag.iter().map(|frag| &frag.byte_len) {
-            self.range().check_less_than_safe(ctx, frag_len, proof.key_byte_len as u64 + 2);
-        }
+        self.check_nodes_phase0(ctx, nodes);
 
         /* Parse RLP
-         * RLP Leaf      for leaf_bytes
          * RLP Extension for select(dummy_extension[idx], nodes[idx], node_types[idx])
          * RLP Branch    for select(nodes[idx], dummy_branch[idx], node_types[idx])
          */
-        let leaf_parsed =
-            self.parse_leaf_phase0(ctx, proof.leaf_bytes.clone(), key_byte_len, value_max_byte_len);
-        let mut exts_parsed = Vec::with_capacity(max_depth - 1);
-        let mut branches_parsed = Vec::with_capacity(max_depth - 1);
-        for node in proof.nodes.iter() {
+        let mut exts_parsed = Vec::with_capacity(nodes.len());
+        let mut branches_parsed = Vec::with_capacity(nodes.len());
+        for node in nodes.iter() {
             debug_assert_eq!(node.rlp_bytes.len(), node_max_byte_len);
             let (ext_in, branch_in): (Vec<_>, Vec<_>) = node
                 .rlp_bytes
@@ -525,6 +591,59 @@ impl<'v, F: Field> MPTChip<'v, F> {
             let branch_parsed = self.parse_nonterminal_branch_phase0(ctx, branch_in);
             branches_parsed.push(branch_parsed);
         }
+        (exts_parsed, branches_parsed)
+    }
+
+    /// Parses everything in `proof` except `proof.nodes`: the leaf, the key fragments and the key. The returned
+    /// witness has no parsed nodes.
+    fn parse_key_path_phase0(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        proof: MPTFixedKeyProof<'v, F>,
+    ) -> MPTFixedKeyProofWitness<'v, F> {
+        let key_byte_len = proof.key_byte_len;
+        let value_max_byte_len = proof.value_max_byte_len;
+        let max_depth = proof.max_depth;
+
+        /* Validate inputs, check that:
+         * all inputs are bytes
+         * key_frag_is_odd[idx] in {0, 1}
+         * key_frag_hexes are hexs
+         * 0 < depth <= max_depth
+         * 0 <= value_byte_len <= value_max_byte_len
+         * 0 <= key_frag_byte_len[idx] <= key_byte_len + 1
+         * key_frag_is_odd[idx] = 0 if key_frag_byte_len[idx] = 0 (in `hex_prefix_len`)
+         */
+        for byte in proof
+            .key_bytes
+            .iter()
+            .chain(proof.value_bytes.iter())
+            .chain(proof.root_hash_bytes.iter())
+            .chain(proof.leaf_bytes.iter())
+        {
+            self.range().range_check(ctx, byte, 8);
+        }
+        for frag in proof.key_frag.iter() {
+            self.gate().assert_bit(ctx, &frag.is_odd);
+        }
+        for nibble in proof.key_frag.iter().flat_map(|frag| frag.nibbles.iter()) {
+            self.range().range_check(ctx, nibble, 4);
+        }
+        self.range().check_less_than_safe(ctx, &proof.depth, proof.max_depth as u64 + 1);
+        let depth_is_zero = self.gate().is_zero(ctx, &proof.depth);
+        self.gate().assert_is_const(ctx, &depth_is_zero, F::zero());
+        self.range().check_less_than_safe(
+            ctx,
+            &proof.value_byte_len,
+            proof.value_max_byte_len as u64 + 1,
+        );
+        for frag_len in proof.key_frag.iter().map(|frag| &frag.byte_len) {
+            self.range().check_less_than_safe(ctx, frag_len, proof.key_byte_len as u64 + 2);
+        }
+
+        // RLP Leaf for leaf_bytes
+        let leaf_parsed =
+            self.parse_leaf_phase0(ctx, proof.leaf_bytes.clone(), key_byte_len, value_max_byte_len);
 
         // Check key fragment and prefix consistency
         let mut key_frag_ext_bytes = Vec::with_capacity(max_depth - 1);
@@ -576,8 +695,8 @@ impl<'v, F: Field> MPTChip<'v, F> {
         MPTFixedKeyProofWitness {
             proof,
             leaf_parsed,
-            exts_parsed,
-            branches_parsed,
+            exts_parsed: vec![],
+            branches_parsed: vec![],
             key_frag_ext_bytes,
             key_frag_leaf_bytes,
             key_hexs,
@@ -586,10 +705,74 @@ impl<'v, F: Field> MPTChip<'v, F> {
     }
 
     pub fn parse_mpt_inclusion_fixed_key_phase1(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        mut witness: MPTFixedKeyProofWitness<'v, F>,
+    ) -> MPTFixedKeyProofTrace<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 1);
+        let exts_parsed: Vec<ExtensionTrace<'_, F>> = mem::take(&mut witness.exts_parsed)
+            .into_iter()
+            .map(|x| self.parse_ext_phase1(ctx, x))
+            .collect();
+        let branches_parsed: Vec<BranchTrace<'_, F>> = mem::take(&mut witness.branches_parsed)
+            .into_iter()
+            .map(|x| self.parse_nonterminal_branch_phase1(ctx, x))
+            .collect();
+        let path_nodes = witness
+            .proof
+            .nodes
+            .iter()
+            .zip(exts_parsed.iter())
+            .zip(branches_parsed.iter())
+            .map(|((node, ext_parsed), branch_parsed)| {
+                self.path_node(ctx, &node.node_type, ext_parsed, branch_parsed)
+            })
+            .collect();
+        let leaf_parsed = self.parse_key_path_phase1(ctx, witness, path_nodes);
+
+        MPTFixedKeyProofTrace { leaf_parsed, exts_parsed, branches_parsed }
+    }
+
+    /// The parts of a parsed node that the inclusion check uses.
+    fn path_node(
+        &self,
+        ctx: &mut Context<'v, F>,
+        node_type: &AssignedValue<'v, F>,
+        ext_parsed: &ExtensionTrace<'v, F>,
+        branch_parsed: &BranchTrace<'v, F>,
+    ) -> MPTPathNode<'v, F> {
+        let node_hash = rlc_select(
+            ctx,
+            self.gate(),
+            &ext_parsed.ext_hash_rlc,
+            &branch_parsed.branch_hash_rlc,
+            node_type,
+        );
+        let var_len = |trace: &RlcTrace<'v, F>| RlcVarLen {
+            rlc_val: trace.rlc_val.clone(),
+            len: trace.len.clone(),
+        };
+        MPTPathNode {
+            node_type: node_type.clone(),
+            node_hash,
+            ext_key_path: var_len(&ext_parsed.key_path.field_trace),
+            ext_ref: var_len(&ext_parsed.node_ref.field_trace),
+            branch_refs: branch_parsed
+                .node_refs
+                .iter()
+                .map(|node_ref| var_len(&node_ref.field_trace))
+                .collect(),
+        }
+    }
+
+    /// Checks the leaf, key fragments and hash chain of `witness` against the nodes on its path, given by
+    /// `path_nodes` instead of `witness.proof.nodes`.
+    fn parse_key_path_phase1(
         &mut self,
         ctx: &mut Context<'v, F>,
         witness: MPTFixedKeyProofWitness<'v, F>,
-    ) {
+        path_nodes: Vec<MPTPathNode<'v, F>>,
+    ) -> LeafTrace<'v, F> {
         debug_assert_eq!(ctx.current_phase(), 1);
         let MPTFixedKeyProof {
             key_bytes: _,
@@ -597,7 +780,7 @@ impl<'v, F: Field> MPTChip<'v, F> {
             value_byte_len,
             root_hash_bytes,
             leaf_bytes: _,
-            nodes,
+            nodes: _,
             depth,
             key_frag,
             key_byte_len,
@@ -605,13 +788,6 @@ impl<'v, F: Field> MPTChip<'v, F> {
             max_depth,
         } = witness.proof;
         let leaf_parsed = self.parse_leaf_phase1(ctx, witness.leaf_parsed);
-        let exts_parsed: Vec<ExtensionTrace<'_, F>> =
-            witness.exts_parsed.into_iter().map(|x| self.parse_ext_phase1(ctx, x)).collect();
-        let branches_parsed: Vec<BranchTrace<'_, F>> = witness
-            .branches_parsed
-            .into_iter()
-            .map(|x| self.parse_nonterminal_branch_phase1(ctx, x))
-            .collect();
         let key_frag_ext_byte_rlcs: Vec<_> = witness
             .key_frag_ext_bytes
             .into_iter()
@@ -627,22 +803,37 @@ impl<'v, F: Field> MPTChip<'v, F> {
         let key_hexs = witness.key_hexs;
 
         // Match fragments to node key
-        for ((ext_parsed, key_frag_ext_byte_rlc), node) in
-            exts_parsed.iter().zip(key_frag_ext_byte_rlcs.iter()).zip(nodes.iter())
+        for (idx, ((node, key_frag_ext_byte_rlc), frag)) in
+            path_nodes.iter().zip(key_frag_ext_byte_rlcs.iter()).zip(key_frag.iter()).enumerate()
         {
             // When node is extension, check node key RLC equals key frag RLC
-            let mut node_key_is_equal = rlc_is_equal(
-                ctx,
-                self.gate(),
-                &ext_parsed.key_path.field_trace,
-                key_frag_ext_byte_rlc,
-            );
+            let mut node_key_is_equal =
+                rlc_is_equal(ctx, self.gate(), &node.ext_key_path, key_frag_ext_byte_rlc);
             // is equal or node not extension
             let is_not_ext = self.gate().not(ctx, Existing(&node.node_type));
             node_key_is_equal =
                 self.gate().or(ctx, Existing(&node_key_is_equal), Existing(&is_not_ext));
             // assuming node type is not extension if idx > pf.len() [we don't care what happens for these idx]
             self.gate().assert_is_const(ctx, &node_key_is_equal, F::one());
+
+            // When node is a branch above the leaf, its fragment is the single nibble selecting the child:
+            // key_frag_byte_len = key_frag_is_odd = 1
+            let is_above_leaf = self.range().is_less_than(
+                ctx,
+                Constant(self.gate().get_field_element(idx as u64 + 1)),
+                Existing(&depth),
+                bit_length(max_depth as u64),
+            );
+            let is_branch = self.gate().and(ctx, Existing(&is_not_ext), Existing(&is_above_leaf));
+            for frag_field in [&frag.byte_len, &frag.is_odd] {
+                let expected = self.gate().select(
+                    ctx,
+                    Constant(F::one()),
+                    Existing(frag_field),
+                    Existing(&is_branch),
+                );
+                ctx.constrain_equal(&expected, frag_field);
+            }
         }
         let depth_minus_one = self.gate().sub(ctx, Existing(&depth), Constant(F::one()));
         // Quiz for auditers: is the following necessary?
@@ -698,20 +889,11 @@ impl<'v, F: Field> MPTChip<'v, F> {
          */
         let mut matches = Vec::with_capacity(max_depth - 1);
         // assert so later array indexing doesn't do bound check
-        assert_eq!(exts_parsed.len(), max_depth - 1);
-        assert_eq!(branches_parsed.len(), max_depth - 1);
-        assert_eq!(nodes.len(), max_depth - 1);
+        assert_eq!(path_nodes.len(), max_depth - 1);
         for idx in 0..max_depth {
-            // `node_hash_rlc` can be viewed as a fixed length RLC
+            // `node_hash_rlc` is the MPT hash of the node: its keccak hash, or the node itself if it is embedded
             let mut node_hash_rlc = leaf_parsed.leaf_hash_rlc.clone();
             if idx < max_depth - 1 {
-                node_hash_rlc = rlc_select(
-                    ctx,
-                    self.gate(),
-                    &exts_parsed[idx].ext_hash_rlc,
-                    &branches_parsed[idx].branch_hash_rlc,
-                    &nodes[idx].node_type,
-                );
                 let is_leaf = self.gate().is_equal(
                     ctx,
                     Existing(&depth),
@@ -721,7 +903,7 @@ impl<'v, F: Field> MPTChip<'v, F> {
                     ctx,
                     self.gate(),
                     &leaf_parsed.leaf_hash_rlc,
-                    &node_hash_rlc,
+                    &path_nodes[idx].node_hash,
                     &is_leaf,
                 );
             }
@@ -735,26 +917,21 @@ impl<'v, F: Field> MPTChip<'v, F> {
                     self.gate().get_field_element(32),
                 );
             } else {
-                let ext_ref_rlc = &exts_parsed[idx - 1].node_ref.field_trace;
+                let parent = &path_nodes[idx - 1];
                 let branch_ref_rlc = rlc_select_from_idx(
                     ctx,
                     self.gate(),
-                    branches_parsed[idx - 1]
-                        .node_refs
-                        .iter()
-                        .map(|node| (&node.field_trace).into())
-                        .collect(),
+                    parent.branch_refs.iter().map(|node_ref| node_ref.into()).collect(),
                     &fragment_rlcs[idx - 1].values[0],
                 );
                 let match_hash_rlc = rlc_select(
                     ctx,
                     self.gate(),
-                    ext_ref_rlc,
+                    &parent.ext_ref,
                     &branch_ref_rlc,
-                    &nodes[idx - 1].node_type,
+                    &parent.node_type,
                 );
-                // as long as one of the RLCs is fixed len (in this case `node_hash_rlc`), we don't need to check
-                // whether lengths are equal
+                // an embedded node is shorter than 32 bytes, so the lengths differ and `rlc_is_equal` checks them
                 let is_match = rlc_is_equal(ctx, self.gate(), &match_hash_rlc, &node_hash_rlc);
                 matches.push(is_match);
             }
@@ -767,8 +944,12 @@ impl<'v, F: Field> MPTChip<'v, F> {
             Existing(&depth_minus_one),
         );
         ctx.constrain_equal(&match_cnt, &depth_minus_one);
+
+        leaf_parsed
     }
 
+    /// Not implemented yet. Variable length keys, with or without embedded nodes, are out of scope for now; only
+    /// [`MPTChip::parse_mpt_inclusion_fixed_key_phase0`] supports embedded nodes.
     pub fn parse_mpt_inclusion_var_key(
         &self,
         _ctx: &mut Context<'_, F>,
@@ -881,7 +1062,9 @@ pub fn hex_prefix_len<'v, F: ScalarField>(
         vec![(0, None), (3, None)],
     );
     let byte_len_is_zero = gate.is_zero(ctx, key_frag_byte_len);
-    // TODO: should we constrain is_odd to be 0 when is_zero = 1?
+    // an empty fragment has no nibbles, so it cannot be odd
+    let is_odd_and_empty = gate.and(ctx, Existing(is_odd), Existing(&byte_len_is_zero));
+    gate.assert_is_const(ctx, &is_odd_and_empty, F::zero());
     gate.select(ctx, Constant(F::zero()), Existing(&hex_len), Existing(&byte_len_is_zero))
 }
 
@@ -909,14 +1092,99 @@ lazy_static! {
 }
 
 impl MPTFixedKeyInput {
+    /// Checks that the proof fits the max lengths and that its nodes can be parsed, so that it can be assigned.
+    pub fn validate(&self) -> Result<(), MPTProofError> {
+        const KEY_BYTE_LEN: usize = 32;
+        let depth = self.proof.len();
+        if depth == 0 {
+            return Err(MPTProofError::Empty);
+        }
+        if depth > self.max_depth {
+            return Err(MPTProofError::TooDeep { depth, max_depth: self.max_depth });
+        }
+        if self.value.len() > self.value_max_byte_len {
+            return Err(MPTProofError::ValueTooLong {
+                len: self.value.len(),
+                max_len: self.value_max_byte_len,
+            });
+        }
+        let (_, max_leaf_bytes) = max_leaf_lens(KEY_BYTE_LEN, self.value_max_byte_len);
+        let (_, max_ext_bytes) = max_ext_lens(KEY_BYTE_LEN);
+        let max_node_bytes = max(max_ext_bytes, MAX_BRANCH_LENS.1);
+
+        let mut path_idx = 0;
+        for (idx, node) in self.proof.iter().enumerate() {
+            let invalid = |reason: String| MPTProofError::InvalidNode { node: idx, reason };
+            let is_leaf = idx + 1 == depth;
+            let max_len = if is_leaf { max_leaf_bytes } else { max_node_bytes };
+            if node.len() > max_len {
+                return Err(invalid(format!(
+                    "has {} bytes, more than the max {max_len} bytes",
+                    node.len()
+                )));
+            }
+            let decode = Rlp::new(node);
+            match decode.item_count() {
+                Ok(2) => {
+                    let encoded_path = decode
+                        .at(0)
+                        .and_then(|path| path.data())
+                        .map_err(|e| invalid(format!("invalid RLP: {e}")))?;
+                    let prefix = match encoded_path.first() {
+                        Some(byte) if byte >> 4 <= 3 => byte >> 4,
+                        _ => return Err(invalid("invalid hex-prefix encoded path".to_string())),
+                    };
+                    let is_odd = prefix & 1 == 1;
+                    path_idx += 2 * (encoded_path.len() - 1) + usize::from(is_odd);
+                }
+                Ok(17) if !is_leaf => path_idx += 1,
+                Ok(item_count) => {
+                    let expected = if is_leaf { "2" } else { "2 or 17" };
+                    return Err(invalid(format!(
+                        "has {item_count} RLP items, expected {expected}"
+                    )));
+                }
+                Err(e) => return Err(invalid(format!("invalid RLP: {e}"))),
+            }
+            if path_idx > 2 * KEY_BYTE_LEN {
+                return Err(invalid(format!("path is longer than {} nibbles", 2 * KEY_BYTE_LEN)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as [`MPTFixedKeyInput::assign`], but returns an error instead of panicking if the proof is invalid.
+    pub fn try_assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> Result<MPTFixedKeyProof<'v, F>, MPTProofError> {
+        self.validate()?;
+        Ok(self.assign(ctx, gate))
+    }
+
+    /// # Panics
+    /// If the proof is invalid, see [`MPTFixedKeyInput::validate`].
     pub fn assign<'v, F: Field>(
         &self,
         ctx: &mut Context<'_, F>,
         gate: &impl GateInstructions<F>,
     ) -> MPTFixedKeyProof<'v, F> {
+        self.assign_with_nodes(ctx, gate, true)
+    }
+
+    /// Assigns the proof, with no `nodes` unless `with_nodes`, for proofs whose nodes are assigned once for a batch.
+    fn assign_with_nodes<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+        with_nodes: bool,
+    ) -> MPTFixedKeyProof<'v, F> {
+        if let Err(e) = self.validate() {
+            panic!("{e}");
+        }
         let Self { path, value, root_hash, proof, value_max_byte_len, max_depth } = self;
         let depth = proof.len();
-        assert!(depth <= *max_depth);
         let mut value = value.clone();
         let mut proof = proof.clone();
         let value_max_byte_len = *value_max_byte_len;
@@ -988,6 +1256,9 @@ impl MPTFixedKeyInput {
         let value_bytes = load_bytes(&value);
         let root_hash_bytes = load_bytes(root_hash.as_bytes());
         let leaf_bytes = load_bytes(&leaf);
+        if !with_nodes {
+            nodes.clear();
+        }
         let nodes = nodes
             .into_iter()
             .map(|(node_bytes, node_type)| {
@@ -1027,3 +1298,70 @@ impl MPTFixedKeyInput {
         }
     }
 }
+
+impl<'v, F: Field> MPTFixedKeyProof<'v, F> {
+    /// Assigns a proof of unknown values with the same shape as the proof assigned by [`MPTFixedKeyInput::assign`]
+    /// for a proof with the given max lengths, for keygen.
+    pub fn assign_unknown(
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+        value_max_byte_len: usize,
+        max_depth: usize,
+    ) -> Self {
+        Self::assign_unknown_with_nodes(ctx, gate, value_max_byte_len, max_depth, true)
+    }
+
+    /// Same as [`MPTFixedKeyProof::assign_unknown`], with no `nodes` unless `with_nodes`.
+    fn assign_unknown_with_nodes(
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+        value_max_byte_len: usize,
+        max_depth: usize,
+        with_nodes: bool,
+    ) -> Self {
+        const KEY_BYTE_LEN: usize = 32;
+        let (_, max_leaf_bytes) = max_leaf_lens(KEY_BYTE_LEN, value_max_byte_len);
+        let (_, max_ext_bytes) = max_ext_lens(KEY_BYTE_LEN);
+        let max_node_bytes = max(max_ext_bytes, MAX_BRANCH_LENS.1);
+
+        // assign in the same order as `MPTFixedKeyInput::assign`
+        let mut load_bytes =
+            |len: usize| gate.assign_witnesses(ctx, repeat(Value::unknown()).take(len));
+        let value_byte_len = load_bytes(1).pop().unwrap();
+        let depth = load_bytes(1).pop().unwrap();
+        let key_bytes = load_bytes(KEY_BYTE_LEN);
+        let value_bytes = load_bytes(value_max_byte_len);
+        let root_hash_bytes = load_bytes(32);
+        let leaf_bytes = load_bytes(max_leaf_bytes);
+        let num_nodes = if with_nodes { max_depth - 1 } else { 0 };
+        let nodes = (0..num_nodes)
+            .map(|_| {
+                let rlp_bytes = load_bytes(max_node_bytes);
+                let node_type = load_bytes(1).pop().unwrap();
+                MPTNode { rlp_bytes, node_type }
+            })
+            .collect_vec();
+        let key_frag = (0..max_depth)
+            .map(|_| {
+                let nibbles = load_bytes(2 * KEY_BYTE_LEN);
+                let byte_len = load_bytes(1).pop().unwrap();
+                let is_odd = load_bytes(1).pop().unwrap();
+                MPTKeyFragment { nibbles, is_odd, byte_len }
+            })
+            .collect_vec();
+
+        MPTFixedKeyProof {
+            key_bytes,
+            value_bytes,
+            value_byte_len,
+            root_hash_bytes,
+            leaf_bytes,
+            nodes,
+            depth,
+            key_frag,
+            key_byte_len: KEY_BYTE_LEN,
+            value_max_byte_len,
+            max_depth,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Out-of-circuit verification of MPT inclusion proofs, to reject bad inputs before proving.
+use super::MPTFixedKeyInput;
+use crate::error::MPTProofError;
+use ethers_core::utils::{hex, keccak256};
+use rlp::Rlp;
+
+/// Splits a hex-prefix encoded path into `(nibbles, is_leaf)`.
+pub(super) fn decode_hex_prefix(encoded_path: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let (&first, rest) = encoded_path.split_first()?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return None;
+    }
+    let mut nibbles = Vec::with_capacity(1 + 2 * rest.len());
+    if flag & 1 == 1 {
+        nibbles.push(first & 0xf);
+    }
+    for byte in rest {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0xf);
+    }
+    Some((nibbles, flag >= 2))
+}
+
+/// The child of `node` along `path` from nibble `path_idx`, if it is embedded in `node` rather than referenced by
+/// hash. Advances `path_idx` past `node`.
+fn embedded_child(node: &[u8], path: &[u8], path_idx: &mut usize) -> Option<Vec<u8>> {
+    let decode = Rlp::new(node);
+    let child = match decode.item_count().ok()? {
+        17 => {
+            let nibble = *path.get(*path_idx)?;
+            *path_idx += 1;
+            decode.at(nibble as usize).ok()?
+        }
+        2 => {
+            let (frag, is_leaf) = decode_hex_prefix(decode.at(0).ok()?.data().ok()?)?;
+            *path_idx += frag.len();
+            if is_leaf {
+                return None;
+            }
+            decode.at(1).ok()?
+        }
+        _ => return None,
+    };
+    child.is_list().then(|| child.as_raw().to_vec())
+}
+
+impl MPTFixedKeyInput {
+    /// Lists the nodes shorter than 32 bytes, which are embedded in their parent instead of referenced by hash,
+    /// as separate proof nodes after their parent. `eth_getProof` omits them, while the circuit and
+    /// [`MPTFixedKeyInput::verify_native`] expect one proof node per node on the path.
+    ///
+    /// Stops expanding at a node that cannot be parsed, which `verify_native` then reports.
+    pub fn with_embedded_nodes(mut self) -> Self {
+        let path: Vec<u8> = self.path.as_bytes().iter().flat_map(|b| [b >> 4, b & 0xf]).collect();
+        let mut path_idx = 0;
+        let mut proof = Vec::with_capacity(self.proof.len());
+        let mut nodes = self.proof.into_iter().peekable();
+        let mut next = nodes.next();
+        while let Some(node) = next {
+            let child = embedded_child(&node, &path, &mut path_idx);
+            proof.push(node);
+            next = match child {
+                // already listed
+                Some(child) if nodes.peek() == Some(&child) => nodes.next(),
+                Some(child) => Some(child),
+                None => nodes.next(),
+            };
+        }
+        self.proof = proof;
+        self
+    }
+
+    /// Walks the proof from `root_hash` along `path`, checking the hash of every node against the reference in its
+    /// parent and that the leaf holds `value`. Returns the first mismatch.
+    pub fn verify_native(&self) -> Result<(), MPTProofError> {
+        let path: Vec<u8> = self.path.as_bytes().iter().flat_map(|b| [b >> 4, b & 0xf]).collect();
+        let mut path_idx = 0;
+        // reference to the next node: its hash, or the node itself if it is shorter than 32 bytes
+        let mut node_ref = self.root_hash.as_bytes().to_vec();
+        for (idx, node) in self.proof.iter().enumerate() {
+            let mismatch = |reason: String| MPTProofError::Mismatch { node: idx, reason };
+            let invalid = |e: rlp::DecoderError| MPTProofError::InvalidNode {
+                node: idx,
+                reason: format!("invalid RLP: {e}"),
+            };
+            let is_referenced = if node_ref.len() == 32 {
+                keccak256(node) == node_ref[..]
+            } else {
+                node[..] == node_ref[..]
+            };
+            if !is_referenced {
+                let reference = if idx == 0 { "the root hash" } else { "its parent" };
+                return Err(mismatch(format!("node is not referenced by {reference}")));
+            }
+            let is_last = idx + 1 == self.proof.len();
+
+            let decode = Rlp::new(node);
+            let child = match decode.item_count().map_err(invalid)? {
+                17 => {
+                    let nibble = *path
+                        .get(path_idx)
+                        .ok_or_else(|| mismatch("branch is deeper than the key".to_string()))?;
+                    path_idx += 1;
+                    decode.at(nibble as usize).map_err(invalid)?
+                }
+                2 => {
+                    let encoded_path = decode.at(0).and_then(|p| p.data()).map_err(invalid)?;
+                    let (frag, is_leaf) = decode_hex_prefix(encoded_path).ok_or_else(|| {
+                        MPTProofError::InvalidNode {
+                            node: idx,
+                            reason: "invalid hex-prefix encoded path".to_string(),
+                        }
+                    })?;
+                    if path.get(path_idx..path_idx + frag.len()) != Some(&frag[..]) {
+                        return Err(mismatch(format!(
+                            "path fragment {frag:?} diverges from the key at nibble {path_idx}"
+                        )));
+                    }
+                    path_idx += frag.len();
+                    if is_leaf {
+                        if !is_last {
+                            return Err(mismatch("leaf is not the last node".to_string()));
+                        }
+                        if path_idx != path.len() {
+                            return Err(mismatch(format!(
+                                "leaf ends at nibble {path_idx} of the {} nibble key",
+                                path.len()
+                            )));
+                        }
+                        let value = decode.at(1).and_then(|v| v.data()).map_err(invalid)?;
+                        if value != &self.value[..] {
+                            return Err(mismatch(format!(
+                                "leaf has value 0x{}, expected 0x{}",
+                                hex::encode(value),
+                                hex::encode(&self.value)
+                            )));
+                        }
+                        return Ok(());
+                    }
+                    decode.at(1).map_err(invalid)?
+                }
+                item_count => {
+                    return Err(MPTProofError::InvalidNode {
+                        node: idx,
+                        reason: format!("has {item_count} RLP items, expected 2 or 17"),
+                    })
+                }
+            };
+            if is_last {
+                return Err(mismatch("proof ends before reaching a leaf".to_string()));
+            }
+            node_ref = if child.is_list() {
+                child.as_raw().to_vec()
+            } else {
+                let child_hash = child.data().map_err(invalid)?;
+                if child_hash.is_empty() {
+                    return Err(mismatch("node has no child along the key".to_string()));
+                }
+                child_hash.to_vec()
+            };
+        }
+        Err(MPTProofError::Empty)
+    }
+}
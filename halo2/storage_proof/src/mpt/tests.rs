@@ -1,7 +1,7 @@
-use super::*;
+use super::{batch::MPTBatchInput, transition::MPTTransitionInput, trie::Trie, *};
 use crate::halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner},
-    dev::MockProver,
+    dev::{MockProver, VerifyFailure},
     halo2curves::bn256::{Bn256, Fr, G1Affine},
     plonk::{Circuit, ConstraintSystem, Error},
     poly::commitment::ParamsProver,
@@ -15,6 +15,7 @@ use crate::halo2_proofs::{
     },
 };
 use ark_std::{end_timer, start_timer};
+use ethers_core::utils::keccak256;
 use halo2_base::{
     halo2_proofs::plonk::{create_proof, keygen_pk, keygen_vk, verify_proof},
     utils::{fs::gen_srs, ScalarField},
@@ -28,18 +29,44 @@ use std::{
     marker::PhantomData,
 };
 
-#[derive(Clone, Debug)]
-pub struct MPTCircuit<F> {
-    inputs: MPTFixedKeyInput,
+/// A test circuit running `synthesize` on an MPT chip configured by `configs/tests/mpt.json`. `synthesize` assigns
+/// and parses the proofs in the first phase, calls [`next_phase`] and finishes the parsing in the second phase.
+#[derive(Clone)]
+pub struct MPTTestCircuit<F, S> {
+    synthesize: S,
     _marker: PhantomData<F>,
 }
 
-impl<F: Field> Circuit<F> for MPTCircuit<F> {
+impl<F: Field, S> MPTTestCircuit<F, S>
+where
+    S: Clone + for<'v> Fn(&mut Context<'v, F>, &mut MPTChip<'v, F>),
+{
+    pub fn new(synthesize: S) -> Self {
+        Self { synthesize, _marker: PhantomData }
+    }
+}
+
+/// Ends the first phase of an [`MPTTestCircuit`] and assigns the keccak witnesses of the second phase.
+fn next_phase<'v, F: Field>(ctx: &mut Context<'v, F>, chip: &mut MPTChip<'v, F>) {
+    chip.keccak.assign_phase0(&mut ctx.region);
+    chip.range().finalize(ctx);
+    // END OF FIRST PHASE
+    ctx.next_phase();
+
+    // SECOND PHASE
+    chip.get_challenge(ctx);
+    chip.keccak.assign_phase1(ctx, &mut chip.rlp.rlc, &chip.rlp.range);
+}
+
+impl<F: Field, S> Circuit<F> for MPTTestCircuit<F, S>
+where
+    S: Clone + for<'v> Fn(&mut Context<'v, F>, &mut MPTChip<'v, F>),
+{
     type Config = MPTConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        self.clone()
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -57,13 +84,13 @@ impl<F: Field> Circuit<F> for MPTCircuit<F> {
         let witness_gen = start_timer!(|| "witness gen");
 
         config.rlp.range.load_lookup_table(&mut layouter).expect("load range lookup tables");
-        config.keccak.load_aux_tables(&mut layouter).expect("load keccak lookup tables");
+        config.load_keccak_aux_tables(&mut layouter).expect("load keccak lookup tables");
         let gamma = layouter.get_challenge(config.rlp.rlc.gamma);
 
         let mut first_pass = SKIP_FIRST_PASS;
         layouter
             .assign_region(
-                || "MPT Fixed Test",
+                || "MPT Test",
                 |region| {
                     if first_pass {
                         first_pass = false;
@@ -80,24 +107,7 @@ impl<F: Field> Circuit<F> for MPTCircuit<F> {
                     );
                     let ctx = &mut aux;
 
-                    let mpt_proof = self.inputs.assign(ctx, chip.gate());
-                    let mpt_witness = chip.parse_mpt_inclusion_fixed_key_phase0(
-                        ctx,
-                        mpt_proof,
-                        32,
-                        self.inputs.value_max_byte_len,
-                        self.inputs.max_depth,
-                    );
-
-                    chip.keccak.assign_phase0(&mut ctx.region);
-                    chip.range().finalize(ctx);
-                    // END OF FIRST PHASE
-                    ctx.next_phase();
-
-                    // SECOND PHASE
-                    chip.get_challenge(ctx);
-                    chip.keccak.assign_phase1(ctx, &mut chip.rlp.rlc, &chip.rlp.range);
-                    chip.parse_mpt_inclusion_fixed_key_phase1(ctx, mpt_witness);
+                    (self.synthesize)(ctx, &mut chip);
                     chip.range().finalize(ctx);
 
                     #[cfg(feature = "display")]
@@ -113,41 +123,53 @@ impl<F: Field> Circuit<F> for MPTCircuit<F> {
     }
 }
 
-impl<F: ScalarField> Default for MPTCircuit<F> {
-    fn default() -> Self {
-        /*let block: serde_json::Value =
-        serde_json::from_reader(File::open("scripts/input_gen/block.json").unwrap()).unwrap();*/
-
-        let pf_str = std::fs::read_to_string("scripts/input_gen/acct_storage_pf.json").unwrap();
-        let pf: serde_json::Value = serde_json::from_str(pf_str.as_str()).unwrap();
-        // let acct_pf = pf["accountProof"].clone();
-        let storage_pf = pf["storageProof"][0].clone();
-        // println!("acct_pf {:?}", acct_pf);
-        // println!("storage_root {:?}", pf["storageHash"]);
-        // println!("storage_pf {:?}", storage_pf);
-
-        let key_bytes_str: String = serde_json::from_value(storage_pf["key"].clone()).unwrap();
-        let path = ethers_core::utils::keccak256(Vec::from_hex(key_bytes_str).unwrap());
-        let value_bytes_str: String = serde_json::from_value(storage_pf["value"].clone()).unwrap();
-        let value = ::rlp::encode(&Vec::from_hex(&value_bytes_str[2..]).unwrap()).to_vec();
-        let root_hash_str: String = serde_json::from_value(pf["storageHash"].clone()).unwrap();
-        let pf_strs: Vec<String> = serde_json::from_value(storage_pf["proof"].clone()).unwrap();
-
-        let value_max_byte_len = 33;
-        let max_depth = 8;
-        let proof = pf_strs.into_iter().map(|pf| Vec::from_hex(&pf[2..]).unwrap()).collect();
-
-        MPTCircuit {
-            inputs: MPTFixedKeyInput {
-                path: H256(path),
-                value,
-                root_hash: H256::from_slice(&Vec::from_hex(&root_hash_str[2..]).unwrap()),
-                proof,
-                value_max_byte_len,
-                max_depth,
-            },
-            _marker: PhantomData,
-        }
+/// Proves the inclusion of a key with the fixed key chip.
+fn inclusion_circuit(inputs: MPTFixedKeyInput) -> impl Circuit<Fr> {
+    MPTTestCircuit::<Fr, _>::new(move |ctx, chip| {
+        let mpt_proof = inputs.assign(ctx, chip.gate());
+        let mpt_witness = chip.parse_mpt_inclusion_fixed_key_phase0(
+            ctx,
+            mpt_proof,
+            32,
+            inputs.value_max_byte_len,
+            inputs.max_depth,
+        );
+        next_phase(ctx, chip);
+        chip.parse_mpt_inclusion_fixed_key_phase1(ctx, mpt_witness);
+    })
+}
+
+/// The proof of the first slot in `scripts/input_gen/acct_storage_pf.json`.
+fn default_input() -> MPTFixedKeyInput {
+    /*let block: serde_json::Value =
+    serde_json::from_reader(File::open("scripts/input_gen/block.json").unwrap()).unwrap();*/
+
+    let pf_str = std::fs::read_to_string("scripts/input_gen/acct_storage_pf.json").unwrap();
+    let pf: serde_json::Value = serde_json::from_str(pf_str.as_str()).unwrap();
+    // let acct_pf = pf["accountProof"].clone();
+    let storage_pf = pf["storageProof"][0].clone();
+    // println!("acct_pf {:?}", acct_pf);
+    // println!("storage_root {:?}", pf["storageHash"]);
+    // println!("storage_pf {:?}", storage_pf);
+
+    let key_bytes_str: String = serde_json::from_value(storage_pf["key"].clone()).unwrap();
+    let path = ethers_core::utils::keccak256(Vec::from_hex(key_bytes_str).unwrap());
+    let value_bytes_str: String = serde_json::from_value(storage_pf["value"].clone()).unwrap();
+    let value = ::rlp::encode(&Vec::from_hex(&value_bytes_str[2..]).unwrap()).to_vec();
+    let root_hash_str: String = serde_json::from_value(pf["storageHash"].clone()).unwrap();
+    let pf_strs: Vec<String> = serde_json::from_value(storage_pf["proof"].clone()).unwrap();
+
+    let value_max_byte_len = 33;
+    let max_depth = 8;
+    let proof = pf_strs.into_iter().map(|pf| Vec::from_hex(&pf[2..]).unwrap()).collect();
+
+    MPTFixedKeyInput {
+        path: H256(path),
+        value,
+        root_hash: H256::from_slice(&Vec::from_hex(&root_hash_str[2..]).unwrap()),
+        proof,
+        value_max_byte_len,
+        max_depth,
     }
 }
 
@@ -157,11 +179,548 @@ pub fn test_mock_mpt_inclusion_fixed() {
         serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
     let k = params.degree;
 
-    let circuit = MPTCircuit::<Fr>::default();
-    // println!("MPTCircuit {:?}", circuit);
+    let circuit = inclusion_circuit(default_input());
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+}
+
+/// A trie with keys `0x00..00 => 0x2a` and `0x00..01 => 0x2b`. The keys share 63 nibbles, so the root is an
+/// extension to a branch, and both the branch and the leaves below it are shorter than 32 bytes, hence embedded in
+/// their parents. As returned by `eth_getProof`, the proof of the first key only lists the root.
+fn embedded_nodes_input() -> MPTFixedKeyInput {
+    let leaf = |value: u8| {
+        let mut leaf = ::rlp::RlpStream::new_list(2);
+        leaf.append(&vec![0x20u8]).append(&vec![value]);
+        leaf.out().to_vec()
+    };
+    let mut branch = ::rlp::RlpStream::new_list(17);
+    branch.append_raw(&leaf(0x2a), 1).append_raw(&leaf(0x2b), 1);
+    for _ in 2..17 {
+        branch.append_empty_data();
+    }
+    let branch = branch.out().to_vec();
+    let mut ext = ::rlp::RlpStream::new_list(2);
+    // hex-prefix encoding of 63 zero nibbles
+    ext.append(&[&[0x10u8], [0u8; 31].as_slice()].concat()).append_raw(&branch, 1);
+    let ext = ext.out().to_vec();
+    assert!(leaf(0x2a).len() < 32 && branch.len() < 32 && ext.len() >= 32);
+
+    MPTFixedKeyInput {
+        path: H256::zero(),
+        value: vec![0x2a],
+        root_hash: H256(keccak256(&ext)),
+        proof: vec![ext],
+        value_max_byte_len: 33,
+        max_depth: 4,
+    }
+}
+
+#[test]
+pub fn test_mpt_embedded_nodes() {
+    let inputs = embedded_nodes_input();
+    assert!(inputs.verify_native().is_err());
+
+    let inputs = inputs.with_embedded_nodes();
+    assert_eq!(inputs.proof.len(), 3);
+    assert_eq!(inputs.verify_native(), Ok(()));
+    assert_eq!(inputs.validate(), Ok(()));
+    // expanding again is a no-op
+    assert_eq!(inputs.clone().with_embedded_nodes().proof, inputs.proof);
+
+    let mut wrong_value = inputs;
+    wrong_value.value = vec![0x2b];
+    assert!(matches!(wrong_value.verify_native(), Err(MPTProofError::Mismatch { node: 2, .. })));
+}
+
+#[test]
+pub fn test_mock_mpt_embedded_nodes() {
+    let params: EthConfigParams =
+        serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
+    let k = params.degree;
+
+    let inputs = embedded_nodes_input().with_embedded_nodes();
+    let circuit = inclusion_circuit(inputs.clone());
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+    // the value of the other leaf in the embedded branch
+    let mut wrong_value = inputs;
+    wrong_value.value = vec![0x2b];
+    let circuit = inclusion_circuit(wrong_value);
+    assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+}
+
+#[test]
+pub fn test_trie_root() {
+    assert_eq!(
+        format!("{:?}", Trie::new().root()),
+        "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+    );
+
+    let entries = [("do", "verb"), ("dog", "puppy"), ("doge", "coin"), ("horse", "stallion")];
+    let mut trie = Trie::new();
+    let mut reversed = Trie::new();
+    for ((key, value), (rev_key, rev_value)) in entries.iter().zip(entries.iter().rev()) {
+        trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        reversed.insert(rev_key.as_bytes(), rev_value.as_bytes().to_vec());
+    }
+    assert_eq!(
+        format!("{:?}", trie.root()),
+        "0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+    );
+    assert_eq!(trie.root(), reversed.root());
+    assert_eq!(trie.get(b"dog"), Some(&b"puppy"[..]));
+    assert_eq!(trie.get(b"d"), None);
+    assert!(trie.exclusion_proof(b"dog").is_none());
+    assert!(trie.exclusion_proof(b"cat").is_some());
+}
+
+#[test]
+pub fn test_trie_fixed_key_inputs() {
+    // a storage trie: keccak(slot) => rlp(value)
+    let mut trie = Trie::new();
+    let slots = (0..20u64).map(H256::from_low_u64_be).collect_vec();
+    for (i, slot) in slots.iter().enumerate() {
+        trie.insert(&keccak256(slot), ::rlp::encode(&(i as u64 + 1)).to_vec());
+    }
+    for slot in &slots {
+        let input = trie.fixed_key_input(H256(keccak256(slot)), 33, 8).unwrap();
+        assert_eq!(input.verify_native(), Ok(()));
+        assert_eq!(input.validate(), Ok(()));
+    }
+    let absent = H256(keccak256(H256::from_low_u64_be(100)));
+    assert!(trie.fixed_key_input(absent, 33, 8).is_none());
+    assert!(!trie.exclusion_proof(absent.as_bytes()).unwrap().is_empty());
+
+    // the embedded nodes fixture, generated by the trie with the embedded nodes listed
+    let expected = embedded_nodes_input().with_embedded_nodes();
+    let mut trie = Trie::new();
+    trie.insert(&[0; 32], vec![0x2a]);
+    trie.insert(&H256::from_low_u64_be(1).0, vec![0x2b]);
+    let input = trie.fixed_key_input(H256::zero(), 33, 4).unwrap();
+    assert_eq!((input.root_hash, &input.proof), (expected.root_hash, &expected.proof));
+}
+
+#[test]
+pub fn test_mock_mpt_trie_inclusion() {
+    let params: EthConfigParams =
+        serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
+    let k = params.degree;
+
+    // keys sharing a prefix, so the proof has an extension node
+    let mut trie = Trie::new();
+    let key = |last_byte: u8| H256::from_low_u64_be(0x0100 + last_byte as u64);
+    for last_byte in 0..3 {
+        trie.insert(key(last_byte).as_bytes(), vec![0xaa; 32 + last_byte as usize]);
+    }
+    trie.insert(&[0xff; 32], vec![0xbb; 32]);
+    let inputs = trie.fixed_key_input(key(1), 33, 6).unwrap();
+    assert_eq!(inputs.verify_native(), Ok(()));
+    let circuit = inclusion_circuit(inputs);
     MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
 }
 
+#[test]
+pub fn test_mpt_input_validate() {
+    let inputs = default_input();
+    assert_eq!(inputs.validate(), Ok(()));
+
+    let depth = inputs.proof.len();
+    let mut too_deep = inputs.clone();
+    too_deep.max_depth = depth - 1;
+    assert_eq!(
+        too_deep.validate(),
+        Err(MPTProofError::TooDeep { depth, max_depth: depth - 1 })
+    );
+
+    let mut bad_node = inputs;
+    // an empty RLP list is neither a branch, extension nor leaf
+    bad_node.proof[1] = vec![0xc0];
+    assert!(matches!(bad_node.validate(), Err(MPTProofError::InvalidNode { node: 1, .. })));
+}
+
+#[test]
+pub fn test_mpt_verify_native() {
+    let inputs = default_input();
+    assert_eq!(inputs.verify_native(), Ok(()));
+
+    let mut wrong_value = inputs.clone();
+    *wrong_value.value.last_mut().unwrap() ^= 1;
+    let last = inputs.proof.len() - 1;
+    assert!(matches!(
+        wrong_value.verify_native(),
+        Err(MPTProofError::Mismatch { node, .. }) if node == last
+    ));
+
+    let mut wrong_node = inputs;
+    *wrong_node.proof[1].last_mut().unwrap() ^= 1;
+    assert!(matches!(wrong_node.verify_native(), Err(MPTProofError::Mismatch { node: 1, .. })));
+}
+
+/// Proves an update of one key with the transition chip.
+fn transition_circuit(inputs: MPTTransitionInput) -> impl Circuit<Fr> {
+    MPTTestCircuit::<Fr, _>::new(move |ctx, chip| {
+        let proof = inputs.assign(ctx, chip.gate());
+        let witness = chip.parse_mpt_transition_fixed_key_phase0(
+            ctx,
+            proof,
+            32,
+            inputs.old.value_max_byte_len,
+            inputs.old.max_depth,
+        );
+        next_phase(ctx, chip);
+        chip.parse_mpt_transition_fixed_key_phase1(ctx, witness);
+    })
+}
+
+/// The trie of [`test_mock_mpt_trie_inclusion`], whose proofs have a branch, an extension and a branch above the
+/// leaf, and the key to update.
+fn transition_trie() -> (Trie, H256) {
+    let mut trie = Trie::new();
+    let key = |last_byte: u8| H256::from_low_u64_be(0x0100 + last_byte as u64);
+    for last_byte in 0..3 {
+        trie.insert(key(last_byte).as_bytes(), vec![0xaa; 32 + last_byte as usize]);
+    }
+    trie.insert(&[0xff; 32], vec![0xbb; 32]);
+    (trie, key(1))
+}
+
+fn transition_input(old: &Trie, new: &Trie, key: H256) -> MPTTransitionInput {
+    MPTTransitionInput {
+        old: old.fixed_key_input(key, 33, 6).unwrap(),
+        new: new.fixed_key_input(key, 33, 6).unwrap(),
+    }
+}
+
+#[test]
+pub fn test_mpt_transition_verify_native() {
+    let (old, key) = transition_trie();
+    let mut new = old.clone();
+    new.insert(key.as_bytes(), vec![0xcc; 20]);
+    let inputs = transition_input(&old, &new, key);
+    assert_eq!(inputs.validate(), Ok(()));
+    assert_eq!(inputs.verify_native(), Ok(()));
+
+    // a sibling of the leaf also changed
+    let mut other_changed = new.clone();
+    other_changed.insert(H256::from_low_u64_be(0x0102).as_bytes(), vec![0xcc; 20]);
+    let inputs = transition_input(&old, &other_changed, key);
+    let depth = inputs.old.proof.len();
+    assert!(matches!(
+        inputs.verify_native(),
+        Err(MPTProofError::Mismatch { node, .. }) if node == depth - 2
+    ));
+
+    // a key under the other child of the root changed
+    let mut other_changed = new;
+    other_changed.insert(&[0xff; 32], vec![0xcc; 20]);
+    let inputs = transition_input(&old, &other_changed, key);
+    assert!(matches!(inputs.verify_native(), Err(MPTProofError::Mismatch { node: 0, .. })));
+
+    let mut other_key = transition_input(&old, &old, key);
+    other_key.new = old.fixed_key_input(H256::from_low_u64_be(0x0100), 33, 6).unwrap();
+    assert!(matches!(other_key.validate(), Err(MPTProofError::Mismatch { node: 0, .. })));
+}
+
+#[test]
+pub fn test_mock_mpt_transition() {
+    let params: EthConfigParams =
+        serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
+    let k = params.degree;
+
+    let (old, key) = transition_trie();
+    let mut new = old.clone();
+    new.insert(key.as_bytes(), vec![0xcc; 20]);
+    let inputs = transition_input(&old, &new, key);
+    let circuit = transition_circuit(inputs);
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+    // both proofs are valid inclusion proofs, but another key changed as well
+    for other_key in [H256::from_low_u64_be(0x0102), H256::repeat_byte(0xff)] {
+        let mut other_changed = new.clone();
+        other_changed.insert(other_key.as_bytes(), vec![0xcc; 20]);
+        let inputs = transition_input(&old, &other_changed, key);
+        assert_eq!(inputs.old.verify_native(), Ok(()));
+        assert_eq!(inputs.new.verify_native(), Ok(()));
+        let circuit = transition_circuit(inputs);
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+    }
+}
+
+/// Proves the inclusion of a batch of keys with shared nodes.
+fn batch_circuit(inputs: MPTBatchInput) -> impl Circuit<Fr> {
+    MPTTestCircuit::<Fr, _>::new(move |ctx, chip| {
+        let batch = inputs.assign(ctx, chip.gate());
+        let first = &inputs.proofs[0];
+        let witness = chip.parse_mpt_batch_inclusion_fixed_key_phase0(
+            ctx,
+            batch,
+            32,
+            first.value_max_byte_len,
+            first.max_depth,
+        );
+        next_phase(ctx, chip);
+        chip.parse_mpt_batch_inclusion_fixed_key_phase1(ctx, witness);
+    })
+}
+
+/// Proofs of all 20 slots of a storage trie, which share their upper nodes.
+fn batch_input() -> MPTBatchInput {
+    let mut trie = Trie::new();
+    let keys = (0..20u64).map(|i| H256(keccak256(H256::from_low_u64_be(i)))).collect_vec();
+    for (i, key) in keys.iter().enumerate() {
+        trie.insert(key.as_bytes(), ::rlp::encode(&(i as u64 + 1)).to_vec());
+    }
+    MPTBatchInput::new(
+        keys.into_iter().map(|key| trie.fixed_key_input(key, 33, 5).unwrap()).collect(),
+    )
+}
+
+#[test]
+pub fn test_mpt_batch_input() {
+    let inputs = batch_input();
+    assert_eq!(inputs.validate(), Ok(()));
+    assert_eq!(inputs.verify_native(), Ok(()));
+    // every proof starts at the root branch
+    let nodes_above_leaves: usize = inputs.proofs.iter().map(|pf| pf.proof.len() - 1).sum();
+    assert!(inputs.max_nodes < nodes_above_leaves);
+
+    let mut too_few_nodes = inputs.clone();
+    too_few_nodes.max_nodes -= 1;
+    assert_eq!(
+        too_few_nodes.validate(),
+        Err(MPTProofError::TooManyNodes {
+            nodes: inputs.max_nodes,
+            max_nodes: inputs.max_nodes - 1
+        })
+    );
+
+    let mut other_root = inputs.clone();
+    let mut trie = Trie::new();
+    trie.insert(&[0; 32], vec![0x2a]);
+    other_root.proofs.push(trie.fixed_key_input(H256::zero(), 33, 5).unwrap());
+    assert!(matches!(other_root.validate(), Err(MPTProofError::Mismatch { node: 0, .. })));
+
+    let mut wrong_value = inputs;
+    wrong_value.proofs[1].value = ::rlp::encode(&100u64).to_vec();
+    assert!(wrong_value.verify_native().is_err());
+}
+
+#[test]
+pub fn test_mock_mpt_batch() {
+    let params: EthConfigParams =
+        serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
+    let k = params.degree;
+
+    let inputs = batch_input();
+    let circuit = batch_circuit(inputs.clone());
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+    // room for more nodes than needed, the padding nodes are not referenced
+    let mut padded = inputs.clone();
+    padded.max_nodes += 2;
+    let circuit = batch_circuit(padded);
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+    let mut wrong_value = inputs;
+    wrong_value.proofs[1].value = ::rlp::encode(&100u64).to_vec();
+    let circuit = batch_circuit(wrong_value);
+    assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+}
+
+/// A malicious witness for the proof of [`transition_trie`], whose nodes are a branch, an extension of 62 nibbles,
+/// a branch and a leaf with an empty path.
+///
+/// Attacks on the input checks of the first phase are run on [`input_check_circuit`]: the witness generation of
+/// the node parsing cannot process node types that are not bits, and the one of the key concatenation cannot
+/// process a zero depth.
+#[derive(Clone, Copy, Debug)]
+enum Attack {
+    /// Parses the root branch as an extension.
+    BranchAsExtension,
+    /// Sets `node_type = 2` for the root branch.
+    NodeTypeNotBit,
+    /// Sets `is_odd = 2` in the fragment of the root branch.
+    IsOddNotBit,
+    /// Sets a nibble of the extension fragment to 16.
+    NibbleNotHex,
+    /// Claims a proof without nodes.
+    DepthZero,
+    /// Claims one more node than the max depth.
+    DepthTooLarge,
+    /// Sets the byte length of a fragment past the depth to `key_byte_len + 2`.
+    FragmentTooLong,
+    /// Sets `is_odd = 1` in an empty fragment past the depth.
+    OddEmptyFragment,
+    /// Moves the nibble of the branch above the leaf into the fragment of the root branch. The fragments still
+    /// select the same children and add up to 64 nibbles, but of a key that is not in the trie.
+    MoveBranchNibble,
+}
+
+/// The kind of constraint that a [`VerifyFailure`] reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rejection {
+    /// A custom gate. Honest witness generation satisfies the gates for any input, so the only gates that fail are
+    /// those of `assert_bit`, which copies its input into a cell the gate expects to hold its square.
+    Gate,
+    /// A lookup, i.e. a range check.
+    Lookup,
+    /// A copy constraint, e.g. `assert_is_const` or `constrain_equal`.
+    Copy,
+}
+
+impl Rejection {
+    fn of(failure: &VerifyFailure) -> Option<Self> {
+        match failure {
+            VerifyFailure::ConstraintNotSatisfied { .. } => Some(Self::Gate),
+            VerifyFailure::Lookup { .. } => Some(Self::Lookup),
+            VerifyFailure::Permutation { .. } => Some(Self::Copy),
+            _ => None,
+        }
+    }
+}
+
+impl Attack {
+    const ALL: [Attack; 9] = [
+        Attack::BranchAsExtension,
+        Attack::NodeTypeNotBit,
+        Attack::IsOddNotBit,
+        Attack::NibbleNotHex,
+        Attack::DepthZero,
+        Attack::DepthTooLarge,
+        Attack::FragmentTooLong,
+        Attack::OddEmptyFragment,
+        Attack::MoveBranchNibble,
+    ];
+
+    /// Whether the attack targets the input checks of the first phase, see [`input_check_circuit`].
+    fn is_input_check(self) -> bool {
+        !matches!(self, Attack::BranchAsExtension | Attack::MoveBranchNibble)
+    }
+
+    /// The constraints that reject the attack, and that every failure reported by `MockProver::verify` must be
+    /// one of. For the constraints added to harden the MPT chip, this shows that no other constraint catches
+    /// the attack:
+    /// - `assert_bit` of the node type: `NodeTypeNotBit`
+    /// - `depth != 0`: `DepthZero`
+    /// - `is_odd = 0` for empty fragments: `OddEmptyFragment`
+    /// - the fragment of a branch above the leaf is a single nibble: `MoveBranchNibble`
+    fn rejections(self) -> &'static [Rejection] {
+        match self {
+            // parsing a branch as an extension fails the RLP range checks, the hash chain or the extension path
+            Attack::BranchAsExtension => &[Rejection::Lookup, Rejection::Copy],
+            Attack::NodeTypeNotBit | Attack::IsOddNotBit => &[Rejection::Gate],
+            // range checks of the nibbles, of the depth and of the fragment lengths
+            Attack::NibbleNotHex | Attack::DepthTooLarge | Attack::FragmentTooLong => {
+                &[Rejection::Lookup]
+            }
+            // `assert_is_const` of `depth == 0` and of an empty odd fragment, and `constrain_equal` of the fragment
+            // of a branch to a single nibble
+            Attack::DepthZero | Attack::OddEmptyFragment | Attack::MoveBranchNibble => {
+                &[Rejection::Copy]
+            }
+        }
+    }
+
+    /// Replaces cells of the honest `proof` with malicious witnesses.
+    fn apply<'v, F: Field>(
+        self,
+        ctx: &mut Context<'v, F>,
+        gate: &impl GateInstructions<F>,
+        proof: &mut MPTFixedKeyProof<'v, F>,
+    ) {
+        let mut witness = |x: u64| gate.load_witness(ctx, Value::known(F::from(x)));
+        let last = proof.max_depth - 1;
+        match self {
+            Attack::BranchAsExtension => proof.nodes[0].node_type = witness(1),
+            Attack::NodeTypeNotBit => proof.nodes[0].node_type = witness(2),
+            Attack::IsOddNotBit => proof.key_frag[0].is_odd = witness(2),
+            Attack::NibbleNotHex => proof.key_frag[1].nibbles[0] = witness(16),
+            Attack::DepthZero => proof.depth = witness(0),
+            Attack::DepthTooLarge => proof.depth = witness(proof.max_depth as u64 + 1),
+            Attack::FragmentTooLong => {
+                proof.key_frag[last].byte_len = witness(proof.key_byte_len as u64 + 2)
+            }
+            Attack::OddEmptyFragment => proof.key_frag[last].is_odd = witness(1),
+            Attack::MoveBranchNibble => {
+                // the key is 0x00..0101: the root branch takes nibble 0, the extension the next 62 nibbles
+                // and the branch above the leaf the last nibble 1
+                proof.key_frag[0].nibbles[1] = witness(1);
+                proof.key_frag[0].byte_len = witness(2);
+                proof.key_frag[0].is_odd = witness(0);
+                proof.key_frag[2].is_odd = witness(0);
+                // nibbles 0, 1, then the extension 0, .., 0, 1, 0
+                let mut key = [0u8; 32];
+                key[0] = 0x01;
+                key[31] = 0x10;
+                proof.key_bytes = key.into_iter().map(|byte| witness(byte as u64)).collect();
+            }
+        }
+    }
+}
+
+/// Proves the inclusion of a key with a malicious witness.
+fn attack_circuit(inputs: MPTFixedKeyInput, attack: Attack) -> impl Circuit<Fr> {
+    MPTTestCircuit::<Fr, _>::new(move |ctx, chip| {
+        let mut mpt_proof = inputs.assign(ctx, chip.gate());
+        attack.apply(ctx, chip.gate(), &mut mpt_proof);
+        let mpt_witness = chip.parse_mpt_inclusion_fixed_key_phase0(
+            ctx,
+            mpt_proof,
+            32,
+            inputs.value_max_byte_len,
+            inputs.max_depth,
+        );
+        next_phase(ctx, chip);
+        chip.parse_mpt_inclusion_fixed_key_phase1(ctx, mpt_witness);
+    })
+}
+
+/// Runs only the input checks of the first phase on a malicious witness: the checks of the nodes, without parsing
+/// them, and the checks of the leaf, the key fragments and the depth.
+fn input_check_circuit(inputs: MPTFixedKeyInput, attack: Option<Attack>) -> impl Circuit<Fr> {
+    MPTTestCircuit::<Fr, _>::new(move |ctx, chip| {
+        let mut mpt_proof = inputs.assign(ctx, chip.gate());
+        if let Some(attack) = attack {
+            attack.apply(ctx, chip.gate(), &mut mpt_proof);
+        }
+        chip.check_nodes_phase0(ctx, &mpt_proof.nodes);
+        chip.parse_key_path_phase0(ctx, mpt_proof);
+    })
+}
+
+#[test]
+pub fn test_mock_mpt_attacks() {
+    let params: EthConfigParams =
+        serde_json::from_reader(File::open("configs/tests/mpt.json").unwrap()).unwrap();
+    let k = params.degree;
+
+    let (trie, key) = transition_trie();
+    let inputs = trie.fixed_key_input(key, 33, 6).unwrap();
+    let circuit = inclusion_circuit(inputs.clone());
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+    let circuit = input_check_circuit(inputs.clone(), None);
+    MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+    for attack in Attack::ALL {
+        let prover = if attack.is_input_check() {
+            MockProver::run(k, &input_check_circuit(inputs.clone(), Some(attack)), vec![])
+        } else {
+            MockProver::run(k, &attack_circuit(inputs.clone(), attack), vec![])
+        };
+        let failures = match prover.unwrap().verify() {
+            Ok(()) => panic!("{attack:?} is not rejected"),
+            Err(failures) => failures,
+        };
+        for failure in failures {
+            let rejection = Rejection::of(&failure);
+            assert!(
+                matches!(rejection, Some(r) if attack.rejections().contains(&r)),
+                "{attack:?} is rejected by {rejection:?}, expected one of {:?}: {failure}",
+                attack.rejections()
+            );
+        }
+    }
+}
+
 #[test]
 fn bench_mpt_inclusion_fixed() -> Result<(), Box<dyn std::error::Error>> {
     let bench_params_file = File::open("configs/bench/mpt.json").unwrap();
@@ -182,7 +741,7 @@ fn bench_mpt_inclusion_fixed() -> Result<(), Box<dyn std::error::Error>> {
             write!(f, "{}", serde_json::to_string(&bench_params).unwrap())?;
         }
         let params = gen_srs(bench_params.degree);
-        let circuit = MPTCircuit::<Fr>::default();
+        let circuit = inclusion_circuit(default_input());
         let vk = keygen_vk(&params, &circuit)?;
         let pk = keygen_pk(&params, vk, &circuit)?;
 
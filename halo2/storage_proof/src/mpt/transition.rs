@@ -0,0 +1,209 @@
+//! Proofs that a trie update only changed the value at one key: inclusion proofs of the key against the old and
+//! new roots that share every node off the path of the key.
+//!
+//! Only updates of an existing key are supported. Inserting or deleting a key changes the shape of the trie
+//! along its path, so the two proofs would not have the same node types and key fragments.
+use super::{
+    native::decode_hex_prefix, MPTChip, MPTFixedKeyInput, MPTFixedKeyProof, MPTFixedKeyProofWitness,
+};
+use crate::{error::MPTProofError, rlp::rlc::rlc_is_equal, Field};
+use halo2_base::{gates::GateInstructions, Context, QuantumCell::Existing};
+use itertools::Itertools;
+use rlp::Rlp;
+
+/// The update of the value at `old.path`: its inclusion proof in the trie before the update, and in the trie after.
+#[derive(Clone, Debug)]
+pub struct MPTTransitionInput {
+    pub old: MPTFixedKeyInput,
+    pub new: MPTFixedKeyInput,
+}
+
+#[derive(Clone, Debug)]
+pub struct MPTTransitionProof<'v, F: Field> {
+    pub old: MPTFixedKeyProof<'v, F>,
+    pub new: MPTFixedKeyProof<'v, F>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MPTTransitionWitness<'v, F: Field> {
+    pub old: MPTFixedKeyProofWitness<'v, F>,
+    pub new: MPTFixedKeyProofWitness<'v, F>,
+}
+
+impl MPTTransitionInput {
+    /// Checks that both proofs can be assigned, for the same key and with the same max lengths.
+    pub fn validate(&self) -> Result<(), MPTProofError> {
+        let Self { old, new } = self;
+        old.validate()?;
+        new.validate()?;
+        if old.path != new.path {
+            return Err(MPTProofError::Mismatch {
+                node: 0,
+                reason: format!(
+                    "old proof is for key {:?}, new proof for {:?}",
+                    old.path, new.path
+                ),
+            });
+        }
+        if (old.value_max_byte_len, old.max_depth) != (new.value_max_byte_len, new.max_depth) {
+            return Err(MPTProofError::Mismatch {
+                node: 0,
+                reason: "old and new proofs have different max lengths".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks everything the transition chip constrains: both proofs verify, and their nodes are the same except
+    /// for the child along the key and the value in the leaf. Returns the first mismatch.
+    pub fn verify_native(&self) -> Result<(), MPTProofError> {
+        self.validate()?;
+        let Self { old, new } = self;
+        old.verify_native()?;
+        new.verify_native()?;
+        if old.proof.len() != new.proof.len() {
+            return Err(MPTProofError::Mismatch {
+                node: old.proof.len().min(new.proof.len()) - 1,
+                reason: format!(
+                    "old proof has {} nodes, new proof has {}",
+                    old.proof.len(),
+                    new.proof.len()
+                ),
+            });
+        }
+
+        let path: Vec<u8> = old.path.as_bytes().iter().flat_map(|b| [b >> 4, b & 0xf]).collect();
+        let mut path_idx = 0;
+        for (idx, (old_node, new_node)) in old.proof.iter().zip(new.proof.iter()).enumerate() {
+            let mismatch =
+                |reason: &str| MPTProofError::Mismatch { node: idx, reason: reason.into() };
+            // both proofs verified, so the nodes are valid
+            let (old_node, new_node) = (Rlp::new(old_node), Rlp::new(new_node));
+            let item_count = old_node.item_count().unwrap();
+            if new_node.item_count().unwrap() != item_count {
+                return Err(mismatch("old and new nodes have different types"));
+            }
+            if item_count == 2 {
+                let encoded_path = old_node.at(0).unwrap().data().unwrap();
+                if new_node.at(0).unwrap().data().unwrap() != encoded_path {
+                    return Err(mismatch("old and new nodes have different paths"));
+                }
+                path_idx += decode_hex_prefix(encoded_path).unwrap().0.len();
+            } else {
+                let nibble = path[path_idx] as usize;
+                path_idx += 1;
+                let item = |node: &Rlp, i| node.at(i).unwrap().as_raw().to_vec();
+                if (0..17).any(|i| i != nibble && item(&old_node, i) != item(&new_node, i)) {
+                    return Err(mismatch("branch changed off the path of the key"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as [`MPTFixedKeyInput::assign`] for both proofs.
+    pub fn assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> MPTTransitionProof<'v, F> {
+        MPTTransitionProof { old: self.old.assign(ctx, gate), new: self.new.assign(ctx, gate) }
+    }
+
+    /// Same as [`MPTTransitionInput::assign`], but returns an error instead of panicking if a proof is invalid.
+    pub fn try_assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> Result<MPTTransitionProof<'v, F>, MPTProofError> {
+        self.validate()?;
+        Ok(self.assign(ctx, gate))
+    }
+}
+
+impl<'v, F: Field> MPTChip<'v, F> {
+    /// Parses the proofs of the same key against the old and new roots. Constrains the proofs to have the same
+    /// depth, node types and key fragments, so they walk the same path down both tries.
+    pub fn parse_mpt_transition_fixed_key_phase0(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        proof: MPTTransitionProof<'v, F>,
+        key_byte_len: usize,
+        value_max_byte_len: usize,
+        max_depth: usize,
+    ) -> MPTTransitionWitness<'v, F> {
+        let MPTTransitionProof { old, new } = proof;
+        for (old_byte, new_byte) in old.key_bytes.iter().zip(new.key_bytes.iter()) {
+            ctx.constrain_equal(old_byte, new_byte);
+        }
+        ctx.constrain_equal(&old.depth, &new.depth);
+        for (old_node, new_node) in old.nodes.iter().zip(new.nodes.iter()) {
+            ctx.constrain_equal(&old_node.node_type, &new_node.node_type);
+        }
+        for (old_frag, new_frag) in old.key_frag.iter().zip(new.key_frag.iter()) {
+            for (old_nibble, new_nibble) in old_frag.nibbles.iter().zip(new_frag.nibbles.iter()) {
+                ctx.constrain_equal(old_nibble, new_nibble);
+            }
+            ctx.constrain_equal(&old_frag.is_odd, &new_frag.is_odd);
+            ctx.constrain_equal(&old_frag.byte_len, &new_frag.byte_len);
+        }
+
+        let old = self.parse_mpt_inclusion_fixed_key_phase0(
+            ctx,
+            old,
+            key_byte_len,
+            value_max_byte_len,
+            max_depth,
+        );
+        let new = self.parse_mpt_inclusion_fixed_key_phase0(
+            ctx,
+            new,
+            key_byte_len,
+            value_max_byte_len,
+            max_depth,
+        );
+        MPTTransitionWitness { old, new }
+    }
+
+    /// Checks both inclusion proofs, and that every branch on the path has the same children in the old and new
+    /// tries except for the child along the key, and the same value.
+    ///
+    /// With the phase 0 constraints, extensions and leaves also have the same paths, so the tries only differ in
+    /// the value at the key and in the nodes above it.
+    pub fn parse_mpt_transition_fixed_key_phase1(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: MPTTransitionWitness<'v, F>,
+    ) {
+        let MPTTransitionWitness { old, new } = witness;
+        let max_depth = old.proof.max_depth;
+        // the index of the child along the key, for the nodes that are branches
+        let path_nibbles =
+            old.proof.key_frag.iter().map(|frag| frag.nibbles[0].clone()).collect_vec();
+
+        let old = self.parse_mpt_inclusion_fixed_key_phase1(ctx, old);
+        let new = self.parse_mpt_inclusion_fixed_key_phase1(ctx, new);
+
+        // extensions and the nodes past the depth are parsed as the same dummy branch in both proofs
+        for idx in 0..max_depth - 1 {
+            let is_child = self.gate().idx_to_indicator(ctx, Existing(&path_nibbles[idx]), 16);
+            for (item, (old_ref, new_ref)) in old.branches_parsed[idx]
+                .node_refs
+                .iter()
+                .zip(new.branches_parsed[idx].node_refs.iter())
+                .enumerate()
+            {
+                let is_equal =
+                    rlc_is_equal(ctx, self.gate(), &old_ref.field_trace, &new_ref.field_trace);
+                if item < 16 {
+                    let is_valid =
+                        self.gate().or(ctx, Existing(&is_equal), Existing(&is_child[item]));
+                    self.gate().assert_is_const(ctx, &is_valid, F::one());
+                } else {
+                    // the value of the branch
+                    self.gate().assert_is_const(ctx, &is_equal, F::one());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! An in-memory Merkle-Patricia trie, to generate proofs without a provider, e.g. for test fixtures or for data
+//! stored in our own tries.
+//!
+//! Keys are used as given: for a secure trie such as Ethereum's state and storage tries, insert `keccak256(key)`.
+use super::MPTFixedKeyInput;
+use ethers_core::{types::H256, utils::keccak256};
+use rlp::RlpStream;
+use std::mem;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0xf]).collect()
+}
+
+fn hex_prefix_encode(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = 2 * u8::from(is_leaf) + (nibbles.len() % 2) as u8;
+    let (first, rest) = if nibbles.len() % 2 == 1 {
+        ((flag << 4) | nibbles[0], &nibbles[1..])
+    } else {
+        (flag << 4, nibbles)
+    };
+    let mut encoded = vec![first];
+    encoded.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+impl Node {
+    fn branch() -> Self {
+        Node::Branch { children: Box::default(), value: None }
+    }
+
+    fn insert(self, path: &[u8], value: Vec<u8>) -> Self {
+        match self {
+            Node::Empty => Node::Leaf { path: path.to_vec(), value },
+            Node::Leaf { path: leaf_path, value: leaf_value } => {
+                if leaf_path == path {
+                    return Node::Leaf { path: leaf_path, value };
+                }
+                let common = common_prefix_len(&leaf_path, path);
+                let branch = Node::branch()
+                    .insert(&leaf_path[common..], leaf_value)
+                    .insert(&path[common..], value);
+                Node::extension(&path[..common], branch)
+            }
+            Node::Extension { path: ext_path, child } => {
+                let common = common_prefix_len(&ext_path, path);
+                if common == ext_path.len() {
+                    return Node::Extension {
+                        child: Box::new(child.insert(&path[common..], value)),
+                        path: ext_path,
+                    };
+                }
+                // split the extension at the first differing nibble
+                let mut children: Box<[Node; 16]> = Box::default();
+                children[ext_path[common] as usize] =
+                    Node::extension(&ext_path[common + 1..], *child);
+                let branch = Node::Branch { children, value: None }.insert(&path[common..], value);
+                Node::extension(&path[..common], branch)
+            }
+            Node::Branch { mut children, value: branch_value } => match path.split_first() {
+                None => Node::Branch { children, value: Some(value) },
+                Some((&nibble, rest)) => {
+                    let child = mem::take(&mut children[nibble as usize]);
+                    children[nibble as usize] = child.insert(rest, value);
+                    Node::Branch { children, value: branch_value }
+                }
+            },
+        }
+    }
+
+    /// An extension with `path` to `child`, or `child` itself if `path` is empty.
+    fn extension(path: &[u8], child: Node) -> Self {
+        if path.is_empty() {
+            child
+        } else {
+            Node::Extension { path: path.to_vec(), child: Box::new(child) }
+        }
+    }
+
+    fn rlp_bytes(&self) -> Vec<u8> {
+        match self {
+            Node::Empty => vec![0x80],
+            Node::Leaf { path, value } => {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(&hex_prefix_encode(path, true)).append(value);
+                stream.out().to_vec()
+            }
+            Node::Extension { path, child } => {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(&hex_prefix_encode(path, false));
+                child.append_ref(&mut stream);
+                stream.out().to_vec()
+            }
+            Node::Branch { children, value } => {
+                let mut stream = RlpStream::new_list(17);
+                for child in children.iter() {
+                    child.append_ref(&mut stream);
+                }
+                match value {
+                    Some(value) => stream.append(value),
+                    None => stream.append_empty_data(),
+                };
+                stream.out().to_vec()
+            }
+        }
+    }
+
+    /// Appends the reference to this node in its parent: its hash, or the node itself if it is shorter than 32
+    /// bytes.
+    fn append_ref(&self, stream: &mut RlpStream) {
+        let rlp_bytes = self.rlp_bytes();
+        if *self == Node::Empty {
+            stream.append_empty_data();
+        } else if rlp_bytes.len() < 32 {
+            stream.append_raw(&rlp_bytes, 1);
+        } else {
+            stream.append(&H256(keccak256(rlp_bytes)));
+        }
+    }
+}
+
+/// An in-memory Merkle-Patricia trie.
+#[derive(Clone, Debug, Default)]
+pub struct Trie {
+    root: Node,
+}
+
+impl Trie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `value` at `key`, replacing the previous value. `value` is stored as is, e.g. the storage trie
+    /// stores the RLP encoding of the slot value.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        let root = mem::take(&mut self.root);
+        self.root = root.insert(&to_nibbles(key), value);
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.walk(key).1
+    }
+
+    /// The root hash. The root of the empty trie is `keccak256(rlp(""))`.
+    pub fn root(&self) -> H256 {
+        H256(keccak256(self.root.rlp_bytes()))
+    }
+
+    /// The RLP encoded nodes on the path of `key`, from the root, and the value at `key` if any.
+    fn walk(&self, key: &[u8]) -> (Vec<Vec<u8>>, Option<&[u8]>) {
+        let key = to_nibbles(key);
+        let mut path = &key[..];
+        let mut node = &self.root;
+        let mut proof = vec![];
+        loop {
+            if *node != Node::Empty {
+                proof.push(node.rlp_bytes());
+            }
+            match node {
+                Node::Empty => return (proof, None),
+                Node::Leaf { path: leaf_path, value } => {
+                    let value = (leaf_path[..] == path[..]).then_some(&value[..]);
+                    return (proof, value);
+                }
+                Node::Extension { path: ext_path, child } => {
+                    match path.strip_prefix(&ext_path[..]) {
+                        Some(rest) => path = rest,
+                        None => return (proof, None),
+                    }
+                    node = child.as_ref();
+                }
+                Node::Branch { children, value } => match path.split_first() {
+                    None => return (proof, value.as_deref()),
+                    Some((&nibble, rest)) => {
+                        path = rest;
+                        node = &children[nibble as usize];
+                    }
+                },
+            }
+        }
+    }
+
+    /// The nodes on the path of `key`, from the root. This is an inclusion proof if `key` is in the trie, and an
+    /// exclusion proof otherwise.
+    ///
+    /// Unlike `eth_getProof`, nodes embedded in their parent are also listed, as after
+    /// [`MPTFixedKeyInput::with_embedded_nodes`].
+    pub fn proof(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        self.walk(key).0
+    }
+
+    /// The proof that `key` is not in the trie, or `None` if it is.
+    pub fn exclusion_proof(&self, key: &[u8]) -> Option<Vec<Vec<u8>>> {
+        match self.walk(key) {
+            (proof, None) => Some(proof),
+            (_, Some(_)) => None,
+        }
+    }
+
+    /// The inclusion proof of `key` as circuit input, or `None` if `key` is not in the trie.
+    pub fn fixed_key_input(
+        &self,
+        key: H256,
+        value_max_byte_len: usize,
+        max_depth: usize,
+    ) -> Option<MPTFixedKeyInput> {
+        let (proof, value) = self.walk(key.as_bytes());
+        Some(MPTFixedKeyInput {
+            path: key,
+            value: value?.to_vec(),
+            root_hash: self.root(),
+            proof,
+            value_max_byte_len,
+            max_depth,
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! Loading of the KZG universal setup.
+//!
+//! Proofs must only be generated with parameters derived from a public ceremony, e.g. the perpetual powers of tau
+//! converted to the halo2 format (`perpetual-powers-of-tau-raw-{n}` files). The ceremony file is downsized to
+//! the `k` a circuit needs and the result is cached as `ceremony_bn254_{k}.srs`, all inside `PARAMS_DIR`
+//! (default `./params`).
+//!
+//! `halo2_base::utils::fs::gen_srs` creates a setup from a known seed; anyone who knows the seed can forge proofs.
+//! It is only used as a fallback when the crate is built with the `dev-srs` feature.
+use halo2_base::halo2_proofs::{
+    halo2curves::bn256::Bn256,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use std::{
+    env::var,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::PathBuf,
+};
+
+/// Largest `k` of a ceremony file we look for.
+pub const MAX_CEREMONY_K: u32 = 28;
+
+pub fn params_dir() -> PathBuf {
+    var("PARAMS_DIR").unwrap_or_else(|_| "./params".to_string()).into()
+}
+
+/// Path of the ceremony file. `SRS_CEREMONY_PATH` takes precedence; otherwise the smallest
+/// `perpetual-powers-of-tau-raw-{n}` with `n >= k` in `PARAMS_DIR` is used.
+pub fn ceremony_path(k: u32) -> Option<PathBuf> {
+    if let Ok(path) = var("SRS_CEREMONY_PATH") {
+        return Some(path.into());
+    }
+    let dir = params_dir();
+    (k..=MAX_CEREMONY_K)
+        .map(|n| dir.join(format!("perpetual-powers-of-tau-raw-{n}")))
+        .find(|path| path.exists())
+}
+
+/// Path of the cached ceremony parameters downsized to `k`.
+pub fn cached_srs_path(k: u32) -> PathBuf {
+    params_dir().join(format!("ceremony_bn254_{k}.srs"))
+}
+
+fn read_params(path: &PathBuf) -> io::Result<ParamsKZG<Bn256>> {
+    ParamsKZG::<Bn256>::read(&mut BufReader::new(File::open(path)?))
+}
+
+/// Makes sure the ceremony parameters for `k` are cached on disk and returns the path of the cached file.
+/// Other `halo2_proofs` backends can read the file directly.
+pub fn ensure_srs_file(k: u32) -> io::Result<PathBuf> {
+    let cached = cached_srs_path(k);
+    if cached.exists() {
+        return Ok(cached);
+    }
+    let ceremony = ceremony_path(k).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "no ceremony file found for k = {k}: set SRS_CEREMONY_PATH or put a \
+                 perpetual-powers-of-tau-raw-{{n}} file with n >= {k} in {:?}",
+                params_dir()
+            ),
+        )
+    })?;
+    let mut params = read_params(&ceremony)?;
+    if params.k() < k {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ceremony file {ceremony:?} only supports k <= {}, need {k}", params.k()),
+        ));
+    }
+    params.downsize(k);
+    fs::create_dir_all(params_dir())?;
+    // write to a temporary file first so an interrupted write is never mistaken for a valid cache entry
+    let tmp = cached.with_extension("srs.tmp");
+    params.write(&mut BufWriter::new(File::create(&tmp)?))?;
+    fs::rename(tmp, &cached)?;
+    Ok(cached)
+}
+
+/// Loads the ceremony parameters for `k`, downsizing and caching them if needed.
+pub fn try_get_srs(k: u32) -> io::Result<ParamsKZG<Bn256>> {
+    let params = read_params(&ensure_srs_file(k)?)?;
+    assert_eq!(params.k(), k);
+    Ok(params)
+}
+
+/// Loads the ceremony parameters for `k`.
+///
+/// # Panics
+/// If no ceremony file is available, unless the `dev-srs` feature is enabled, in which case an insecure setup
+/// is generated instead.
+pub fn get_srs(k: u32) -> ParamsKZG<Bn256> {
+    match try_get_srs(k) {
+        Ok(params) => params,
+        #[cfg(feature = "dev-srs")]
+        Err(e) => {
+            log::warn!("{e}. Falling back to an INSECURE setup, do not use it in production.");
+            halo2_base::utils::fs::gen_srs(k)
+        }
+        #[cfg(not(feature = "dev-srs"))]
+        Err(e) => panic!("{e}. Enable the `dev-srs` feature to use an insecure setup instead."),
+    }
+}
@@ -4,8 +4,14 @@ use crate::{
         EthBlockHeaderChainInstance, GOERLI_BLOCK_HEADER_RLP_MAX_BYTES,
         MAINNET_BLOCK_HEADER_RLP_MAX_BYTES,
     },
+    error::{json_field, read_json, InputError},
     mpt::MPTFixedKeyInput,
-    storage::{EthBlockStorageInput, EthStorageInput},
+    storage::{
+        beacon_root::{beacon_roots_slots, EthBeaconRootInput, BEACON_ROOTS_ADDRESS},
+        EthBlockStorageInput, EthStorageInput, ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
+        DEFAULT_ACCOUNT_PROOF_MAX_DEPTH, DEFAULT_STORAGE_PROOF_MAX_DEPTH,
+        STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
+    },
     util::{get_merkle_mountain_range, u256_to_bytes32_be},
     Network,
 };
@@ -33,9 +39,6 @@ use tokio::runtime::Runtime;
 pub const MAINNET_PROVIDER_URL: &str = "https://mainnet.infura.io/v3/";
 pub const GOERLI_PROVIDER_URL: &str = "https://goerli.infura.io/v3/";
 
-const ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN: usize = 114;
-const STORAGE_PROOF_VALUE_MAX_BYTE_LEN: usize = 33;
-
 pub fn get_block_storage_input(
     provider: &Provider<Http>,
     block_number: u32,
@@ -46,9 +49,8 @@ pub fn get_block_storage_input(
 ) -> EthBlockStorageInput {
     let rt = Runtime::new().unwrap();
 
-    let block = rt.block_on(provider.get_block(block_number as u64)).unwrap().unwrap();
-    let block_hash = block.hash.unwrap();
-    let block_header = get_block_rlp(&block);
+    let (block, block_header) =
+        get_block_and_header(&rt, provider, block_number).unwrap_or_else(|e| panic!("{e}"));
 
     let pf = rt
         .block_on(provider.get_proof(addr, slots, Some(Number(BlockNumber::from(block_number)))))
@@ -58,57 +60,92 @@ pub fn get_block_storage_input(
         println!("key: {:?}, is_assigned_slot: {}", storage_pf.key, is_assigned_slot(&storage_pf));
     }
 
-    let acct_pf = MPTFixedKeyInput {
-        path: H256(keccak256(addr)),
-        value: get_acct_rlp(&pf),
-        root_hash: block.state_root,
-        proof: pf.account_proof.into_iter().map(|x| x.to_vec()).collect(),
-        value_max_byte_len: ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
-        max_depth: acct_pf_max_depth,
-    };
-
-    let storage_pfs = pf
-        .storage_proof
-        .into_iter()
-        .map(|storage_pf| {
-            (
-                storage_pf.key,
-                storage_pf.value,
-                MPTFixedKeyInput {
-                    path: H256(keccak256(storage_pf.key)),
-                    value: storage_pf.value.rlp_bytes().to_vec(),
-                    root_hash: pf.storage_hash,
-                    proof: storage_pf.proof.into_iter().map(|x| x.to_vec()).collect(),
-                    value_max_byte_len: STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
-                    max_depth: storage_pf_max_depth,
-                },
-            )
-        })
-        .collect();
-
-    EthBlockStorageInput {
+    block_storage_input_with_header(
         block,
-        block_number,
-        block_hash,
         block_header,
-        storage: EthStorageInput { addr, acct_pf, storage_pfs },
-    }
+        pf,
+        acct_pf_max_depth,
+        storage_pf_max_depth,
+    )
+    .unwrap_or_else(|e| panic!("{e}"))
 }
 
+/// Beacon root input of the block `block_number`, with the proof of its timestamp and `parentBeaconBlockRoot` in
+/// the storage of [`BEACON_ROOTS_ADDRESS`], see [`EthBeaconRootInput`]. Fails if the block is from before Dencun.
+pub fn get_beacon_root_input(
+    provider: &Provider<Http>,
+    block_number: u32,
+    acct_pf_max_depth: usize,
+    storage_pf_max_depth: usize,
+) -> Result<EthBeaconRootInput, InputError> {
+    let rt = Runtime::new().unwrap();
+    let (block, block_header) = get_block_and_header(&rt, provider, block_number)?;
+    // the system call at the start of the block stores its root, so the proof is against the block's own state
+    let slots = beacon_roots_slots(block.timestamp.low_u64()).to_vec();
+    let block_id = Some(Number(BlockNumber::from(block_number)));
+    let pf = rt
+        .block_on(provider.get_proof(BEACON_ROOTS_ADDRESS, slots, block_id))
+        .map_err(|e| InputError::Rpc { field: "eth_getProof".into(), message: e.to_string() })?;
+    let storage = block_storage_input_with_header(
+        block,
+        block_header.clone(),
+        pf,
+        acct_pf_max_depth,
+        storage_pf_max_depth,
+    )?
+    .storage;
+    EthBeaconRootInput::from_header(block_header, Some(storage))
+}
 
-pub fn saved_block_storage_input(
-    json_path: &str,
-) -> EthBlockStorageInput {
-
-    let store_str = std::fs::read_to_string(json_path).unwrap();
-    let storage: serde_json::Value = serde_json::from_str(store_str.as_str()).unwrap();
+/// The block `block_number` and its RLP encoded header. The block is fetched as raw JSON, so that the header
+/// includes the fields added after London, see [`PostLondonHeaderFields`].
+fn get_block_and_header(
+    rt: &Runtime,
+    provider: &Provider<Http>,
+    block_number: u32,
+) -> Result<(Block<H256>, Vec<u8>), InputError> {
+    let field = "eth_getBlockByNumber";
+    let block: serde_json::Value = rt
+        .block_on(provider.request(field, (BlockNumber::from(block_number), false)))
+        .map_err(|e| InputError::Rpc { field: field.into(), message: e.to_string() })?;
+    if block.is_null() {
+        return Err(InputError::Rpc {
+            field: field.into(),
+            message: format!("block {block_number} not found"),
+        });
+    }
+    block_and_header(&block, "block")
+}
 
-    let block: Block<H256> = serde_json::from_value(storage["block"].clone()).unwrap();
-    let block_hash = block.hash.unwrap();
-    let block_header = get_block_rlp(&block);
-    let block_number: u32 = block.number.unwrap().low_u32();
+/// Storage proof input from a block and the `eth_getProof` response for it.
+pub fn try_block_storage_input(
+    block: Block<H256>,
+    pf: EIP1186ProofResponse,
+    acct_pf_max_depth: usize,
+    storage_pf_max_depth: usize,
+) -> Result<EthBlockStorageInput, InputError> {
+    let block_header = try_get_block_rlp(&block)?;
+    block_storage_input_with_header(
+        block,
+        block_header,
+        pf,
+        acct_pf_max_depth,
+        storage_pf_max_depth,
+    )
+}
 
-    let pf: EIP1186ProofResponse = serde_json::from_value(storage["account"].clone()).unwrap(); //serde_json::from_str(pf_str.as_str()).unwrap();
+/// Like [`try_block_storage_input`], but with the RLP encoded header of `block` given instead of re-encoded from
+/// its fields, e.g. from [`block_from_header_rlp`].
+pub fn block_storage_input_with_header(
+    block: Block<H256>,
+    block_header: Vec<u8>,
+    pf: EIP1186ProofResponse,
+    acct_pf_max_depth: usize,
+    storage_pf_max_depth: usize,
+) -> Result<EthBlockStorageInput, InputError> {
+    let block_hash = block.hash.ok_or_else(|| InputError::MissingField("block.hash".into()))?;
+    let block_number =
+        block.number.ok_or_else(|| InputError::MissingField("block.number".into()))?.low_u32();
     let addr = pf.address;
 
     let acct_pf = MPTFixedKeyInput {
@@ -117,8 +154,9 @@ pub fn saved_block_storage_input(
         root_hash: block.state_root,
         proof: pf.account_proof.into_iter().map(|x| x.to_vec()).collect(),
         value_max_byte_len: ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
-        max_depth: 8,
-    };
+        max_depth: acct_pf_max_depth,
+    }
+    .with_embedded_nodes();
 
     let storage_pfs = pf
         .storage_proof
@@ -133,70 +171,196 @@ pub fn saved_block_storage_input(
                     root_hash: pf.storage_hash,
                     proof: storage_pf.proof.into_iter().map(|x| x.to_vec()).collect(),
                     value_max_byte_len: STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
-                    max_depth: 8,
-                },
+                    max_depth: storage_pf_max_depth,
+                }
+                .with_embedded_nodes(),
             )
         })
         .collect();
 
-    EthBlockStorageInput {
+    Ok(EthBlockStorageInput {
         block,
         block_number,
         block_hash,
         block_header,
-        storage: EthStorageInput { addr, acct_pf, storage_pfs },
-    }
+        storage: EthStorageInput::new(addr, acct_pf, storage_pfs),
+    })
 }
 
-pub fn saved_block_storage_input_fromstr(
+/// Version of the storage proof input schema, see [`try_saved_block_storage_input`].
+pub const STORAGE_INPUT_VERSION: u64 = 1;
+
+/// Reads a saved storage proof input. The format is detected from the json:
+/// * `{ "version": 1, "block": .., "proof": .. }`, where `block` is the `eth_getBlockByNumber` result or the
+///   RLP encoded header as a hex string (e.g. the `debug_getRawHeader` result), and `proof` is the
+///   `eth_getProof` result. Both may also be the unmodified JSON-RPC responses.
+/// * The unversioned `{ "block": .., "account": .. }`, with `account` the `eth_getProof` result.
+///
+/// The proofs get the default max depths [`DEFAULT_ACCOUNT_PROOF_MAX_DEPTH`] and
+/// [`DEFAULT_STORAGE_PROOF_MAX_DEPTH`], see [`EthStorageInput::set_max_depths`] to change them.
+pub fn try_saved_block_storage_input(json_path: &str) -> Result<EthBlockStorageInput, InputError> {
+    block_storage_input_from_json(&read_json(json_path)?)
+}
+
+pub fn try_saved_block_storage_input_fromstr(
     json_input: &str,
-) -> EthBlockStorageInput {
+) -> Result<EthBlockStorageInput, InputError> {
+    let storage = serde_json::from_str(json_input).map_err(|e| InputError::json("input", e))?;
+    block_storage_input_from_json(&storage)
+}
+
+/// Storage proof input from separately saved JSON-RPC responses: `block_path` holds the `eth_getBlockByNumber` or
+/// `debug_getRawHeader` response, or just the header RLP as hex, and `proof_path` the `eth_getProof` response.
+pub fn try_rpc_block_storage_input(
+    block_path: &str,
+    proof_path: &str,
+) -> Result<EthBlockStorageInput, InputError> {
+    let block = fs::read_to_string(block_path)
+        .map_err(|source| InputError::Io { path: block_path.to_string(), source })?;
+    let block = serde_json::from_str(&block)
+        .unwrap_or_else(|_| serde_json::Value::String(block.trim().to_string()));
+    let pf = rpc_result(&read_json(proof_path)?, proof_path)?;
+    let pf = EIP1186ProofResponse::deserialize(pf).map_err(|e| InputError::json(proof_path, e))?;
+    let (block, block_header) = block_and_header(&block, block_path)?;
+    block_storage_input_with_header(
+        block,
+        block_header,
+        pf,
+        DEFAULT_ACCOUNT_PROOF_MAX_DEPTH,
+        DEFAULT_STORAGE_PROOF_MAX_DEPTH,
+    )
+}
 
-    let storage: serde_json::Value = serde_json::from_str(json_input).unwrap();
+fn block_storage_input_from_json(
+    storage: &serde_json::Value,
+) -> Result<EthBlockStorageInput, InputError> {
+    let proof_field = match storage.get("version") {
+        None => "account",
+        Some(version) => {
+            let version = version.as_u64().ok_or_else(|| InputError::InvalidField {
+                field: "version".into(),
+                reason: format!("expected an integer, found {version}"),
+            })?;
+            if version != STORAGE_INPUT_VERSION {
+                return Err(InputError::UnsupportedVersion {
+                    version,
+                    supported: STORAGE_INPUT_VERSION,
+                });
+            }
+            "proof"
+        }
+    };
+    let block = storage.get("block").ok_or_else(|| InputError::MissingField("block".into()))?;
+    let (block, block_header) = block_and_header(block, "block")?;
+    let pf = storage
+        .get(proof_field)
+        .ok_or_else(|| InputError::MissingField(proof_field.into()))
+        .and_then(|pf| rpc_result(pf, proof_field))?;
+    let pf = EIP1186ProofResponse::deserialize(pf).map_err(|e| InputError::json(proof_field, e))?;
+    block_storage_input_with_header(
+        block,
+        block_header,
+        pf,
+        DEFAULT_ACCOUNT_PROOF_MAX_DEPTH,
+        DEFAULT_STORAGE_PROOF_MAX_DEPTH,
+    )
+}
 
-    let block: Block<H256> = serde_json::from_value(storage["block"].clone()).unwrap();
-    let block_hash = block.hash.unwrap();
-    let block_header = get_block_rlp(&block);
-    let block_number: u32 = block.number.unwrap().low_u32();
+/// The `result` of `value` if it is a JSON-RPC response, otherwise `value` itself.
+fn rpc_result<'a>(
+    value: &'a serde_json::Value,
+    field: &str,
+) -> Result<&'a serde_json::Value, InputError> {
+    if value.get("jsonrpc").is_none() {
+        return Ok(value);
+    }
+    if let Some(error) = value.get("error") {
+        return Err(InputError::Rpc { field: field.to_string(), message: error.to_string() });
+    }
+    match value.get("result") {
+        Some(serde_json::Value::Null) | None => {
+            Err(InputError::MissingField(format!("{field}.result")))
+        }
+        Some(result) => Ok(result),
+    }
+}
 
-    let pf: EIP1186ProofResponse = serde_json::from_value(storage["account"].clone()).unwrap(); //serde_json::from_str(pf_str.as_str()).unwrap();
-    let addr = pf.address;
+/// The block and its RLP encoded header from either a block object or the header RLP as a hex string.
+fn block_and_header(
+    value: &serde_json::Value,
+    field: &str,
+) -> Result<(Block<H256>, Vec<u8>), InputError> {
+    match rpc_result(value, field)? {
+        serde_json::Value::String(header) => {
+            let header =
+                ethers_core::utils::hex::decode(header.trim_start_matches("0x")).map_err(|e| {
+                    InputError::InvalidField {
+                        field: field.to_string(),
+                        reason: format!("header is not hex: {e}"),
+                    }
+                })?;
+            Ok((block_from_header_rlp(&header, field)?, header))
+        }
+        json => {
+            let block = Block::<H256>::deserialize(json).map_err(|e| InputError::json(field, e))?;
+            let header =
+                try_get_block_rlp_with(&block, &PostLondonHeaderFields::from_json(json, field)?)?;
+            Ok((block, header))
+        }
+    }
+}
 
-    let acct_pf = MPTFixedKeyInput {
-        path: H256(keccak256(addr)),
-        value: get_acct_rlp(&pf),
-        root_hash: block.state_root,
-        proof: pf.account_proof.into_iter().map(|x| x.to_vec()).collect(),
-        value_max_byte_len: ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
-        max_depth: 8,
+/// Decodes the header fields of an RLP encoded block header. Fields added after London, e.g. `withdrawalsRoot`,
+/// are not decoded, but are kept in `header` itself.
+pub fn block_from_header_rlp(header: &[u8], field: &str) -> Result<Block<H256>, InputError> {
+    let invalid = |e: rlp::DecoderError| InputError::InvalidField {
+        field: field.to_string(),
+        reason: format!("invalid header RLP: {e}"),
     };
+    let rlp = Rlp::new(header);
+    let item_count = rlp.item_count().map_err(invalid)?;
+    if item_count < 15 {
+        return Err(InputError::InvalidField {
+            field: field.to_string(),
+            reason: format!("header has {item_count} fields, expected at least 15"),
+        });
+    }
+    Ok(Block {
+        hash: Some(H256(keccak256(header))),
+        parent_hash: rlp.val_at(0).map_err(invalid)?,
+        uncles_hash: rlp.val_at(1).map_err(invalid)?,
+        author: Some(rlp.val_at(2).map_err(invalid)?),
+        state_root: rlp.val_at(3).map_err(invalid)?,
+        transactions_root: rlp.val_at(4).map_err(invalid)?,
+        receipts_root: rlp.val_at(5).map_err(invalid)?,
+        logs_bloom: Some(rlp.val_at(6).map_err(invalid)?),
+        difficulty: rlp.val_at(7).map_err(invalid)?,
+        number: Some(rlp.val_at(8).map_err(invalid)?),
+        gas_limit: rlp.val_at(9).map_err(invalid)?,
+        gas_used: rlp.val_at(10).map_err(invalid)?,
+        timestamp: rlp.val_at(11).map_err(invalid)?,
+        extra_data: rlp.val_at::<Vec<u8>>(12).map_err(invalid)?.into(),
+        mix_hash: Some(rlp.val_at(13).map_err(invalid)?),
+        nonce: Some(rlp.val_at(14).map_err(invalid)?),
+        base_fee_per_gas: if item_count > 15 {
+            Some(rlp.val_at(15).map_err(invalid)?)
+        } else {
+            None
+        },
+        ..Default::default()
+    })
+}
 
-    let storage_pfs = pf
-        .storage_proof
-        .into_iter()
-        .map(|storage_pf| {
-            (
-                storage_pf.key,
-                storage_pf.value,
-                MPTFixedKeyInput {
-                    path: H256(keccak256(storage_pf.key)),
-                    value: storage_pf.value.rlp_bytes().to_vec(),
-                    root_hash: pf.storage_hash,
-                    proof: storage_pf.proof.into_iter().map(|x| x.to_vec()).collect(),
-                    value_max_byte_len: STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
-                    max_depth: 8,
-                },
-            )
-        })
-        .collect();
+/// # Panics
+/// If the input is malformed, see [`try_saved_block_storage_input`].
+pub fn saved_block_storage_input(json_path: &str) -> EthBlockStorageInput {
+    try_saved_block_storage_input(json_path).unwrap_or_else(|e| panic!("{e}"))
+}
 
-    EthBlockStorageInput {
-        block,
-        block_number,
-        block_hash,
-        block_header,
-        storage: EthStorageInput { addr, acct_pf, storage_pfs },
-    }
+/// # Panics
+/// If the input is malformed, see [`try_saved_block_storage_input_fromstr`].
+pub fn saved_block_storage_input_fromstr(json_input: &str) -> EthBlockStorageInput {
+    try_saved_block_storage_input_fromstr(json_input).unwrap_or_else(|e| panic!("{e}"))
 }
 
 pub fn is_assigned_slot(pf: &StorageProof) -> bool {
@@ -250,26 +414,98 @@ pub fn get_acct_rlp(pf: &EIP1186ProofResponse) -> Vec<u8> {
     rlp.out().into()
 }
 
+/// The header fields added after London, which the `Block` type of `ethers` 1.0 does not have. Read them from the
+/// raw `eth_getBlockByNumber` response with [`PostLondonHeaderFields::from_json`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PostLondonHeaderFields {
+    /// Shanghai
+    pub withdrawals_root: Option<H256>,
+    /// Dencun
+    pub blob_gas_used: Option<U256>,
+    pub excess_blob_gas: Option<U256>,
+    pub parent_beacon_block_root: Option<H256>,
+}
+
+impl PostLondonHeaderFields {
+    /// Reads the fields from a block object, leaving the fields it does not have `None`.
+    pub fn from_json(block: &serde_json::Value, field: &str) -> Result<Self, InputError> {
+        fn get<T: serde::de::DeserializeOwned>(
+            block: &serde_json::Value,
+            field: &str,
+            name: &str,
+        ) -> Result<Option<T>, InputError> {
+            match block.get(name) {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(value) => T::deserialize(value)
+                    .map(Some)
+                    .map_err(|e| InputError::json(format!("{field}.{name}"), e)),
+            }
+        }
+        Ok(Self {
+            withdrawals_root: get(block, field, "withdrawalsRoot")?,
+            blob_gas_used: get(block, field, "blobGasUsed")?,
+            excess_blob_gas: get(block, field, "excessBlobGas")?,
+            parent_beacon_block_root: get(block, field, "parentBeaconBlockRoot")?,
+        })
+    }
+}
+
+/// # Panics
+/// If a header field is missing, see [`try_get_block_rlp`].
 pub fn get_block_rlp(block: &Block<H256>) -> Vec<u8> {
-    let base_fee = block.base_fee_per_gas;
-    let mut rlp = RlpStream::new_list(15 + usize::from(base_fee.is_some()));
+    try_get_block_rlp(block).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// The RLP encoded header of `block`, up to London. Fails if a field of the header is missing, e.g. `mixHash` or
+/// `nonce` from the response of a node that omits them for pre-merge blocks. Use [`try_get_block_rlp_with`] for
+/// blocks from after Shanghai.
+pub fn try_get_block_rlp(block: &Block<H256>) -> Result<Vec<u8>, InputError> {
+    try_get_block_rlp_with(block, &PostLondonHeaderFields::default())
+}
+
+/// The RLP encoded header of `block` with the fields added after London in `post_london`. Fails if a field of the
+/// header is missing, including an optional field followed by a present one, e.g. `withdrawalsRoot` of a block
+/// with a `parentBeaconBlockRoot`.
+pub fn try_get_block_rlp_with(
+    block: &Block<H256>,
+    post_london: &PostLondonHeaderFields,
+) -> Result<Vec<u8>, InputError> {
+    fn field<T>(value: Option<T>, name: &str) -> Result<T, InputError> {
+        value.ok_or_else(|| InputError::MissingField(format!("block.{name}")))
+    }
+    // the fields after `nonce` were added by forks, so each one is only present if the ones before it are
+    let optional = [
+        ("baseFeePerGas", block.base_fee_per_gas.map(|x| rlp::encode(&x))),
+        ("withdrawalsRoot", post_london.withdrawals_root.map(|x| rlp::encode(&x))),
+        ("blobGasUsed", post_london.blob_gas_used.map(|x| rlp::encode(&x))),
+        ("excessBlobGas", post_london.excess_blob_gas.map(|x| rlp::encode(&x))),
+        ("parentBeaconBlockRoot", post_london.parent_beacon_block_root.map(|x| rlp::encode(&x))),
+    ];
+    let num_optional = optional.iter().rposition(|(_, value)| value.is_some()).map_or(0, |i| i + 1);
+    let optional = optional[..num_optional]
+        .iter()
+        .map(|(name, value)| field(value.as_ref(), name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rlp = RlpStream::new_list(15 + optional.len());
     rlp.append(&block.parent_hash);
     rlp.append(&block.uncles_hash);
-    rlp.append(&block.author.unwrap());
+    rlp.append(&field(block.author, "miner")?);
     rlp.append(&block.state_root);
     rlp.append(&block.transactions_root);
     rlp.append(&block.receipts_root);
-    rlp.append(&block.logs_bloom.unwrap());
+    rlp.append(&field(block.logs_bloom, "logsBloom")?);
     rlp.append(&block.difficulty);
-    rlp.append(&block.number.unwrap());
+    rlp.append(&field(block.number, "number")?);
     rlp.append(&block.gas_limit);
     rlp.append(&block.gas_used);
     rlp.append(&block.timestamp);
     rlp.append(&block.extra_data.to_vec());
-    rlp.append(&block.mix_hash.unwrap());
-    rlp.append(&block.nonce.unwrap());
-    base_fee.map(|base_fee| rlp.append(&base_fee));
-    rlp.out().into()
+    rlp.append(&field(block.mix_hash, "mixHash")?);
+    rlp.append(&field(block.nonce, "nonce")?);
+    for value in optional {
+        rlp.append_raw(value, 1);
+    }
+    Ok(rlp.out().into())
 }
 
 serde_with::serde_conv!(
@@ -327,15 +563,13 @@ pub fn get_blocks_input(
             let mut prev_hash = H256::zero();
 
             for block_number in start_block_number..start_block_number + num_blocks {
-                let block = rt
-                    .block_on(provider.get_block(block_number as u64))
-                    .expect("get_block JSON-RPC call")
-                    .unwrap_or_else(|| panic!("block {block_number} should exist"));
+                let (block, block_rlp) = get_block_and_header(&rt, provider, block_number)
+                    .unwrap_or_else(|e| panic!("{e}"));
                 if block_number == start_block_number {
                     prev_hash = block.parent_hash;
                 }
                 block_hashes.push(block.hash.unwrap());
-                block_rlps.push(get_block_rlp(&block));
+                block_rlps.push(block_rlp);
             }
             // write this to file
             let file = File::create(path.as_str()).unwrap();
@@ -363,6 +597,10 @@ pub fn get_blocks_input(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::block_header::parent_beacon_block_root;
+
+    // mainnet block 0xef0000, from before Shanghai
+    const BLOCK_0XEF0000_RLP: &str = "f90201a09ed65266c0958d1ba3e3be4329b41ef541391f2db0f53b99506ae1df5db86ab0a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d4934794388c818ca8b9251b393131c08a736a67ccb19297a0771bced6d4acaab391f3996cfb5f6475b6218759efefab7da25f77f01567446aa0339e9acc250d8aa0f041cb9f428dd18ceef89386d0c18a595bf3103caa3a4175a0371131531246fd6b266a67377943fd3ee59d82eb31c0cec6f3d76cc5421c52c2b90100bcfe8a0b973288ca19f84674b03bb7bd6350074141ae9a788099b462dd6e921a92c415f702493ac86038dcb95ab707011310e2bfca23785102478001a07eb45a03d0db880e59b17a6b06acfa006b616804f4cf97a54b164a8e029fc7cd3f9515b3400de03bc76c683d471524493149de2ae00672a27304622034819b9008044ccab685da2b2e911aa44ac8c487904834a66b743917cc267f60f4004660938122bfe1bb83424be44c1ce34af7c501a88a058466e600ebae7391e43947240b80524d52392790f263d9c85a4ae66a3ce7f73a884b4a34df06559084192fc260340a0d33663e4808450412bcbf1363dda86450b89f6f294db842e34518a84b52b4228083ef00008401c9c38083cf055784633a003780a06d81c46262890668551c0a5d37a3ecb03d6e3cc6741a7637a0043c611b3dc8658800000000000000008502615e4790";
 
     #[test]
     fn test_infura() {
@@ -375,6 +613,36 @@ mod tests {
 
         let rt = Runtime::new().unwrap();
         let block = rt.block_on(provider.get_block(0xef0000)).unwrap().unwrap();
-        assert_eq!(hex::encode(get_block_rlp(&block)), "f90201a09ed65266c0958d1ba3e3be4329b41ef541391f2db0f53b99506ae1df5db86ab0a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d4934794388c818ca8b9251b393131c08a736a67ccb19297a0771bced6d4acaab391f3996cfb5f6475b6218759efefab7da25f77f01567446aa0339e9acc250d8aa0f041cb9f428dd18ceef89386d0c18a595bf3103caa3a4175a0371131531246fd6b266a67377943fd3ee59d82eb31c0cec6f3d76cc5421c52c2b90100bcfe8a0b973288ca19f84674b03bb7bd6350074141ae9a788099b462dd6e921a92c415f702493ac86038dcb95ab707011310e2bfca23785102478001a07eb45a03d0db880e59b17a6b06acfa006b616804f4cf97a54b164a8e029fc7cd3f9515b3400de03bc76c683d471524493149de2ae00672a27304622034819b9008044ccab685da2b2e911aa44ac8c487904834a66b743917cc267f60f4004660938122bfe1bb83424be44c1ce34af7c501a88a058466e600ebae7391e43947240b80524d52392790f263d9c85a4ae66a3ce7f73a884b4a34df06559084192fc260340a0d33663e4808450412bcbf1363dda86450b89f6f294db842e34518a84b52b4228083ef00008401c9c38083cf055784633a003780a06d81c46262890668551c0a5d37a3ecb03d6e3cc6741a7637a0043c611b3dc8658800000000000000008502615e4790");
+        assert_eq!(hex::encode(get_block_rlp(&block)), BLOCK_0XEF0000_RLP);
+    }
+
+    #[test]
+    fn test_block_rlp_post_london_fields() {
+        let london_header = hex::decode(BLOCK_0XEF0000_RLP).unwrap();
+        let block = block_from_header_rlp(&london_header, "block").unwrap();
+        let mut json = serde_json::to_value(&block).unwrap();
+        assert_eq!(block_and_header(&json, "block").unwrap().1, london_header);
+
+        // the same block with the fields added by Shanghai and Dencun, so not a real mainnet block
+        let root = H256::repeat_byte(0xbe);
+        json["withdrawalsRoot"] = serde_json::to_value(H256::repeat_byte(0x56)).unwrap();
+        json["blobGasUsed"] = "0x20000".into();
+        json["excessBlobGas"] = "0x0".into();
+        json["parentBeaconBlockRoot"] = serde_json::to_value(root).unwrap();
+        let mut expected = RlpStream::new_list(20);
+        for field in Rlp::new(&london_header).iter() {
+            expected.append_raw(field.as_raw(), 1);
+        }
+        expected.append(&H256::repeat_byte(0x56)).append(&0x20000u64).append(&0u64).append(&root);
+        let (_, header) = block_and_header(&json, "block").unwrap();
+        assert_eq!(header, expected.out().to_vec());
+        assert_eq!(parent_beacon_block_root(&header), Some(root));
+
+        json.as_object_mut().unwrap().remove("withdrawalsRoot");
+        let err = block_and_header(&json, "block").unwrap_err();
+        assert!(
+            matches!(&err, InputError::MissingField(field) if field == "block.withdrawalsRoot"),
+            "{err}"
+        );
     }
 }
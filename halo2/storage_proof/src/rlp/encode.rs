@@ -0,0 +1,395 @@
+//! RLP encoding of byte strings, integers and lists, for values computed in the circuit.
+use super::{
+    max_rlp_len_len,
+    rlc::{RlcTrace, RLC_PHASE},
+    RlpChip,
+};
+use crate::{halo2_proofs::circuit::Value, util::num_to_bytes_be};
+use halo2_base::{
+    gates::{GateInstructions, RangeInstructions},
+    utils::{bit_length, ScalarField},
+    AssignedValue, Context,
+    QuantumCell::{Constant, Existing},
+};
+
+#[derive(Clone, Debug)]
+enum RlpPayloadWitness<'v, F: ScalarField> {
+    /// A byte string, the first `payload_len` cells.
+    Bytes(Vec<AssignedValue<'v, F>>),
+    /// The big endian bytes of an integer, encoded without its leading zeros.
+    Uint(Vec<AssignedValue<'v, F>>),
+    /// A list of the first `num_items` items.
+    List { items: Vec<RlpEncodingWitness<'v, F>>, num_items: AssignedValue<'v, F> },
+}
+
+#[derive(Clone, Debug)]
+pub struct RlpEncodingWitness<'v, F: ScalarField> {
+    prefix: AssignedValue<'v, F>,
+    prefix_len: AssignedValue<'v, F>,
+    len_len: AssignedValue<'v, F>,
+    /// The big endian bytes of the long form length, with leading zeros
+    len_cells: Vec<AssignedValue<'v, F>>,
+    payload_len: AssignedValue<'v, F>,
+    payload: RlpPayloadWitness<'v, F>,
+
+    pub rlp_len: AssignedValue<'v, F>,
+    /// The RLP encoding followed by `0`s. The cells beyond `rlp_len` are not constrained.
+    pub rlp_cells: Vec<AssignedValue<'v, F>>,
+}
+
+/// The values of `cells[start..start + len]`.
+fn subarray_value<F: ScalarField>(
+    cells: &[AssignedValue<F>],
+    start: Value<usize>,
+    len: Value<usize>,
+) -> Value<Vec<F>> {
+    let values = cells.iter().fold(Value::known(Vec::with_capacity(cells.len())), |acc, cell| {
+        acc.zip(cell.value()).map(|(mut acc, value)| {
+            acc.push(*value);
+            acc
+        })
+    });
+    values.zip(start).zip(len).map(|((values, start), len)| values[start..start + len].to_vec())
+}
+
+fn value_to_usize<F: ScalarField>(cell: &AssignedValue<F>) -> Value<usize> {
+    cell.value().map(|value| value.get_lower_32() as usize)
+}
+
+impl<'g, F: ScalarField> RlpChip<'g, F> {
+    /// Returns the number of leading zeros of `bytes`.
+    fn num_leading_zeros<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        bytes: &[AssignedValue<'v, F>],
+    ) -> AssignedValue<'v, F> {
+        if bytes.is_empty() {
+            return self.gate().load_zero(ctx);
+        }
+        // is_zero_prefix[idx] = 1 iff bytes[..=idx] are all zero
+        let mut is_zero_prefix: Vec<AssignedValue<'v, F>> = Vec::with_capacity(bytes.len());
+        for byte in bytes {
+            let is_zero = self.gate().is_zero(ctx, byte);
+            let is_zero = match is_zero_prefix.last() {
+                Some(prev) => self.gate().and(ctx, Existing(prev), Existing(&is_zero)),
+                None => is_zero,
+            };
+            is_zero_prefix.push(is_zero);
+        }
+        self.gate().sum(ctx, is_zero_prefix.iter().map(Existing))
+    }
+
+    /// Returns `(prefix, len_len, len_cells)` of a string or list (depending on `offset = 0x80` or `0xc0`) with
+    /// payload of length `payload_len`. Assumes `payload_len <= max_payload_len`.
+    fn encode_rlp_header_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        payload_len: &AssignedValue<'v, F>,
+        max_payload_len: usize,
+        offset: u64,
+    ) -> (AssignedValue<'v, F>, AssignedValue<'v, F>, Vec<AssignedValue<'v, F>>) {
+        let max_len_len = max_rlp_len_len(max_payload_len);
+        let short_prefix = self.gate().add(ctx, Existing(payload_len), Constant(F::from(offset)));
+        if max_len_len == 0 {
+            return (short_prefix, self.gate().load_zero(ctx), vec![]);
+        }
+        let is_big = self.range.is_less_than(
+            ctx,
+            Constant(F::from(55)),
+            Existing(payload_len),
+            bit_length(max_payload_len as u64),
+        );
+        let big_len = self.gate().mul(ctx, Existing(&is_big), Existing(payload_len));
+        let len_cells = num_to_bytes_be(ctx, &self.range, &big_len, max_len_len);
+        // the long form length has no leading zeros, and is empty for the short form
+        let num_zeros = self.num_leading_zeros(ctx, &len_cells);
+        let len_len =
+            self.gate().sub(ctx, Constant(F::from(max_len_len as u64)), Existing(&num_zeros));
+        let long_prefix = self.gate().add(ctx, Existing(&len_len), Constant(F::from(offset + 55)));
+        let prefix = self.gate().select(
+            ctx,
+            Existing(&long_prefix),
+            Existing(&short_prefix),
+            Existing(&is_big),
+        );
+        (prefix, len_len, len_cells)
+    }
+
+    fn assign_rlp_encoding_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        (prefix, prefix_len): (AssignedValue<'v, F>, AssignedValue<'v, F>),
+        (len_len, len_cells): (AssignedValue<'v, F>, Vec<AssignedValue<'v, F>>),
+        (payload_len, payload, max_payload_len): (
+            AssignedValue<'v, F>,
+            RlpPayloadWitness<'v, F>,
+            usize,
+        ),
+        payload_value: Value<Vec<F>>,
+    ) -> RlpEncodingWitness<'v, F> {
+        let rlp_len = self
+            .gate()
+            .sum(ctx, [Existing(&prefix_len), Existing(&len_len), Existing(&payload_len)]);
+
+        let prefix_value =
+            subarray_value(&[prefix.clone()], Value::known(0), value_to_usize(&prefix_len));
+        let len_value = subarray_value(
+            &len_cells,
+            value_to_usize(&len_len).map(|len_len| len_cells.len() - len_len),
+            value_to_usize(&len_len),
+        );
+        let rlp_value = prefix_value.zip(len_value).zip(payload_value).map(
+            |((prefix_value, len_value), payload_value)| {
+                [prefix_value, len_value, payload_value].concat()
+            },
+        );
+        let max_rlp_len = 1 + len_cells.len() + max_payload_len;
+        let rlp_cells = self.gate().assign_witnesses(
+            ctx,
+            (0..max_rlp_len).map(|idx| {
+                rlp_value.as_ref().map(|value| value.get(idx).copied().unwrap_or_else(F::zero))
+            }),
+        );
+
+        RlpEncodingWitness {
+            prefix,
+            prefix_len,
+            len_len,
+            len_cells,
+            payload_len,
+            payload,
+            rlp_len,
+            rlp_cells,
+        }
+    }
+
+    fn encode_rlp_string_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        first_byte: Option<AssignedValue<'v, F>>,
+        field_len: AssignedValue<'v, F>,
+        payload: RlpPayloadWitness<'v, F>,
+    ) -> RlpEncodingWitness<'v, F> {
+        let (cells, field_start) = match &payload {
+            RlpPayloadWitness::Bytes(cells) => (cells, Value::known(0)),
+            RlpPayloadWitness::Uint(cells) => {
+                (cells, value_to_usize(&field_len).map(|len| cells.len() - len))
+            }
+            RlpPayloadWitness::List { .. } => unreachable!(),
+        };
+        let max_field_len = cells.len();
+        let field_value = subarray_value(cells, field_start, value_to_usize(&field_len));
+
+        let (prefix, len_len, len_cells) =
+            self.encode_rlp_header_phase0(ctx, &field_len, max_field_len, 0x80);
+        // a single byte below 0x80 is its own encoding
+        let is_literal = match first_byte {
+            Some(first_byte) => {
+                let is_single_byte =
+                    self.gate().is_equal(ctx, Existing(&field_len), Constant(F::one()));
+                let is_small =
+                    self.range.is_less_than(ctx, Existing(&first_byte), Constant(F::from(0x80)), 8);
+                self.gate().and(ctx, Existing(&is_single_byte), Existing(&is_small))
+            }
+            None => self.gate().load_zero(ctx),
+        };
+        let prefix = self.gate().mul_not(ctx, Existing(&is_literal), Existing(&prefix));
+        let prefix_len = self.gate().not(ctx, Existing(&is_literal));
+
+        self.assign_rlp_encoding_phase0(
+            ctx,
+            (prefix, prefix_len),
+            (len_len, len_cells),
+            (field_len, payload, max_field_len),
+            field_value,
+        )
+    }
+
+    /// Compute and assign witnesses for the RLP encoding of the byte string `field_cells[..field_len]`.
+    /// Assumes that `field_cells` are bytes.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn encode_rlp_field_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        field_cells: Vec<AssignedValue<'v, F>>,
+        field_len: AssignedValue<'v, F>,
+    ) -> RlpEncodingWitness<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 0);
+        self.range.check_less_than_safe(ctx, &field_len, (field_cells.len() + 1) as u64);
+        let first_byte = field_cells.first().cloned();
+        self.encode_rlp_string_phase0(
+            ctx,
+            first_byte,
+            field_len,
+            RlpPayloadWitness::Bytes(field_cells),
+        )
+    }
+
+    /// Compute and assign witnesses for the RLP encoding of the integer with big endian bytes `bytes`, e.g.
+    /// `0` is encoded as the empty string and a `U256` storage value from its 32 bytes. Assumes that `bytes`
+    /// are bytes, see [`num_to_bytes_be`] to get them from a small integer.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn encode_rlp_uint_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        bytes: Vec<AssignedValue<'v, F>>,
+    ) -> RlpEncodingWitness<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 0);
+        let num_zeros = self.num_leading_zeros(ctx, &bytes);
+        let field_len =
+            self.gate().sub(ctx, Constant(F::from(bytes.len() as u64)), Existing(&num_zeros));
+        // if all bytes are zero, `num_zeros` is out of range and this is `0`
+        let first_byte = (!bytes.is_empty()).then(|| {
+            self.gate().select_from_idx(ctx, bytes.iter().map(Existing), Existing(&num_zeros))
+        });
+        self.encode_rlp_string_phase0(ctx, first_byte, field_len, RlpPayloadWitness::Uint(bytes))
+    }
+
+    /// Compute and assign witnesses for the RLP encoding of the list of `items`.
+    ///
+    /// If `num_items` is `None`, the list has exactly `items.len()` items. Otherwise the list consists of the first
+    /// `num_items` of `items`, and the circuit constrains `num_items <= items.len()`.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn encode_rlp_list_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        items: Vec<RlpEncodingWitness<'v, F>>,
+        num_items: Option<AssignedValue<'v, F>>,
+    ) -> RlpEncodingWitness<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), 0);
+        let max_payload_len: usize = items.iter().map(|item| item.rlp_cells.len()).sum();
+
+        let item_lens: Vec<_> = match &num_items {
+            Some(num_items) => {
+                self.range.check_less_than_safe(ctx, num_items, (items.len() + 1) as u64);
+                let bits = bit_length(items.len() as u64);
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| {
+                        let in_list = self.range.is_less_than(
+                            ctx,
+                            Constant(F::from(idx as u64)),
+                            Existing(num_items),
+                            bits,
+                        );
+                        self.gate().mul(ctx, Existing(&in_list), Existing(&item.rlp_len))
+                    })
+                    .collect()
+            }
+            None => items.iter().map(|item| item.rlp_len.clone()).collect(),
+        };
+        let num_items = match num_items {
+            Some(num_items) => num_items,
+            None => self.gate().load_constant(ctx, F::from(items.len() as u64)),
+        };
+        let payload_len = self.gate().sum(ctx, item_lens.iter().map(Existing));
+        let payload_value = items.iter().zip(item_lens.iter()).fold(
+            Value::known(Vec::with_capacity(max_payload_len)),
+            |acc, (item, item_len)| {
+                let item_value =
+                    subarray_value(&item.rlp_cells, Value::known(0), value_to_usize(item_len));
+                acc.zip(item_value).map(|(mut acc, item_value)| {
+                    acc.extend(item_value);
+                    acc
+                })
+            },
+        );
+
+        let (prefix, len_len, len_cells) =
+            self.encode_rlp_header_phase0(ctx, &payload_len, max_payload_len, 0xc0);
+        let prefix_len = self.gate().load_constant(ctx, F::one());
+
+        self.assign_rlp_encoding_phase0(
+            ctx,
+            (prefix, prefix_len),
+            (len_len, len_cells),
+            (payload_len, RlpPayloadWitness::List { items, num_items }, max_payload_len),
+            payload_value,
+        )
+    }
+
+    /// Use RLC to constrain the RLP encoding witness, including the encodings of its items. This MUST be done in
+    /// `SecondPhase`.
+    ///
+    /// Returns the RLC of the encoding `rlp_cells[..rlp_len]`.
+    pub fn encode_rlp_phase1<'v>(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: RlpEncodingWitness<'v, F>,
+    ) -> RlcTrace<'v, F> {
+        debug_assert_eq!(ctx.current_phase(), RLC_PHASE);
+
+        let RlpEncodingWitness {
+            prefix,
+            prefix_len,
+            len_len,
+            len_cells,
+            payload_len,
+            payload,
+            rlp_len,
+            rlp_cells,
+        } = witness;
+        let max_len_len = len_cells.len();
+
+        self.rlc.load_rlc_cache(ctx, self.range.gate(), bit_length(rlp_cells.len() as u64));
+
+        // leading zeros do not change the RLC, so this is also the RLC of `len_cells` without them
+        let len_rlc = self.rlc.compute_rlc_fixed_len(ctx, self.gate(), len_cells).rlc_val;
+        let rlp_rlc = self.rlc.compute_rlc(ctx, self.gate(), rlp_cells, rlp_len);
+
+        let field = match payload {
+            RlpPayloadWitness::Bytes(cells) => {
+                let max_field_len = cells.len();
+                Some((
+                    self.rlc.compute_rlc(ctx, self.gate(), cells, payload_len.clone()).rlc_val,
+                    max_field_len,
+                ))
+            }
+            RlpPayloadWitness::Uint(cells) => {
+                let max_field_len = cells.len();
+                // the RLC of the integer without its leading zeros
+                Some((
+                    self.rlc.compute_rlc_fixed_len(ctx, self.gate(), cells).rlc_val,
+                    max_field_len,
+                ))
+            }
+            RlpPayloadWitness::List { items, num_items } => {
+                let max_num_frags = 2 + items.len();
+                let item_rlcs: Vec<_> =
+                    items.into_iter().map(|item| self.encode_rlp_phase1(ctx, item)).collect();
+                let num_frags = self.gate().add(ctx, Existing(&num_items), Constant(F::from(2)));
+                self.rlc.constrain_rlc_concat_var(
+                    ctx,
+                    self.gate(),
+                    [(&prefix, &prefix_len, 1), (&len_rlc, &len_len, max_len_len)]
+                        .into_iter()
+                        .chain(
+                            item_rlcs.iter().map(|item| (&item.rlc_val, &item.len, item.max_len)),
+                        ),
+                    (&rlp_rlc.rlc_val, &rlp_rlc.len),
+                    &num_frags,
+                    max_num_frags,
+                    self.rlc.gamma_pow_cached(),
+                );
+                None
+            }
+        };
+        if let Some((field_rlc, max_field_len)) = field {
+            self.rlc.constrain_rlc_concat(
+                ctx,
+                self.gate(),
+                [
+                    (&prefix, &prefix_len, 1),
+                    (&len_rlc, &len_len, max_len_len),
+                    (&field_rlc, &payload_len, max_field_len),
+                ],
+                (&rlp_rlc.rlc_val, &rlp_rlc.len),
+            );
+        }
+        rlp_rlc
+    }
+}
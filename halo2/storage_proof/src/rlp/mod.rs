@@ -14,7 +14,9 @@ use halo2_base::{
 };
 use std::iter;
 
+pub mod encode;
 pub mod rlc;
+pub mod schema;
 #[cfg(test)]
 mod tests;
 
@@ -159,6 +161,17 @@ pub struct RlpArrayTrace<'v, F: ScalarField> {
     // pub array_trace: RlcTrace<'v, F>,
 }
 
+/// The kind of an item of an RLP list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RlpItemKind {
+    /// A byte string.
+    String,
+    /// A byte string, or a list shorter than 32 bytes, see [`RlpChip::parse_rlp_field_or_embedded_list_prefix`].
+    StringOrEmbeddedList,
+    /// A list, parsed as a field whose value is its whole RLP encoding.
+    List,
+}
+
 #[derive(Clone, Debug)]
 pub struct RlpConfig<F: ScalarField> {
     pub rlc: RlcConfig<F>,
@@ -197,12 +210,17 @@ impl<F: ScalarField> RlpConfig<F> {
 pub struct RlpChip<'g, F: ScalarField> {
     pub rlc: RlcChip<'g, F>,
     pub range: RangeConfig<F>,
+    /// Whether to constrain the parsed fields and arrays to be canonically encoded, as the `rlp` crate requires when
+    /// decoding. Otherwise the same bytes may be parsed as different fields by the circuit and by native code.
+    /// Off by default, since it adds cells to every parsed field. Enable it for a single parse with
+    /// [`RlpChip::with_strict`].
+    pub strict: bool,
 }
 
 impl<'g, F: ScalarField> RlpChip<'g, F> {
     pub fn new(config: RlpConfig<F>, gamma: Value<F>) -> RlpChip<'g, F> {
         let rlc = RlcChip::new(config.rlc, gamma);
-        Self { rlc, range: config.range }
+        Self { rlc, range: config.range, strict: false }
     }
 
     pub fn gate(&self) -> &FlexGateConfig<F> {
@@ -213,6 +231,15 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         &self.rlc
     }
 
+    /// Runs `f` with [`RlpChip::strict`] set. Use it to parse RLP that native code decodes as well, such as block
+    /// headers and MPT leaves and values, so both read the same fields.
+    pub fn with_strict<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let strict = std::mem::replace(&mut self.strict, true);
+        let out = f(self);
+        self.strict = strict;
+        out
+    }
+
     pub fn get_challenge(&mut self, ctx: &mut Context<F>) {
         self.rlc.get_challenge(ctx);
     }
@@ -263,6 +290,55 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         RlpFieldPrefixParsed { is_not_literal, is_big, next_len, len_len }
     }
 
+    /// Parses the prefix of a field that is either a string or a list shorter than 32 bytes, such as a node
+    /// reference in an MPT node, where nodes shorter than 32 bytes are embedded instead of hashed.
+    ///
+    /// An embedded list is parsed as a literal whose length includes the prefix, so the field is the whole
+    /// RLP encoding of the list.
+    pub fn parse_rlp_field_or_embedded_list_prefix<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        prefix: &AssignedValue<'v, F>,
+    ) -> RlpFieldPrefixParsed<'v, F> {
+        let is_list = self.range.is_less_than(
+            ctx,
+            Constant(self.gate().get_field_element(191)),
+            Existing(prefix),
+            8,
+        );
+        // is valid: the whole list is shorter than 32 bytes
+        self.range.check_less_than(
+            ctx,
+            Existing(prefix),
+            Constant(self.gate().get_field_element(192 + 31)),
+            8,
+        );
+        // parse lists as the empty string, then override
+        let string_prefix = self.gate().select(
+            ctx,
+            Constant(self.gate().get_field_element(128)),
+            Existing(prefix),
+            Existing(&is_list),
+        );
+        let parsed = self.parse_rlp_field_prefix(ctx, &string_prefix);
+        let is_not_literal = self.gate().select(
+            ctx,
+            Constant(F::zero()),
+            Existing(&parsed.is_not_literal),
+            Existing(&is_list),
+        );
+        let list_len =
+            self.gate().sub(ctx, Existing(prefix), Constant(self.gate().get_field_element(191)));
+        let next_len = self.gate().select(
+            ctx,
+            Existing(&list_len),
+            Existing(&parsed.next_len),
+            Existing(&is_list),
+        );
+        // the empty string has `is_big = len_len = 0`
+        RlpFieldPrefixParsed { is_not_literal, next_len, ..parsed }
+    }
+
     pub fn parse_rlp_array_prefix<'v>(
         &self,
         ctx: &mut Context<'v, F>,
@@ -295,6 +371,63 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         RlpArrayPrefixParsed { /*is_empty,*/ is_big, next_len, len_len }
     }
 
+    /// Constrains a field with prefix `prefix`, `0` if the field is a literal, to be canonically encoded: a single
+    /// byte below `0x80` is its own encoding, and the length of a long field is canonical, see
+    /// [`RlpChip::constrain_canonical_len`].
+    #[allow(clippy::too_many_arguments)]
+    fn constrain_canonical_field<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        prefix: &AssignedValue<'v, F>,
+        is_big: &AssignedValue<'v, F>,
+        len_cells: &[AssignedValue<'v, F>],
+        field_len: &AssignedValue<'v, F>,
+        field_cells: &[AssignedValue<'v, F>],
+        max_field_len: usize,
+    ) {
+        if let Some(first_byte) = field_cells.first() {
+            let is_single_byte = self.gate().is_equal(
+                ctx,
+                Existing(prefix),
+                Constant(self.gate().get_field_element(0x81)),
+            );
+            let is_literal = self.range.is_less_than(
+                ctx,
+                Existing(first_byte),
+                Constant(self.gate().get_field_element(0x80)),
+                8,
+            );
+            let is_invalid = self.gate().and(ctx, Existing(&is_single_byte), Existing(&is_literal));
+            self.gate().assert_is_const(ctx, &is_invalid, F::zero());
+        }
+        self.constrain_canonical_len(ctx, is_big, len_cells, field_len, max_field_len);
+    }
+
+    /// If `is_big = 1`, constrains the length `len` with big endian bytes `len_cells` of a field or array to be
+    /// canonically encoded: without leading zeros, and at least 56, since shorter lengths fit in the prefix.
+    fn constrain_canonical_len<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        is_big: &AssignedValue<'v, F>,
+        len_cells: &[AssignedValue<'v, F>],
+        len: &AssignedValue<'v, F>,
+        max_len: usize,
+    ) {
+        // lengths up to `max_len <= 55` always fit in the prefix, so there are no length bytes
+        if let Some(first_len_byte) = len_cells.first() {
+            let has_leading_zero = self.gate().is_zero(ctx, first_len_byte);
+            let is_short = self.range.is_less_than(
+                ctx,
+                Existing(len),
+                Constant(self.gate().get_field_element(56)),
+                bit_length(max_len as u64),
+            );
+            let is_invalid = self.gate().or(ctx, Existing(&has_leading_zero), Existing(&is_short));
+            let is_invalid = self.gate().and(ctx, Existing(is_big), Existing(&is_invalid));
+            self.gate().assert_is_const(ctx, &is_invalid, F::zero());
+        }
+    }
+
     fn parse_rlp_len<'v>(
         &self,
         ctx: &mut Context<'v, F>,
@@ -372,6 +505,17 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
             field_len.value(),
             max_field_len,
         );
+        if self.strict {
+            self.constrain_canonical_field(
+                ctx,
+                &prefix,
+                &prefix_parsed.is_big,
+                &len_cells,
+                &field_len,
+                &field_cells,
+                max_field_len,
+            );
+        }
 
         let rlp_len = self.gate().sum(
             ctx,
@@ -451,6 +595,56 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         max_field_lens: &[usize],
         is_variable_len: bool,
     ) -> RlpArrayTraceWitness<'v, F> {
+        let embedded_list_fields = vec![false; max_field_lens.len()];
+        self.decompose_rlp_array_with_embedded_lists_phase0(
+            ctx,
+            rlp_array,
+            max_field_lens,
+            &embedded_list_fields,
+            is_variable_len,
+        )
+    }
+
+    /// Same as [`RlpChip::decompose_rlp_array_phase0`], but the fields where `embedded_list_fields[idx]` is true may
+    /// also be lists shorter than 32 bytes, see [`RlpChip::parse_rlp_field_or_embedded_list_prefix`].
+    pub fn decompose_rlp_array_with_embedded_lists_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        rlp_array: Vec<AssignedValue<'v, F>>,
+        max_field_lens: &[usize],
+        embedded_list_fields: &[bool],
+        is_variable_len: bool,
+    ) -> RlpArrayTraceWitness<'v, F> {
+        let item_kinds: Vec<_> = embedded_list_fields
+            .iter()
+            .map(|&is_embedded_list| {
+                if is_embedded_list {
+                    RlpItemKind::StringOrEmbeddedList
+                } else {
+                    RlpItemKind::String
+                }
+            })
+            .collect();
+        self.decompose_rlp_items_phase0(
+            ctx,
+            rlp_array,
+            max_field_lens,
+            &item_kinds,
+            is_variable_len,
+        )
+    }
+
+    /// Same as [`RlpChip::decompose_rlp_array_phase0`], with the kind of each item given by `item_kinds`. The field
+    /// of an item of kind [`RlpItemKind::List`] is its whole RLP encoding, of at most `max_field_lens[idx]` bytes.
+    fn decompose_rlp_items_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        rlp_array: Vec<AssignedValue<'v, F>>,
+        max_field_lens: &[usize],
+        item_kinds: &[RlpItemKind],
+        is_variable_len: bool,
+    ) -> RlpArrayTraceWitness<'v, F> {
+        assert_eq!(max_field_lens.len(), item_kinds.len());
         let max_rlp_array_len = rlp_array.len();
         let max_len_len = max_rlp_len_len(max_rlp_array_len);
 
@@ -494,6 +688,15 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
             &list_payload_len,
             (max_rlp_array_len - max_len_len) as u64,
         );
+        if self.strict {
+            self.constrain_canonical_len(
+                ctx,
+                &prefix_parsed.is_big,
+                &len_cells,
+                &list_payload_len,
+                max_rlp_array_len,
+            );
+        }
 
         // this is automatically <= max_rlp_array_len
         let rlp_len = self
@@ -505,14 +708,41 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         let mut prefix_idx = self.gate().add(ctx, Constant(F::one()), Existing(&len_len));
         let mut running_max_len = max_len_len + 1;
 
-        for &max_field_len in max_field_lens {
+        for (&max_field_len, &item_kind) in max_field_lens.iter().zip(item_kinds) {
             let mut prefix = self.gate().select_from_idx(
                 ctx,
                 // selecting from the whole array is wasteful: we only select from the max range currently possible
                 rlp_array.iter().map(Existing).take(running_max_len + 1),
                 Existing(&prefix_idx),
             );
-            let prefix_parsed = self.parse_rlp_field_prefix(ctx, &prefix);
+            if item_kind == RlpItemKind::List {
+                let field_in_list = is_variable_len.then(|| {
+                    self.range.is_less_than(
+                        ctx,
+                        Existing(&prefix_idx),
+                        Existing(&rlp_len),
+                        bit_length(max_rlp_array_len as u64),
+                    )
+                });
+                let witness = self.parse_rlp_list_item_phase0(
+                    ctx,
+                    &rlp_array,
+                    &prefix,
+                    &prefix_idx,
+                    max_field_len,
+                    field_in_list.as_ref(),
+                );
+                running_max_len += max_field_len;
+                prefix_idx =
+                    self.gate().add(ctx, Existing(&prefix_idx), Existing(&witness.field_len));
+                field_witness.push(witness);
+                continue;
+            }
+            let prefix_parsed = if item_kind == RlpItemKind::StringOrEmbeddedList {
+                self.parse_rlp_field_or_embedded_list_prefix(ctx, &prefix)
+            } else {
+                self.parse_rlp_field_prefix(ctx, &prefix)
+            };
 
             let mut len_len = prefix_parsed.len_len;
             let max_field_len_len = max_rlp_len_len(max_field_len);
@@ -566,6 +796,20 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
                 field_len = self.gate().mul(ctx, Existing(&field_len), Existing(&field_in_list));
             }
             prefix = self.gate().mul(ctx, Existing(&prefix), Existing(&prefix_len));
+            if self.strict {
+                // `is_big` implies `prefix_len = 1`, unless the field is past the end of the list
+                let is_big =
+                    self.gate().mul(ctx, Existing(&prefix_parsed.is_big), Existing(&prefix_len));
+                self.constrain_canonical_field(
+                    ctx,
+                    &prefix,
+                    &is_big,
+                    &len_cells,
+                    &field_len,
+                    &field_cells,
+                    max_field_len,
+                );
+            }
             prefix_idx = self.gate().sum(
                 ctx,
                 vec![
@@ -591,6 +835,83 @@ impl<'g, F: ScalarField> RlpChip<'g, F> {
         RlpArrayTraceWitness { field_witness, len_len, len_cells, rlp_len, rlp_array }
     }
 
+    /// Parses the item with prefix `prefix` at `prefix_idx` of `rlp_array` that is a list of at most `max_item_len`
+    /// bytes, as a field whose value is the whole RLP encoding of the list. The field is empty if the item is past
+    /// the end of a variable length list, i.e. `field_in_list = Some(0)`.
+    ///
+    /// The length bytes of the list are not constrained here: the list must also be decoded from the field, see
+    /// [`RlpChip::decompose_rlp_list_phase0`].
+    fn parse_rlp_list_item_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        rlp_array: &[AssignedValue<'v, F>],
+        prefix: &AssignedValue<'v, F>,
+        prefix_idx: &AssignedValue<'v, F>,
+        max_item_len: usize,
+        field_in_list: Option<&AssignedValue<'v, F>>,
+    ) -> RlpFieldWitness<'v, F> {
+        // past the end of the list, parse the empty list instead
+        let prefix = match field_in_list {
+            Some(field_in_list) => self.gate().select(
+                ctx,
+                Existing(prefix),
+                Constant(self.gate().get_field_element(192)),
+                Existing(field_in_list),
+            ),
+            None => prefix.clone(),
+        };
+        let prefix_parsed = self.parse_rlp_array_prefix(ctx, &prefix);
+        let max_len_len = max_rlp_len_len(max_item_len);
+        self.range.check_less_than_safe(ctx, &prefix_parsed.len_len, (max_len_len + 1) as u64);
+
+        let len_start_id = prefix_idx.value().copied() + Value::known(F::one());
+        let len_cells = witness_subarray(
+            ctx,
+            self.gate(),
+            rlp_array,
+            len_start_id.as_ref(),
+            prefix_parsed.len_len.value(),
+            max_len_len,
+        );
+        let len_byte_val =
+            evaluate_byte_array(ctx, self.gate(), &len_cells, &prefix_parsed.len_len);
+        let payload_len = self.gate().select(
+            ctx,
+            Existing(&len_byte_val),
+            Existing(&prefix_parsed.next_len),
+            Existing(&prefix_parsed.is_big),
+        );
+        let mut field_len = self.gate().sum(
+            ctx,
+            [Constant(F::one()), Existing(&prefix_parsed.len_len), Existing(&payload_len)],
+        );
+        if let Some(field_in_list) = field_in_list {
+            field_len = self.gate().mul(ctx, Existing(&field_len), Existing(field_in_list));
+        }
+        self.range.check_less_than_safe(ctx, &field_len, (max_item_len + 1) as u64);
+
+        let field_cells = witness_subarray(
+            ctx,
+            self.gate(),
+            rlp_array,
+            prefix_idx.value(),
+            field_len.value(),
+            max_item_len,
+        );
+        // the prefix and length are part of the field
+        let zero = self.gate().load_zero(ctx);
+        RlpFieldWitness {
+            prefix: zero.clone(),
+            prefix_len: zero.clone(),
+            len_len: zero,
+            len_cells: vec![],
+            max_len_len: 0,
+            field_len,
+            field_cells,
+            max_field_len: max_item_len,
+        }
+    }
+
     /// Use RLC to constrain the parsed RLP array witness. This MUST be done in `SecondPhase`.
     ///
     /// We do not make any guarantees on the values in the original RLP sequence beyond the parsed length for the total payload
@@ -0,0 +1,172 @@
+//! Decoding of nested RLP lists, such as receipt logs or access lists, given the shape of the list.
+use super::{max_rlp_len_len, RlpArrayTrace, RlpArrayTraceWitness, RlpChip, RlpItemKind};
+use halo2_base::{
+    gates::GateInstructions,
+    utils::ScalarField,
+    AssignedValue, Context,
+    QuantumCell::{Constant, Existing},
+};
+use rlp::RlpStream;
+
+/// The shape of an RLP item, with the max length of every byte string in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RlpSchema {
+    /// A byte string of at most the given number of bytes.
+    Field(usize),
+    /// A list of `items`. If `is_variable_len`, the list may end before the last items, see
+    /// [`RlpChip::decompose_rlp_array_phase0`].
+    List { items: Vec<RlpSchema>, is_variable_len: bool },
+}
+
+impl RlpSchema {
+    /// A list of exactly `items`.
+    pub fn list(items: Vec<RlpSchema>) -> Self {
+        Self::List { items, is_variable_len: false }
+    }
+
+    /// A list of at most `max_items` items of the same shape, e.g. the topics of a log.
+    pub fn var_list(item: RlpSchema, max_items: usize) -> Self {
+        Self::List { items: vec![item; max_items], is_variable_len: true }
+    }
+
+    /// The max length of the RLP encoding of an item of this shape.
+    pub fn max_rlp_len(&self) -> usize {
+        match self {
+            Self::Field(max_len) => 1 + max_rlp_len_len(*max_len) + max_len,
+            Self::List { items, .. } => {
+                let payload_len: usize = items.iter().map(Self::max_rlp_len).sum();
+                // `decompose_rlp_array_phase0` allows as many length bytes as the total length needs
+                let mut len_len = max_rlp_len_len(payload_len);
+                while max_rlp_len_len(1 + len_len + payload_len) > len_len {
+                    len_len += 1;
+                }
+                1 + len_len + payload_len
+            }
+        }
+    }
+
+    /// The RLP encoding of an item of this shape with empty strings and variable length lists, decoded in place of
+    /// the items past the end of a variable length list.
+    fn dummy_rlp(&self) -> Vec<u8> {
+        match self {
+            Self::Field(_) => vec![0x80],
+            Self::List { is_variable_len: true, .. } => vec![0xc0],
+            Self::List { items, .. } => {
+                let mut stream = RlpStream::new_list(items.len());
+                for item in items {
+                    stream.append_raw(&item.dummy_rlp(), 1);
+                }
+                stream.out().to_vec()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RlpListWitness<'v, F: ScalarField> {
+    pub array: RlpArrayTraceWitness<'v, F>,
+    /// The decoded items that are lists, `None` for byte strings.
+    pub lists: Vec<Option<RlpListWitness<'v, F>>>,
+    is_variable_len: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RlpListTrace<'v, F: ScalarField> {
+    /// The items as fields of the list. The field of an item that is a list is its whole RLP encoding.
+    pub array: RlpArrayTrace<'v, F>,
+    /// The decoded items that are lists, `None` for byte strings.
+    pub lists: Vec<Option<RlpListTrace<'v, F>>>,
+}
+
+impl<'g, F: ScalarField> RlpChip<'g, F> {
+    /// Compute and assign witnesses for deserializing an RLP list of shape `schema`, including its nested lists.
+    /// `rlp_list` must have length `schema.max_rlp_len()`.
+    ///
+    /// Witnesses MUST be generated in `FirstPhase` to be able to compute RLC of them in `SecondPhase`
+    pub fn decompose_rlp_list_phase0<'v>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        rlp_list: Vec<AssignedValue<'v, F>>,
+        schema: &RlpSchema,
+    ) -> RlpListWitness<'v, F> {
+        let (items, is_variable_len) = match schema {
+            RlpSchema::List { items, is_variable_len } => (items, *is_variable_len),
+            RlpSchema::Field(_) => {
+                panic!("schema must be a list, decode fields with `decompose_rlp_field_phase0`")
+            }
+        };
+        debug_assert_eq!(rlp_list.len(), schema.max_rlp_len());
+
+        let (max_field_lens, item_kinds): (Vec<_>, Vec<_>) = items
+            .iter()
+            .map(|item| match item {
+                RlpSchema::Field(max_len) => (*max_len, RlpItemKind::String),
+                RlpSchema::List { .. } => (item.max_rlp_len(), RlpItemKind::List),
+            })
+            .unzip();
+        let array = self.decompose_rlp_items_phase0(
+            ctx,
+            rlp_list,
+            &max_field_lens,
+            &item_kinds,
+            is_variable_len,
+        );
+
+        let mut lists = Vec::with_capacity(items.len());
+        for (item, field) in items.iter().zip(array.field_witness.iter()) {
+            if let RlpSchema::Field(_) = item {
+                lists.push(None);
+                continue;
+            }
+            let mut item_rlp = field.field_cells.clone();
+            let mut in_list = None;
+            if is_variable_len {
+                // an item in the list is at least its prefix, so it is past the end of the list iff it is empty
+                let is_empty = self.gate().is_zero(ctx, &field.field_len);
+                let is_in_list = self.gate().not(ctx, Existing(&is_empty));
+                let mut dummy = item.dummy_rlp();
+                dummy.resize(item_rlp.len(), 0);
+                item_rlp = item_rlp
+                    .iter()
+                    .zip(dummy)
+                    .map(|(byte, dummy_byte)| {
+                        self.gate().select(
+                            ctx,
+                            Existing(byte),
+                            Constant(F::from(dummy_byte as u64)),
+                            Existing(&is_in_list),
+                        )
+                    })
+                    .collect();
+                in_list = Some(is_in_list);
+            }
+            let list = self.decompose_rlp_list_phase0(ctx, item_rlp, item);
+            // the field is exactly the nested list
+            let list_len = match &in_list {
+                Some(in_list) => {
+                    self.gate().mul(ctx, Existing(&list.array.rlp_len), Existing(in_list))
+                }
+                None => list.array.rlp_len.clone(),
+            };
+            ctx.constrain_equal(&list_len, &field.field_len);
+            lists.push(Some(list));
+        }
+        RlpListWitness { array, lists, is_variable_len }
+    }
+
+    /// Use RLC to constrain the decoded RLP list witness, including its nested lists. This MUST be done in
+    /// `SecondPhase`.
+    pub fn decompose_rlp_list_phase1<'v>(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: RlpListWitness<'v, F>,
+    ) -> RlpListTrace<'v, F> {
+        let RlpListWitness { array, lists, is_variable_len } = witness;
+        let array = self.decompose_rlp_array_phase1(ctx, array, is_variable_len);
+        let mut list_traces = Vec::with_capacity(lists.len());
+        for list in lists {
+            list_traces.push(list.map(|list| self.decompose_rlp_list_phase1(ctx, list)));
+        }
+        RlpListTrace { array, lists: list_traces }
+    }
+}
@@ -241,7 +241,7 @@ mod rlc {
 }
 
 mod rlp {
-    use crate::rlp::*;
+    use crate::rlp::{schema::RlpSchema, *};
     use halo2_base::{
         halo2_proofs::{
             circuit::{Layouter, SimpleFloorPlanner},
@@ -252,7 +252,9 @@ mod rlp {
         ContextParams, SKIP_FIRST_PASS,
     };
     use hex::FromHex;
-    use std::marker::PhantomData;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use std::{collections::HashSet, marker::PhantomData};
 
     const DEGREE: u32 = 18;
 
@@ -263,6 +265,7 @@ mod rlp {
         max_field_lens: Vec<usize>,
         is_array: bool,
         is_variable_len: bool,
+        strict: bool,
         _marker: PhantomData<F>,
     }
 
@@ -290,6 +293,7 @@ mod rlp {
 
             let gamma = config.rlc.gamma;
             let mut chip = RlpChip::new(config, layouter.get_challenge(gamma));
+            chip.strict = self.strict;
 
             let mut first_pass = SKIP_FIRST_PASS;
             layouter.assign_region(
@@ -373,6 +377,7 @@ mod rlp {
                 max_field_lens: vec![15, 9, 11, 10, 17],
                 is_array: true,
                 is_variable_len: true,
+                strict: false,
                 _marker: PhantomData,
             };
             MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
@@ -392,6 +397,7 @@ mod rlp {
             max_field_lens: vec![],
             is_array: false,
             is_variable_len: false,
+            strict: false,
             _marker: PhantomData,
         };
         MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
@@ -409,6 +415,7 @@ mod rlp {
             max_field_lens: vec![],
             is_array: false,
             is_variable_len: false,
+            strict: false,
             _marker: PhantomData,
         };
         MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
@@ -425,6 +432,7 @@ mod rlp {
             max_field_lens: vec![],
             is_array: false,
             is_variable_len: false,
+            strict: false,
             _marker: PhantomData,
         };
         MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
@@ -441,8 +449,588 @@ mod rlp {
             max_field_lens: vec![],
             is_array: false,
             is_variable_len: false,
+            strict: false,
             _marker: PhantomData,
         };
         MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
     }
+
+    fn field_circuit(mut input: Vec<u8>, max_len: usize, strict: bool) -> RlpTestCircuit<Fr> {
+        input.resize(1 + max_rlp_len_len(max_len) + max_len, 0);
+        RlpTestCircuit {
+            inputs: input,
+            max_len,
+            max_field_lens: vec![],
+            is_array: false,
+            is_variable_len: false,
+            strict,
+            _marker: PhantomData,
+        }
+    }
+
+    fn array_circuit(
+        mut input: Vec<u8>,
+        max_len: usize,
+        max_field_lens: Vec<usize>,
+        strict: bool,
+    ) -> RlpTestCircuit<Fr> {
+        input.resize(max_len, 0);
+        RlpTestCircuit {
+            inputs: input,
+            max_len,
+            max_field_lens,
+            is_array: true,
+            is_variable_len: false,
+            strict,
+            _marker: PhantomData,
+        }
+    }
+
+    fn is_satisfied(circuit: &RlpTestCircuit<Fr>) -> bool {
+        MockProver::run(DEGREE, circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    pub fn test_mock_rlp_non_canonical() {
+        let long_payload = vec![0x11; 64];
+        let fields = [
+            // a single byte below 0x80 with a length prefix
+            vec![0x81, 0x2a],
+            // a short string with a long form length
+            [&[0xb8, 0x03][..], b"cat"].concat(),
+            // a long form length with a leading zero
+            [&[0xb9, 0x00, 0x40][..], &long_payload].concat(),
+        ];
+        for field in fields {
+            assert!(is_satisfied(&field_circuit(field.clone(), 300, false)));
+            assert!(!is_satisfied(&field_circuit(field, 300, true)));
+        }
+        // the canonical encodings of the same strings
+        for field in
+            [vec![0x2a], [&[0x83][..], b"cat"].concat(), ::rlp::encode(&long_payload).to_vec()]
+        {
+            assert!(is_satisfied(&field_circuit(field, 300, true)));
+        }
+
+        // [ "cat", "dog" ] with a long form length
+        let cat_dog = vec![0xf8, 0x08, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g'];
+        assert!(is_satisfied(&array_circuit(cat_dog.clone(), 69, vec![3, 3], false)));
+        assert!(!is_satisfied(&array_circuit(cat_dog.clone(), 69, vec![3, 3], true)));
+        let canonical = [&[0xc8][..], &cat_dog[2..]].concat();
+        assert!(is_satisfied(&array_circuit(canonical, 69, vec![3, 3], true)));
+        assert!(is_satisfied(&array_circuit(
+            ::rlp::encode_list::<Vec<u8>, _>(&[long_payload.clone(), vec![0x2a]]).to_vec(),
+            69,
+            vec![64, 1],
+            true
+        )));
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum FuzzEncoding {
+        Canonical,
+        /// a length prefix even if the payload is a single byte below 0x80
+        ShortPrefix,
+        /// a long form length even if the payload is short
+        LongFormLen,
+        /// a long form length with a leading zero
+        LeadingZeroLen,
+    }
+
+    /// A random string of at most `max_len` bytes, encoded canonically or in one of the non-canonical ways that
+    /// still parse with at most `max_len_len` length bytes.
+    fn fuzz_rlp_string(
+        rng: &mut impl Rng,
+        max_len: usize,
+        max_len_len: usize,
+    ) -> (Vec<u8>, FuzzEncoding) {
+        let len = if rng.gen_bool(0.25) { 1 } else { rng.gen_range(0..=max_len) };
+        let payload: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        match rng.gen_range(0..4) {
+            1 if len <= 55 => {
+                ([&[0x80 + len as u8][..], &payload].concat(), FuzzEncoding::ShortPrefix)
+            }
+            2 if len < 256 && max_len_len >= 1 => {
+                ([&[0xb8, len as u8][..], &payload].concat(), FuzzEncoding::LongFormLen)
+            }
+            3 if len < 256 && max_len_len >= 2 => {
+                ([&[0xb9, 0, len as u8][..], &payload].concat(), FuzzEncoding::LeadingZeroLen)
+            }
+            _ => (::rlp::encode(&payload).to_vec(), FuzzEncoding::Canonical),
+        }
+    }
+
+    /// Checks that in strict mode, the circuit accepts exactly the fields the `rlp` crate decodes.
+    #[test]
+    pub fn test_mock_rlp_field_strict_differential() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0x2e1f);
+        let max_len = 300;
+        let (mut encodings, mut outcomes) = (HashSet::new(), HashSet::new());
+        for _ in 0..32 {
+            let (field, encoding) = fuzz_rlp_string(&mut rng, max_len, max_rlp_len_len(max_len));
+            let circuit = field_circuit(field.clone(), max_len, true);
+            let rlp = ::rlp::Rlp::new(&circuit.inputs);
+            let is_canonical = rlp.payload_info().is_ok()
+                && rlp.as_val::<Vec<u8>>().map_or(false, |data| data.len() <= max_len);
+            assert_eq!(is_satisfied(&circuit), is_canonical, "field 0x{}", hex::encode(&field));
+            encodings.insert(encoding);
+            outcomes.insert(is_canonical);
+        }
+        // the fuzzer must have produced every encoding, and fields that are both accepted and rejected
+        assert_eq!(encodings.len(), 4, "only produced {encodings:?}");
+        assert_eq!(outcomes.len(), 2);
+    }
+
+    /// Checks that in strict mode, the circuit accepts exactly the lists of two strings the `rlp` crate decodes.
+    #[test]
+    pub fn test_mock_rlp_array_strict_differential() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0x2e20);
+        let (max_len, max_field_lens) = (130, vec![60, 60]);
+        let (mut encodings, mut headers, mut outcomes) =
+            (HashSet::new(), HashSet::new(), HashSet::new());
+        for _ in 0..16 {
+            let mut payload = vec![];
+            for &max_field_len in &max_field_lens {
+                let (field, encoding) =
+                    fuzz_rlp_string(&mut rng, max_field_len, max_rlp_len_len(max_field_len));
+                payload.extend(field);
+                encodings.insert(encoding);
+            }
+            // a long form length even if the payload is short, half of the time
+            let is_long_form = payload.len() > 55 || rng.gen_bool(0.5);
+            let header = if is_long_form {
+                vec![0xf8, payload.len() as u8]
+            } else {
+                vec![0xc0 + payload.len() as u8]
+            };
+            headers.insert(is_long_form);
+            let array = [header, payload].concat();
+            let circuit = array_circuit(array.clone(), max_len, max_field_lens.clone(), true);
+            // the unpadded array: the `rlp` crate counts the zero padding as list items
+            let rlp = ::rlp::Rlp::new(&array);
+            let is_canonical = rlp.payload_info().is_ok()
+                && rlp.item_count() == Ok(2)
+                && (0..2).all(|idx| {
+                    rlp.at(idx).map_or(false, |item| {
+                        item.payload_info().is_ok()
+                            && item
+                                .as_val::<Vec<u8>>()
+                                .map_or(false, |data| data.len() <= max_field_lens[idx])
+                    })
+                });
+            assert_eq!(is_satisfied(&circuit), is_canonical, "array 0x{}", hex::encode(&array));
+            outcomes.insert(is_canonical);
+        }
+        // fields of at most 60 bytes have one length byte, so there is no room for a leading zero
+        assert_eq!(encodings.len(), 3, "only produced {encodings:?}");
+        assert!(!encodings.contains(&FuzzEncoding::LeadingZeroLen));
+        assert_eq!(headers.len(), 2);
+        assert_eq!(outcomes.len(), 2);
+    }
+
+    /// Decodes a list of shape `schema`, and checks that the byte strings in it have lengths `field_lens` if given,
+    /// in depth-first order with `0` for the items past the end of variable length lists.
+    #[derive(Clone, Debug)]
+    pub struct RlpSchemaTestCircuit<F> {
+        inputs: Vec<u8>,
+        schema: RlpSchema,
+        field_lens: Option<Vec<usize>>,
+        _marker: PhantomData<F>,
+    }
+
+    fn trace_field_lens<'v, F: ScalarField>(
+        trace: &schema::RlpListTrace<'v, F>,
+        lens: &mut Vec<AssignedValue<'v, F>>,
+    ) {
+        for (field, list) in trace.array.field_trace.iter().zip(trace.lists.iter()) {
+            match list {
+                Some(list) => trace_field_lens(list, lens),
+                None => lens.push(field.field_trace.len.clone()),
+            }
+        }
+    }
+
+    impl<F: ScalarField> Circuit<F> for RlpSchemaTestCircuit<F> {
+        type Config = RlpConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            RlpConfig::configure(meta, 1, &[1, 1], &[1], 1, 8, 0, DEGREE as usize)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config
+                .range
+                .load_lookup_table(&mut layouter)
+                .expect("load lookup table should not fail");
+
+            let gamma = config.rlc.gamma;
+            let mut chip = RlpChip::new(config, layouter.get_challenge(gamma));
+
+            let mut first_pass = SKIP_FIRST_PASS;
+            layouter.assign_region(
+                || "RLP schema test",
+                |region| {
+                    if first_pass {
+                        first_pass = false;
+                        return Ok(());
+                    }
+                    let mut aux = Context::new(
+                        region,
+                        ContextParams {
+                            max_rows: chip.gate().max_rows,
+                            num_context_ids: 2,
+                            fixed_columns: chip.gate().constants.clone(),
+                        },
+                    );
+                    let ctx = &mut aux;
+
+                    let mut inputs = self.inputs.clone();
+                    inputs.resize(self.schema.max_rlp_len(), 0);
+                    let inputs_assigned = chip.gate().assign_witnesses(
+                        ctx,
+                        inputs.iter().map(|x| Value::known(F::from(*x as u64))),
+                    );
+
+                    let witness =
+                        chip.decompose_rlp_list_phase0(ctx, inputs_assigned, &self.schema);
+                    chip.range.finalize(ctx);
+                    ctx.next_phase();
+
+                    chip.get_challenge(ctx);
+                    let trace = chip.decompose_rlp_list_phase1(ctx, witness);
+
+                    if let Some(field_lens) = &self.field_lens {
+                        let mut lens = vec![];
+                        trace_field_lens(&trace, &mut lens);
+                        assert_eq!(lens.len(), field_lens.len());
+                        for (len, &expected) in lens.iter().zip(field_lens.iter()) {
+                            len.value().map(|len| assert_eq!(*len, F::from(expected as u64)));
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    /// The lengths of the byte strings of shape `schema` in `rlp`, in depth-first order with `0` for the items
+    /// past the end of variable length lists.
+    fn schema_field_lens(rlp: Option<::rlp::Rlp>, schema: &RlpSchema) -> Vec<usize> {
+        match schema {
+            RlpSchema::Field(_) => vec![rlp.map_or(0, |rlp| rlp.data().unwrap().len())],
+            RlpSchema::List { items, .. } => items
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, item)| {
+                    schema_field_lens(rlp.as_ref().and_then(|rlp| rlp.at(idx).ok()), item)
+                })
+                .collect(),
+        }
+    }
+
+    /// A receipt `[status, cumulative_gas, logs_bloom, logs]` with logs `[address, topics, data]`.
+    fn receipt_schema() -> RlpSchema {
+        let log = RlpSchema::list(vec![
+            RlpSchema::Field(20),
+            RlpSchema::var_list(RlpSchema::Field(32), 4),
+            RlpSchema::Field(64),
+        ]);
+        RlpSchema::list(vec![
+            RlpSchema::Field(1),
+            RlpSchema::Field(8),
+            RlpSchema::Field(256),
+            RlpSchema::var_list(log, 2),
+        ])
+    }
+
+    fn receipt_rlp(logs: &[(u8, usize, usize)]) -> Vec<u8> {
+        let mut stream = ::rlp::RlpStream::new_list(4);
+        stream.append(&vec![1u8]);
+        stream.append(&vec![0x01u8, 0x23, 0x45]);
+        stream.append(&vec![0u8; 256]);
+        stream.begin_list(logs.len());
+        for &(address, num_topics, data_len) in logs {
+            stream.begin_list(3);
+            stream.append(&vec![address; 20]);
+            stream.begin_list(num_topics);
+            for topic in 0..num_topics {
+                stream.append(&vec![topic as u8 + 1; 32]);
+            }
+            stream.append(&vec![0xdd; data_len]);
+        }
+        stream.out().to_vec()
+    }
+
+    fn schema_circuit(inputs: Vec<u8>, schema: RlpSchema) -> RlpSchemaTestCircuit<Fr> {
+        let field_lens = schema_field_lens(Some(::rlp::Rlp::new(&inputs)), &schema);
+        RlpSchemaTestCircuit { inputs, schema, field_lens: Some(field_lens), _marker: PhantomData }
+    }
+
+    #[test]
+    pub fn test_mock_rlp_schema() {
+        let schema = receipt_schema();
+        for logs in [vec![], vec![(0xaa, 2, 0)], vec![(0xaa, 4, 64), (0xbb, 0, 3)]] {
+            let circuit = schema_circuit(receipt_rlp(&logs), schema.clone());
+            MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+        }
+
+        // an access list: a list of `[address, storage_keys]`
+        let access_list = RlpSchema::var_list(
+            RlpSchema::list(vec![
+                RlpSchema::Field(20),
+                RlpSchema::var_list(RlpSchema::Field(32), 2),
+            ]),
+            2,
+        );
+        let mut stream = ::rlp::RlpStream::new_list(1);
+        stream.begin_list(2).append(&vec![0xaau8; 20]).begin_list(1).append(&vec![0x01u8; 32]);
+        let circuit = schema_circuit(stream.out().to_vec(), access_list);
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+
+    #[test]
+    pub fn test_mock_rlp_schema_inconsistent() {
+        // the topics list of the log claims one topic instead of two, so the second topic is parsed as the data
+        // and the log has bytes left over
+        let mut inputs = receipt_rlp(&[(0xaa, 2, 0)]);
+        let topics_prefix = inputs.iter().position(|&byte| byte == 0xc0 + 66).unwrap();
+        inputs[topics_prefix] = 0xc0 + 33;
+        let circuit = RlpSchemaTestCircuit::<Fr> {
+            inputs,
+            schema: receipt_schema(),
+            field_lens: None,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(DEGREE, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    /// An item to RLP encode in the circuit.
+    #[derive(Clone, Debug)]
+    enum EncodeItem {
+        /// A byte string of at most `max_len` bytes.
+        Bytes(Vec<u8>, usize),
+        /// An integer as big endian bytes.
+        Uint(Vec<u8>),
+        /// An integer as a field element of `num_bytes` bytes.
+        Num(u64, usize),
+        /// A list of the first `num_items` of the items, or all of them.
+        List(Vec<EncodeItem>, Option<usize>),
+    }
+
+    impl EncodeItem {
+        fn rlp(&self) -> Vec<u8> {
+            match self {
+                EncodeItem::Bytes(bytes, _) => ::rlp::encode(bytes).to_vec(),
+                EncodeItem::Uint(bytes) => {
+                    let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(bytes.len());
+                    ::rlp::encode(&bytes[start..].to_vec()).to_vec()
+                }
+                EncodeItem::Num(num, _) => ::rlp::encode(num).to_vec(),
+                EncodeItem::List(items, num_items) => {
+                    let items = &items[..num_items.unwrap_or(items.len())];
+                    let mut stream = ::rlp::RlpStream::new_list(items.len());
+                    for item in items {
+                        stream.append_raw(&item.rlp(), 1);
+                    }
+                    stream.out().to_vec()
+                }
+            }
+        }
+
+        fn encode_phase0<'v, F: ScalarField>(
+            &self,
+            chip: &RlpChip<F>,
+            ctx: &mut Context<'v, F>,
+        ) -> encode::RlpEncodingWitness<'v, F> {
+            let witness = |x: u64| Value::known(F::from(x));
+            match self {
+                EncodeItem::Bytes(bytes, max_len) => {
+                    let mut cells = bytes.clone();
+                    cells.resize(*max_len, 0);
+                    let cells =
+                        chip.gate().assign_witnesses(ctx, cells.iter().map(|&x| witness(x as u64)));
+                    let len = chip.gate().load_witness(ctx, witness(bytes.len() as u64));
+                    chip.encode_rlp_field_phase0(ctx, cells, len)
+                }
+                EncodeItem::Uint(bytes) => {
+                    let cells =
+                        chip.gate().assign_witnesses(ctx, bytes.iter().map(|&x| witness(x as u64)));
+                    chip.encode_rlp_uint_phase0(ctx, cells)
+                }
+                EncodeItem::Num(num, num_bytes) => {
+                    let num = chip.gate().load_witness(ctx, witness(*num));
+                    let bytes = crate::util::num_to_bytes_be(ctx, &chip.range, &num, *num_bytes);
+                    chip.encode_rlp_uint_phase0(ctx, bytes)
+                }
+                EncodeItem::List(items, num_items) => {
+                    let items = items.iter().map(|item| item.encode_phase0(chip, ctx)).collect();
+                    let num_items = num_items
+                        .map(|num_items| chip.gate().load_witness(ctx, witness(num_items as u64)));
+                    chip.encode_rlp_list_phase0(ctx, items, num_items)
+                }
+            }
+        }
+    }
+
+    /// Encodes `item` and constrains the encoding to be `expected`.
+    #[derive(Clone, Debug)]
+    pub struct RlpEncodeTestCircuit<F> {
+        item: EncodeItem,
+        expected: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: ScalarField> Circuit<F> for RlpEncodeTestCircuit<F> {
+        type Config = RlpConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            RlpConfig::configure(meta, 1, &[1, 1], &[1], 1, 8, 0, DEGREE as usize)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config
+                .range
+                .load_lookup_table(&mut layouter)
+                .expect("load lookup table should not fail");
+
+            let gamma = config.rlc.gamma;
+            let mut chip = RlpChip::new(config, layouter.get_challenge(gamma));
+
+            let mut first_pass = SKIP_FIRST_PASS;
+            layouter.assign_region(
+                || "RLP encode test",
+                |region| {
+                    if first_pass {
+                        first_pass = false;
+                        return Ok(());
+                    }
+                    let mut aux = Context::new(
+                        region,
+                        ContextParams {
+                            max_rows: chip.gate().max_rows,
+                            num_context_ids: 2,
+                            fixed_columns: chip.gate().constants.clone(),
+                        },
+                    );
+                    let ctx = &mut aux;
+
+                    let witness = self.item.encode_phase0(&chip, ctx);
+                    let expected = chip.gate().assign_witnesses(
+                        ctx,
+                        self.expected.iter().map(|x| Value::known(F::from(*x as u64))),
+                    );
+                    assert!(expected.len() <= witness.rlp_cells.len());
+                    for (byte, expected) in witness.rlp_cells.iter().zip(expected.iter()) {
+                        ctx.constrain_equal(byte, expected);
+                    }
+                    chip.gate().assert_is_const(
+                        ctx,
+                        &witness.rlp_len,
+                        F::from(self.expected.len() as u64),
+                    );
+                    chip.range.finalize(ctx);
+                    ctx.next_phase();
+
+                    chip.get_challenge(ctx);
+                    chip.encode_rlp_phase1(ctx, witness);
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn encode_circuit(item: EncodeItem) -> RlpEncodeTestCircuit<Fr> {
+        RlpEncodeTestCircuit { expected: item.rlp(), item, _marker: PhantomData }
+    }
+
+    #[test]
+    pub fn test_mock_rlp_encode_account() {
+        for (nonce, balance) in [(0u64, 0u64), (1, 0x7f), (0x80, 1_000_000_007)] {
+            let mut balance_bytes = vec![0u8; 24];
+            balance_bytes.extend(balance.to_be_bytes());
+            let account = EncodeItem::List(
+                vec![
+                    EncodeItem::Num(nonce, 8),
+                    EncodeItem::Uint(balance_bytes),
+                    EncodeItem::Bytes(vec![0x56; 32], 32),
+                    EncodeItem::Bytes(vec![0xc5; 32], 32),
+                ],
+                None,
+            );
+            let circuit = encode_circuit(account);
+            MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+        }
+    }
+
+    #[test]
+    pub fn test_mock_rlp_encode_strings() {
+        // literals, the short and long forms, and a long form list
+        let strings =
+            [vec![], vec![0x05], vec![0x80], vec![0xaa; 55], vec![0xbb; 56], vec![0xcc; 300]]
+                .into_iter()
+                .map(|bytes| EncodeItem::Bytes(bytes, 300))
+                .collect();
+        let circuit = encode_circuit(EncodeItem::List(strings, None));
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+
+        // transaction indices
+        let indices = [0, 1, 0x7f, 0x80, 0xff, 0x1234]
+            .into_iter()
+            .map(|idx| EncodeItem::Num(idx, 2))
+            .collect();
+        let circuit = encode_circuit(EncodeItem::List(indices, None));
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+
+    #[test]
+    pub fn test_mock_rlp_encode_var_list() {
+        let topics: Vec<_> = (1..=4).map(|topic| EncodeItem::Bytes(vec![topic; 32], 32)).collect();
+        for num_topics in 0..=4 {
+            let log = EncodeItem::List(
+                vec![
+                    EncodeItem::Bytes(vec![0xaa; 20], 20),
+                    EncodeItem::List(topics.clone(), Some(num_topics)),
+                    EncodeItem::Bytes(vec![0xdd; 3], 64),
+                ],
+                None,
+            );
+            let circuit = encode_circuit(log);
+            MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+        }
+    }
+
+    #[test]
+    pub fn test_mock_rlp_encode_non_canonical() {
+        // `0x05` is its own encoding, not `0x81 0x05`
+        let mut circuit = encode_circuit(EncodeItem::Num(5, 2));
+        circuit.expected = vec![0x81, 0x05];
+        let prover = MockProver::run(DEGREE, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // a list past its number of items
+        let topics: Vec<_> = (1..=2).map(|topic| EncodeItem::Bytes(vec![topic; 32], 32)).collect();
+        let mut circuit = encode_circuit(EncodeItem::List(topics.clone(), Some(1)));
+        circuit.expected = EncodeItem::List(topics, None).rlp();
+        let prover = MockProver::run(DEGREE, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
 }
@@ -0,0 +1,488 @@
+//! SHA-256 of byte arrays of fixed or variable length, e.g. for SSZ Merkle proofs of beacon chain data.
+//!
+//! Unlike [`KeccakChip`](crate::keccak::KeccakChip), which looks its queries up in a separate keccak table, this
+//! chip constrains each query when it is made, by computing the compression function on the bits of the message
+//! with `GateInstructions`. Everything is in `FirstPhase`. Bits known when the circuit is built, such as the
+//! padding of a fixed length message, are folded into constants, so a block of padding only costs the rounds.
+//!
+//! This is expensive. Every bit is a cell and every XOR costs two gates, so the compression of a block costs about
+//! 300k to 400k cells: roughly 80k for the message schedule and 3k per round for the 64 rounds. A block of constant
+//! padding skips the message schedule and still costs about 200k. A 64 byte input, such as a node of an SSZ Merkle
+//! branch, is a block and a block of padding, so about 600k cells, and a Merkle branch of depth `d` costs `d` times
+//! that: with 16 advice columns, a degree 18 circuit fits a branch of depth 6. Proofs deep in the beacon state need
+//! a lookup based SHA-256 circuit instead, like the keccak table of [`KeccakChip`](crate::keccak::KeccakChip).
+use crate::keccak::get_bytes;
+use halo2_base::{
+    gates::{GateInstructions, RangeInstructions},
+    utils::{bit_length, value_to_option, ScalarField},
+    AssignedValue, Context,
+    QuantumCell::{self, Constant, Existing},
+};
+use sha2::{Digest, Sha256};
+
+#[cfg(test)]
+mod tests;
+
+pub const NUM_BYTES_SHA256: usize = 32;
+pub const SHA256_BLOCK_BYTES: usize = 64;
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+pub fn sha256(bytes: &[u8]) -> [u8; NUM_BYTES_SHA256] {
+    Sha256::digest(bytes).into()
+}
+
+/// The number of blocks of the padded message of `num_bytes` bytes.
+pub fn num_sha256_blocks(num_bytes: usize) -> usize {
+    (num_bytes + 8) / SHA256_BLOCK_BYTES + 1
+}
+
+#[derive(Clone, Debug)]
+pub struct Sha256FixedLenQuery<'v, F: ScalarField> {
+    pub input_bytes: Vec<u8>,
+    pub input_assigned: Vec<AssignedValue<'v, F>>,
+
+    pub output_bytes: [u8; NUM_BYTES_SHA256],
+    pub output_assigned: Vec<AssignedValue<'v, F>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sha256VarLenQuery<'v, F: ScalarField> {
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    pub num_bytes: usize,
+    pub length: AssignedValue<'v, F>,
+    pub input_bytes: Vec<u8>,
+    pub input_assigned: Vec<AssignedValue<'v, F>>,
+
+    pub output_bytes: [u8; NUM_BYTES_SHA256],
+    pub output_assigned: Vec<AssignedValue<'v, F>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sha256Chip<'v, F: ScalarField> {
+    pub var_len_queries: Vec<Sha256VarLenQuery<'v, F>>,
+    pub fixed_len_queries: Vec<Sha256FixedLenQuery<'v, F>>,
+}
+
+impl<'v, F: ScalarField> Default for Sha256Chip<'v, F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'v, F: ScalarField> Sha256Chip<'v, F> {
+    pub fn new() -> Self {
+        Self { var_len_queries: vec![], fixed_len_queries: vec![] }
+    }
+
+    /// Takes a byte vector of known fixed length and computes the SHA-256 digest of `bytes`.
+    /// - Returns the index of the query in `fixed_len_queries`.
+    /// - Constrains `output_assigned` of the query to be the digest of `input_assigned`, and the input cells to be
+    ///   bytes.
+    ///
+    /// Assumes that `input_bytes` coincides with the values of `input_assigned` as bytes,
+    /// if provided (`bytes` is used for faster witness generation).
+    pub fn sha256_fixed_len(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        gate: &impl GateInstructions<F>,
+        input_assigned: Vec<AssignedValue<'v, F>>,
+        input_bytes: Option<Vec<u8>>,
+    ) -> usize {
+        let bytes = input_bytes.unwrap_or_else(|| get_bytes(&input_assigned[..]));
+        debug_assert_eq!(bytes.len(), input_assigned.len());
+        let output_bytes = sha256(&bytes);
+
+        let len = input_assigned.len();
+        let mut message = Vec::with_capacity(num_sha256_blocks(len) * SHA256_BLOCK_BYTES);
+        for byte in &input_assigned {
+            message.push(byte_bits(ctx, gate, byte));
+        }
+        // padding: 0x80, then zeros, then the bit length as 8 big endian bytes
+        let mut padding = vec![0x80];
+        padding.resize(num_sha256_blocks(len) * SHA256_BLOCK_BYTES - len - 8, 0);
+        padding.extend((8 * len as u64).to_be_bytes());
+        message.extend(padding.into_iter().map(constant_byte_bits));
+
+        let state = message
+            .chunks(SHA256_BLOCK_BYTES)
+            .fold(initial_state(), |state, block| compress(ctx, gate, &state, block));
+        let output_assigned = state_bytes(ctx, gate, &state);
+
+        self.fixed_len_queries.push(Sha256FixedLenQuery {
+            input_bytes: bytes,
+            input_assigned,
+            output_bytes,
+            output_assigned,
+        });
+        self.fixed_len_queries.len() - 1
+    }
+
+    /// Takes a fixed length byte vector and computes the SHA-256 digest of `bytes[..len]`.
+    /// - Returns the index of the query in `var_len_queries`.
+    /// - Constrains `output_assigned` of the query to be the digest of `input_assigned[..len]`, and these cells to
+    ///   be bytes. The cells of `input_assigned` beyond `len` may be anything.
+    ///
+    /// Assumes that `input_bytes[..len]` coincides with the values of `input_assigned[..len]` as bytes,
+    /// if provided (`bytes` is used for faster witness generation).
+    ///
+    /// Constrains `min_len <= len <= bytes.len()`.
+    pub fn sha256_var_len(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        range: &impl RangeInstructions<F>,
+        input_assigned: Vec<AssignedValue<'v, F>>,
+        input_bytes: Option<Vec<u8>>,
+        len: AssignedValue<'v, F>,
+        min_len: usize,
+    ) -> usize {
+        let gate = range.gate();
+        let bytes = input_bytes.unwrap_or_else(|| get_bytes(&input_assigned[..]));
+        let max_len = input_assigned.len();
+
+        range.check_less_than_safe(ctx, &len, (max_len + 1) as u64);
+        if min_len != 0 {
+            range.check_less_than(
+                ctx,
+                Constant(gate.get_field_element((min_len - 1) as u64)),
+                Existing(&len),
+                bit_length((max_len + 1) as u64),
+            );
+        }
+        let num_bytes =
+            value_to_option(len.value()).map(|v| v.get_lower_32() as usize).unwrap_or(min_len);
+        debug_assert!(bytes.len() >= num_bytes);
+        let output_bytes = sha256(&bytes[..num_bytes]);
+
+        let min_blocks = num_sha256_blocks(min_len);
+        let max_blocks = num_sha256_blocks(max_len);
+        // the padded message has `k` blocks iff `64 * (k - 1) <= len + 8 < 64 * k`
+        let len_plus_8 = gate.add(ctx, Existing(&len), Constant(F::from(8)));
+        let bits = bit_length((max_blocks * SHA256_BLOCK_BYTES) as u64);
+        let mut is_shorter = vec![gate.load_zero(ctx)];
+        for num_blocks in 1..=max_blocks {
+            let max = Constant(F::from((num_blocks * SHA256_BLOCK_BYTES) as u64));
+            is_shorter.push(range.is_less_than(ctx, Existing(&len_plus_8), max, bits));
+        }
+        // is_last_block[idx] = 1 iff the padded message ends with block `idx`
+        let is_last_block: Vec<_> = is_shorter
+            .windows(2)
+            .map(|pair| gate.sub(ctx, Existing(&pair[1]), Existing(&pair[0])))
+            .collect();
+
+        // is_len[idx] = 1 iff idx == len, is_input[idx] = 1 iff idx < len
+        let is_len = gate.idx_to_indicator(ctx, Existing(&len), max_len + 1);
+        let mut is_input = Vec::with_capacity(max_len);
+        let mut num_input = gate.load_constant(ctx, F::one());
+        for is_len_idx in &is_len[..max_len] {
+            num_input = gate.sub(ctx, Existing(&num_input), Existing(is_len_idx));
+            is_input.push(num_input.clone());
+        }
+        // the bit length `8 * len`, big endian, of which only the last bytes can be nonzero
+        let bit_len = gate.mul(ctx, Existing(&len), Constant(F::from(8)));
+        let mut bit_len_bits = gate.num_to_bits(ctx, &bit_len, bit_length(8 * max_len as u64));
+        bit_len_bits.resize_with(64, || gate.load_zero(ctx));
+        let bit_len_bytes: Vec<_> = (0..8)
+            .rev()
+            .map(|idx| {
+                gate.inner_product(
+                    ctx,
+                    bit_len_bits[8 * idx..8 * idx + 8].iter().map(Existing),
+                    (0..8).map(|bit| Constant(gate.pow_of_two()[bit])),
+                )
+            })
+            .collect();
+
+        let mut message = Vec::with_capacity(max_blocks * SHA256_BLOCK_BYTES);
+        for idx in 0..max_blocks * SHA256_BLOCK_BYTES {
+            // byte = input[idx] * (idx < len) + 0x80 * (idx == len) + (bit length byte if this block is the last)
+            let mut terms = vec![];
+            if idx < max_len {
+                terms.push((Existing(&input_assigned[idx]), Existing(&is_input[idx])));
+            }
+            if idx <= max_len {
+                terms.push((Constant(F::from(0x80)), Existing(&is_len[idx])));
+            }
+            let block = idx / SHA256_BLOCK_BYTES;
+            let offset = idx % SHA256_BLOCK_BYTES;
+            if offset >= SHA256_BLOCK_BYTES - 8 && block + 1 >= min_blocks {
+                terms.push((
+                    Existing(&bit_len_bytes[offset + 8 - SHA256_BLOCK_BYTES]),
+                    Existing(&is_last_block[block]),
+                ));
+            }
+            if terms.is_empty() {
+                message.push(constant_byte_bits(0));
+            } else {
+                let (values, selectors): (Vec<_>, Vec<_>) = terms.into_iter().unzip();
+                let byte = gate.inner_product(ctx, values, selectors);
+                message.push(byte_bits(ctx, gate, &byte));
+            }
+        }
+
+        let mut state = initial_state();
+        let mut candidates = vec![];
+        for (idx, block) in message.chunks(SHA256_BLOCK_BYTES).enumerate() {
+            state = compress(ctx, gate, &state, block);
+            if idx + 1 >= min_blocks {
+                candidates.push((state_bytes(ctx, gate, &state), &is_last_block[idx]));
+            }
+        }
+        let output_assigned = (0..NUM_BYTES_SHA256)
+            .map(|idx| {
+                gate.inner_product(
+                    ctx,
+                    candidates.iter().map(|(bytes, _)| Existing(&bytes[idx])),
+                    candidates.iter().map(|(_, is_last)| Existing(*is_last)),
+                )
+            })
+            .collect();
+
+        self.var_len_queries.push(Sha256VarLenQuery {
+            min_bytes: min_len,
+            max_bytes: max_len,
+            num_bytes,
+            length: len,
+            input_bytes: bytes,
+            input_assigned,
+            output_bytes,
+            output_assigned,
+        });
+        self.var_len_queries.len() - 1
+    }
+}
+
+/// A bit of a message or of the hash state, which is a constant if it is known when the circuit is built.
+#[derive(Clone, Debug)]
+enum Bit<'v, F: ScalarField> {
+    Constant(bool),
+    Assigned(AssignedValue<'v, F>),
+}
+
+impl<'v, F: ScalarField> Bit<'v, F> {
+    fn cell(&self) -> QuantumCell<'_, 'v, F> {
+        match self {
+            Bit::Constant(bit) => Constant(F::from(*bit as u64)),
+            Bit::Assigned(bit) => Existing(bit),
+        }
+    }
+}
+
+/// A 32-bit word as little endian bits.
+type Word<'v, F> = Vec<Bit<'v, F>>;
+
+fn constant_word<'v, F: ScalarField>(word: u32) -> Word<'v, F> {
+    (0..32).map(|idx| Bit::Constant(word >> idx & 1 == 1)).collect()
+}
+
+fn initial_state<'v, F: ScalarField>() -> Vec<Word<'v, F>> {
+    INITIAL_STATE.into_iter().map(constant_word).collect()
+}
+
+fn constant_byte_bits<'v, F: ScalarField>(byte: u8) -> Vec<Bit<'v, F>> {
+    (0..8).map(|idx| Bit::Constant(byte >> idx & 1 == 1)).collect()
+}
+
+/// The little endian bits of `byte`, constraining it to be a byte.
+fn byte_bits<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    byte: &AssignedValue<'v, F>,
+) -> Vec<Bit<'v, F>> {
+    gate.num_to_bits(ctx, byte, 8).into_iter().map(Bit::Assigned).collect()
+}
+
+fn xor<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    a: &Bit<'v, F>,
+    b: &Bit<'v, F>,
+) -> Bit<'v, F> {
+    match (a, b) {
+        (Bit::Constant(a), Bit::Constant(b)) => Bit::Constant(a ^ b),
+        (Bit::Constant(c), Bit::Assigned(x)) | (Bit::Assigned(x), Bit::Constant(c)) => {
+            if *c {
+                Bit::Assigned(gate.not(ctx, Existing(x)))
+            } else {
+                Bit::Assigned(x.clone())
+            }
+        }
+        (Bit::Assigned(a), Bit::Assigned(b)) => {
+            // a ^ b = a + b * (1 - 2a)
+            let one_minus_two_a =
+                gate.mul_add(ctx, Existing(a), Constant(-F::from(2)), Constant(F::one()));
+            Bit::Assigned(gate.mul_add(ctx, Existing(b), Existing(&one_minus_two_a), Existing(a)))
+        }
+    }
+}
+
+/// Returns `a` if `sel` else `b`.
+fn select<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    a: &Bit<'v, F>,
+    b: &Bit<'v, F>,
+    sel: &Bit<'v, F>,
+) -> Bit<'v, F> {
+    match (a, b, sel) {
+        (_, _, Bit::Constant(sel)) => {
+            if *sel {
+                a.clone()
+            } else {
+                b.clone()
+            }
+        }
+        (Bit::Constant(a), Bit::Constant(b), _) if a == b => Bit::Constant(*a),
+        (_, _, Bit::Assigned(sel)) => {
+            Bit::Assigned(gate.select(ctx, a.cell(), b.cell(), Existing(sel)))
+        }
+    }
+}
+
+fn rotr<'v, F: ScalarField>(word: &Word<'v, F>, n: usize) -> Word<'v, F> {
+    (0..32).map(|idx| word[(idx + n) % 32].clone()).collect()
+}
+
+fn shr<'v, F: ScalarField>(word: &Word<'v, F>, n: usize) -> Word<'v, F> {
+    (0..32).map(|idx| word.get(idx + n).cloned().unwrap_or(Bit::Constant(false))).collect()
+}
+
+fn xor3<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    [a, b, c]: [Word<'v, F>; 3],
+) -> Word<'v, F> {
+    (0..32)
+        .map(|idx| {
+            let ab = xor(ctx, gate, &a[idx], &b[idx]);
+            xor(ctx, gate, &ab, &c[idx])
+        })
+        .collect()
+}
+
+fn big_sigma<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    word: &Word<'v, F>,
+    [r0, r1, r2]: [usize; 3],
+) -> Word<'v, F> {
+    xor3(ctx, gate, [rotr(word, r0), rotr(word, r1), rotr(word, r2)])
+}
+
+fn small_sigma<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    word: &Word<'v, F>,
+    [r0, r1, s]: [usize; 3],
+) -> Word<'v, F> {
+    xor3(ctx, gate, [rotr(word, r0), rotr(word, r1), shr(word, s)])
+}
+
+/// Returns the sum of `words` modulo `2^32`.
+fn add_words<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    words: &[&Word<'v, F>],
+) -> Word<'v, F> {
+    let mut constant = 0u64;
+    let mut bits = vec![];
+    let mut coeffs = vec![];
+    for word in words {
+        for (idx, bit) in word.iter().enumerate() {
+            match bit {
+                Bit::Constant(bit) => constant += (*bit as u64) << idx,
+                Bit::Assigned(bit) => {
+                    bits.push(bit);
+                    coeffs.push(gate.pow_of_two()[idx]);
+                }
+            }
+        }
+    }
+    if bits.is_empty() {
+        return constant_word(constant as u32);
+    }
+    let sum =
+        gate.inner_product(ctx, bits.into_iter().map(Existing), coeffs.into_iter().map(Constant));
+    let sum = gate.add(ctx, Existing(&sum), Constant(F::from(constant)));
+    let num_bits = bit_length(words.len() as u64 * u32::MAX as u64);
+    let mut sum_bits = gate.num_to_bits(ctx, &sum, num_bits);
+    sum_bits.truncate(32);
+    sum_bits.into_iter().map(Bit::Assigned).collect()
+}
+
+/// The SHA-256 compression function of `block` of 64 bytes, given as little endian bits.
+fn compress<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    state: &[Word<'v, F>],
+    block: &[Vec<Bit<'v, F>>],
+) -> Vec<Word<'v, F>> {
+    debug_assert_eq!(block.len(), SHA256_BLOCK_BYTES);
+    // message schedule, the words of the block are big endian
+    let mut w: Vec<Word<'v, F>> =
+        block.chunks(4).map(|bytes| bytes.iter().rev().flatten().cloned().collect()).collect();
+    for t in 16..64 {
+        let s0 = small_sigma(ctx, gate, &w[t - 15], [7, 18, 3]);
+        let s1 = small_sigma(ctx, gate, &w[t - 2], [17, 19, 10]);
+        let w_t = add_words(ctx, gate, &[&s1, &w[t - 7], &s0, &w[t - 16]]);
+        w.push(w_t);
+    }
+
+    let mut vars = state.to_vec();
+    for (t, w_t) in w.iter().enumerate() {
+        let [a, b, c, d, e, f, g, h] = [0, 1, 2, 3, 4, 5, 6, 7].map(|idx| &vars[idx]);
+        let s1 = big_sigma(ctx, gate, e, [6, 11, 25]);
+        let ch: Word<F> = (0..32).map(|idx| select(ctx, gate, &f[idx], &g[idx], &e[idx])).collect();
+        let k = constant_word(ROUND_CONSTANTS[t]);
+        let s0 = big_sigma(ctx, gate, a, [2, 13, 22]);
+        // maj(a, b, c) = c if a != b else a
+        let maj: Word<F> = (0..32)
+            .map(|idx| {
+                let a_xor_b = xor(ctx, gate, &a[idx], &b[idx]);
+                select(ctx, gate, &c[idx], &a[idx], &a_xor_b)
+            })
+            .collect();
+        let new_a = add_words(ctx, gate, &[h, &s1, &ch, &k, w_t, &s0, &maj]);
+        let new_e = add_words(ctx, gate, &[d, h, &s1, &ch, &k, w_t]);
+        vars.pop();
+        vars.insert(0, new_a);
+        vars[4] = new_e;
+    }
+    state.iter().zip(vars.iter()).map(|(x, y)| add_words(ctx, gate, &[x, y])).collect()
+}
+
+/// The digest of the hash state, as big endian bytes.
+fn state_bytes<'v, F: ScalarField>(
+    ctx: &mut Context<'v, F>,
+    gate: &impl GateInstructions<F>,
+    state: &[Word<'v, F>],
+) -> Vec<AssignedValue<'v, F>> {
+    let byte_bits: Vec<_> = state
+        .iter()
+        .flat_map(|word| (0..4).rev().map(move |idx| &word[8 * idx..8 * idx + 8]))
+        .collect();
+    byte_bits
+        .into_iter()
+        .map(|bits| {
+            gate.inner_product(
+                ctx,
+                bits.iter().map(Bit::cell),
+                (0..8).map(|idx| Constant(gate.pow_of_two()[idx])),
+            )
+        })
+        .collect()
+}
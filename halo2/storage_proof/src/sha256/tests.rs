@@ -0,0 +1,158 @@
+use super::*;
+use crate::halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    dev::MockProver,
+    halo2curves::bn256::Fr,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use halo2_base::{
+    gates::range::{RangeConfig, RangeStrategy},
+    ContextParams, SKIP_FIRST_PASS,
+};
+use std::marker::PhantomData;
+
+const DEGREE: u32 = 18;
+
+/// Hashes `input`, or `input[..len]` with a variable length query if `len` is given, and constrains the digest to
+/// be `expected`.
+#[derive(Clone, Debug)]
+pub struct Sha256TestCircuit<F> {
+    input: Vec<u8>,
+    len: Option<usize>,
+    min_len: usize,
+    expected: [u8; NUM_BYTES_SHA256],
+    _marker: PhantomData<F>,
+}
+
+impl<F: ScalarField> Sha256TestCircuit<F> {
+    fn fixed_len(input: Vec<u8>) -> Self {
+        let expected = sha256(&input);
+        Self { input, len: None, min_len: 0, expected, _marker: PhantomData }
+    }
+
+    fn var_len(input: Vec<u8>, len: usize, min_len: usize) -> Self {
+        let expected = sha256(&input[..len]);
+        Self { input, len: Some(len), min_len, expected, _marker: PhantomData }
+    }
+}
+
+impl<F: ScalarField> Circuit<F> for Sha256TestCircuit<F> {
+    type Config = RangeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let mut range = RangeConfig::configure(
+            meta,
+            RangeStrategy::Vertical,
+            &[16],
+            &[1],
+            1,
+            8,
+            0,
+            DEGREE as usize,
+        );
+        range.gate.max_rows = (1 << DEGREE) - meta.minimum_rows();
+        range
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load_lookup_table(&mut layouter).expect("load lookup table should not fail");
+
+        let mut first_pass = SKIP_FIRST_PASS;
+        layouter.assign_region(
+            || "sha256 test",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let mut aux = Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: config.gate.max_rows,
+                        num_context_ids: 1,
+                        fixed_columns: config.gate.constants.clone(),
+                    },
+                );
+                let ctx = &mut aux;
+                let gate = config.gate();
+
+                let input = gate.assign_witnesses(
+                    ctx,
+                    self.input.iter().map(|byte| Value::known(F::from(*byte as u64))),
+                );
+                let mut sha256_chip = Sha256Chip::new();
+                let output = if let Some(len) = self.len {
+                    let len = gate.load_witness(ctx, Value::known(F::from(len as u64)));
+                    let idx =
+                        sha256_chip.sha256_var_len(ctx, &config, input, None, len, self.min_len);
+                    sha256_chip.var_len_queries[idx].output_assigned.clone()
+                } else {
+                    let idx = sha256_chip.sha256_fixed_len(ctx, gate, input, None);
+                    sha256_chip.fixed_len_queries[idx].output_assigned.clone()
+                };
+                for (byte, expected) in output.iter().zip(self.expected) {
+                    gate.assert_is_const(ctx, byte, F::from(expected as u64));
+                }
+                config.finalize(ctx);
+                Ok(())
+            },
+        )
+    }
+}
+
+#[test]
+pub fn test_native_sha256() {
+    assert_eq!(
+        sha256(b"abc"),
+        *ethers_core::utils::hex::decode(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        )
+        .unwrap()
+    );
+    assert_eq!([0, 1, 55, 56, 63, 64, 119, 120].map(num_sha256_blocks), [1, 1, 1, 2, 2, 2, 2, 3]);
+}
+
+#[test]
+pub fn test_mock_sha256_fixed_len() {
+    for input in [vec![], b"abc".to_vec(), (0u8..56).collect(), (0u8..64).collect()] {
+        let circuit = Sha256TestCircuit::<Fr>::fixed_len(input);
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+}
+
+#[test]
+pub fn test_mock_sha256_var_len() {
+    // the bytes beyond `len` are not hashed
+    let input: Vec<u8> = (1u8..=100).collect();
+    for (len, min_len) in [(0, 0), (3, 0), (55, 32), (56, 32), (100, 32)] {
+        let circuit = Sha256TestCircuit::<Fr>::var_len(input.clone(), len, min_len);
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+}
+
+#[test]
+pub fn test_mock_sha256_wrong_digest() {
+    // the digest of a different message
+    let circuit = Sha256TestCircuit::<Fr> {
+        expected: sha256(b"abd"),
+        ..Sha256TestCircuit::fixed_len(b"abc".to_vec())
+    };
+    assert!(MockProver::run(DEGREE, &circuit, vec![]).unwrap().verify().is_err());
+
+    // the digest of a shorter prefix
+    let input: Vec<u8> = (1u8..=100).collect();
+    let circuit = Sha256TestCircuit::<Fr> {
+        expected: sha256(&input[..54]),
+        ..Sha256TestCircuit::var_len(input.clone(), 55, 0)
+    };
+    assert!(MockProver::run(DEGREE, &circuit, vec![]).unwrap().verify().is_err());
+}
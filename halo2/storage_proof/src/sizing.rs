@@ -0,0 +1,250 @@
+//! Sizing of [`EthConfigParams`] for circuits built on [`EthChip`](crate::EthChip).
+//!
+//! The number of rows a circuit needs depends on the shape of its inputs (number of slots, proof depths, ...),
+//! so instead of hardcoding the params we run witness generation once in a dry run, record how many cells each
+//! phase used, and compute the smallest params that fit.
+//!
+//! Circuits report their usage with [`record_phase`] just before `RangeConfig::finalize` in each phase and with
+//! [`record_keccak`] before assigning the keccak table. Recording is a no-op outside of [`measure`].
+use crate::{
+    halo2_proofs::{
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, ConstraintSystem},
+    },
+    keccak::{max_keccak_rows_per_round, KeccakChip},
+    util::EthConfigParams,
+    EthConfig, Field,
+};
+use halo2_base::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// `zkevm-keccak` needs strictly more rows per round than bytes per word.
+pub const MIN_KECCAK_ROWS_PER_ROUND: usize = 9;
+/// Number of times the sized params are validated, adding a column on failure, before giving up.
+const MAX_VALIDATION_ATTEMPTS: usize = 3;
+/// Number of times the unusable rows are recomputed for the columns they leave room for.
+const MAX_UNUSABLE_ROWS_ITERATIONS: usize = 4;
+
+thread_local! {
+    static USAGE: RefCell<Option<CircuitUsage>> = RefCell::new(None);
+}
+
+/// Cells used by a single phase of a circuit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseUsage {
+    /// Advice cells used by each context id: 0 for the range chip, 1 for the RLC chip.
+    pub advice_cells: Vec<usize>,
+    /// Cells range checked with a lookup.
+    pub lookup_cells: usize,
+}
+
+/// Cells used by a circuit, as recorded during [`measure`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitUsage {
+    pub phases: Vec<PhaseUsage>,
+    pub fixed_cells: usize,
+    /// Number of keccak permutations the keccak table must have room for.
+    pub num_keccak_f: usize,
+}
+
+/// Restores the usage of an enclosing [`measure`] when dropped, also on panic.
+struct UsageGuard(Option<CircuitUsage>);
+
+impl Drop for UsageGuard {
+    fn drop(&mut self) {
+        let prev = self.0.take();
+        USAGE.with(|usage| *usage.borrow_mut() = prev);
+    }
+}
+
+/// Runs `f` and returns the usage recorded by the circuits synthesized in it on the current thread.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, CircuitUsage) {
+    let _guard = UsageGuard(USAGE.with(|usage| usage.replace(Some(CircuitUsage::default()))));
+    let res = f();
+    let usage = USAGE.with(|usage| usage.borrow_mut().take()).unwrap_or_default();
+    (res, usage)
+}
+
+/// Records the cells used in the current phase of `ctx`. Call this right before `RangeConfig::finalize`, which
+/// consumes the cells to lookup.
+pub fn record_phase<F: Field>(ctx: &Context<F>) {
+    USAGE.with(|usage| {
+        if let Some(usage) = usage.borrow_mut().as_mut() {
+            let advice_cells = ctx
+                .advice_alloc
+                .iter()
+                .map(|&(column, offset)| column * ctx.max_rows + offset)
+                .collect();
+            usage.phases.push(PhaseUsage { advice_cells, lookup_cells: ctx.cells_to_lookup.len() });
+            usage.fixed_cells =
+                usage.fixed_cells.max(ctx.fixed_col * ctx.max_rows + ctx.fixed_offset);
+        }
+    })
+}
+
+/// Records the keccak permutations queued in `keccak`. Call this before `KeccakChip::assign_phase0`.
+pub fn record_keccak<F: Field>(keccak: &KeccakChip<F>) {
+    USAGE.with(|usage| {
+        if let Some(usage) = usage.borrow_mut().as_mut() {
+            usage.num_keccak_f += keccak.capacity();
+        }
+    })
+}
+
+impl CircuitUsage {
+    /// The fewest columns that fit this usage in `2^degree` rows, of which `unusable_rows` are reserved for
+    /// blinding. Returns `None` if the keccak table does not fit.
+    pub fn params_for_degree(&self, degree: u32, unusable_rows: usize) -> Option<EthConfigParams> {
+        let usable_rows = (1usize << degree).checked_sub(unusable_rows)?;
+        let num_columns = |cells: usize| (cells + usable_rows - 1) / usable_rows;
+
+        let keccak_rows_per_round = max_keccak_rows_per_round(self.num_keccak_f, usable_rows);
+        if keccak_rows_per_round < MIN_KECCAK_ROWS_PER_ROUND {
+            return None;
+        }
+        let num_range_advice = self
+            .phases
+            .iter()
+            .map(|phase| num_columns(phase.advice_cells.first().copied().unwrap_or(0)).max(1))
+            .collect();
+        let num_lookup_advice = self
+            .phases
+            .iter()
+            .enumerate()
+            // range checks in `FirstPhase` always need a lookup column
+            .map(|(i, phase)| num_columns(phase.lookup_cells).max(usize::from(i == 0)))
+            .collect();
+        let num_rlc_columns = self
+            .phases
+            .iter()
+            .map(|phase| num_columns(phase.advice_cells.get(1).copied().unwrap_or(0)))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        Some(EthConfigParams {
+            degree,
+            num_rlc_columns,
+            num_range_advice,
+            num_lookup_advice,
+            num_fixed: num_columns(self.fixed_cells).max(1),
+            unusable_rows,
+            keccak_rows_per_round,
+        })
+    }
+}
+
+/// Limits for [`size_circuit`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizingOptions {
+    pub min_degree: u32,
+    pub max_degree: u32,
+    /// Maximum number of advice columns, not counting the keccak columns. The smallest degree within this
+    /// limit is chosen.
+    pub max_advice_columns: usize,
+}
+
+impl Default for SizingOptions {
+    fn default() -> Self {
+        // the range lookup table has `2^8` rows
+        Self { min_degree: 10, max_degree: 24, max_advice_columns: 64 }
+    }
+}
+
+/// The number of rows `EthConfig` reserves at the end of each column when configured with `params`.
+pub fn minimum_rows(params: &EthConfigParams) -> usize {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    EthConfig::configure(&mut meta, params.clone(), 0);
+    meta.minimum_rows()
+}
+
+/// Synthesizes `circuit` with `params` in a `MockProver`, without checking the constraints. Returns the usage,
+/// or `None` if the circuit does not fit.
+pub fn dry_run<C: Circuit<Fr>>(
+    circuit: &C,
+    instances: Vec<Vec<Fr>>,
+    params: &EthConfigParams,
+) -> Option<CircuitUsage> {
+    let (res, usage) = measure(|| {
+        // chips panic when they run out of columns
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            params.scope(|| MockProver::run(params.degree, circuit, instances))
+        }))
+    });
+    matches!(res, Ok(Ok(_))).then_some(usage)
+}
+
+/// The smallest params with `2^degree` rows, `degree >= min_degree`, that fit `usage` within the limits of
+/// `options`.
+pub fn fit_params(usage: &CircuitUsage, options: &SizingOptions) -> Option<EthConfigParams> {
+    (options.min_degree..=options.max_degree).find_map(|degree| {
+        // the unusable rows grow with the number of columns, which shrink with the usable rows
+        let mut unusable_rows = 0;
+        for _ in 0..MAX_UNUSABLE_ROWS_ITERATIONS {
+            let params = usage.params_for_degree(degree, unusable_rows)?;
+            let num_advice = params.num_rlc_columns
+                + params.num_range_advice.iter().sum::<usize>()
+                + params.num_lookup_advice.iter().sum::<usize>();
+            if num_advice > options.max_advice_columns {
+                return None;
+            }
+            let min_rows = minimum_rows(&params);
+            if min_rows <= unusable_rows {
+                return Some(params);
+            }
+            unusable_rows = min_rows;
+        }
+        None
+    })
+}
+
+/// Computes the smallest [`EthConfigParams`] for `circuit` with its current inputs.
+///
+/// Witness generation is first run with `dry_run_params`, doubling the rows until the circuit fits, to measure
+/// its usage. The params computed from the usage are then validated with another dry run. Gates that do not fit
+/// in the remainder of a column are moved to the next one, so a column is added to each phase if validation
+/// fails.
+///
+/// The circuit's `configure` must read its params from [`EthConfigParams::current`].
+///
+/// # Panics
+/// If the circuit does not fit within `options`.
+pub fn size_circuit<C: Circuit<Fr>>(
+    circuit: &C,
+    instances: Vec<Vec<Fr>>,
+    dry_run_params: EthConfigParams,
+    options: &SizingOptions,
+) -> EthConfigParams {
+    let mut params = dry_run_params;
+    let usage = loop {
+        if let Some(usage) = dry_run(circuit, instances.clone(), &params) {
+            break usage;
+        }
+        assert!(
+            params.degree < options.max_degree,
+            "circuit does not fit in 2^{} rows with {params:?}",
+            options.max_degree
+        );
+        params.degree += 1;
+    };
+
+    let mut params = fit_params(&usage, options)
+        .unwrap_or_else(|| panic!("no params within {options:?} fit circuit usage {usage:?}"));
+    for _ in 0..MAX_VALIDATION_ATTEMPTS {
+        if dry_run(circuit, instances.clone(), &params).is_some() {
+            return params;
+        }
+        for num_columns in
+            params.num_range_advice.iter_mut().chain(params.num_lookup_advice.iter_mut())
+        {
+            *num_columns += 1;
+        }
+        params.num_rlc_columns += 1;
+        params.unusable_rows = params.unusable_rows.max(minimum_rows(&params));
+    }
+    panic!("sized params {params:?} do not fit circuit usage {usage:?}");
+}
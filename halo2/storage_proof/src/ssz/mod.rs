@@ -0,0 +1,200 @@
+//! SSZ Merkle proofs, e.g. of a field of the beacon state against a beacon block root.
+//!
+//! Nodes of a Merkle tree are addressed by their [generalized index](https://github.com/ethereum/consensus-specs/blob/dev/ssz/merkle-proofs.md#generalized-merkle-tree-index):
+//! the root is `1` and the children of node `i` are `2i` and `2i + 1`. The generalized index of a field nested in
+//! several containers is the concatenation of the indices at each level, see [`concat_gindices`].
+use crate::{
+    bytes::AssignedBytes,
+    halo2_proofs::circuit::Value,
+    sha256::{sha256, Sha256Chip, NUM_BYTES_SHA256},
+};
+use ethers_core::types::H256;
+use halo2_base::{
+    gates::GateInstructions, utils::ScalarField, AssignedValue, Context, QuantumCell::Existing,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod tests;
+
+pub type SszChunk = [u8; NUM_BYTES_SHA256];
+
+pub fn hash_pair(left: &SszChunk, right: &SszChunk) -> SszChunk {
+    sha256(&[&left[..], &right[..]].concat())
+}
+
+/// The depth of the node with generalized index `gindex`, which is the length of its Merkle branch.
+pub fn gindex_depth(gindex: u64) -> usize {
+    assert_ne!(gindex, 0, "generalized indices start at 1");
+    gindex.ilog2() as usize
+}
+
+/// The generalized index of node `gindices[1]` of the subtree rooted at node `gindices[0]`, and so on.
+pub fn concat_gindices(gindices: &[u64]) -> u64 {
+    gindices.iter().fold(1, |acc, gindex| {
+        let depth = gindex_depth(*gindex);
+        acc << depth | (gindex ^ (1 << depth))
+    })
+}
+
+/// The roots of the subtrees of zero chunks of depth `0..=depth`.
+pub fn zero_hashes(depth: usize) -> Vec<SszChunk> {
+    let mut zero_hashes = vec![[0u8; NUM_BYTES_SHA256]];
+    for idx in 0..depth {
+        zero_hashes.push(hash_pair(&zero_hashes[idx], &zero_hashes[idx]));
+    }
+    zero_hashes
+}
+
+/// The layers of the Merkle tree of `chunks` padded with zero chunks to `limit`, rounded up to a power of two,
+/// from the leaves to the root. Zero subtrees on the right are omitted.
+fn merkle_layers(chunks: &[SszChunk], limit: usize) -> Vec<Vec<SszChunk>> {
+    assert!(chunks.len() <= limit, "more chunks than the limit");
+    let depth = limit.max(1).next_power_of_two().ilog2() as usize;
+    let zero_hashes = zero_hashes(depth);
+    let mut layers = vec![chunks.to_vec()];
+    for zero_hash in &zero_hashes[..depth] {
+        let layer = layers.last().unwrap();
+        let next = layer
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(zero_hash)))
+            .collect();
+        layers.push(next);
+    }
+    layers
+}
+
+/// The Merkle root of `chunks` padded with zero chunks to `limit`, rounded up to a power of two.
+pub fn merkleize(chunks: &[SszChunk], limit: usize) -> SszChunk {
+    let layers = merkle_layers(chunks, limit);
+    let depth = layers.len() - 1;
+    layers[depth].first().copied().unwrap_or(zero_hashes(depth)[depth])
+}
+
+/// The Merkle branch of `chunks[idx]` in the tree of [`merkleize`]`(chunks, limit)`, from the leaf up to the root.
+pub fn merkle_branch(chunks: &[SszChunk], limit: usize, idx: usize) -> Vec<SszChunk> {
+    let layers = merkle_layers(chunks, limit);
+    let depth = layers.len() - 1;
+    let zero_hashes = zero_hashes(depth);
+    (0..depth)
+        .map(|height| {
+            layers[height].get((idx >> height) ^ 1).copied().unwrap_or(zero_hashes[height])
+        })
+        .collect()
+}
+
+/// The root of an SSZ list, from the root of its chunks and its length.
+pub fn mix_in_length(root: &SszChunk, len: u64) -> SszChunk {
+    let mut len_chunk = [0u8; NUM_BYTES_SHA256];
+    len_chunk[..8].copy_from_slice(&len.to_le_bytes());
+    hash_pair(root, &len_chunk)
+}
+
+/// The root of the Merkle tree in which `leaf` is the node with generalized index `gindex` and `branch` is its
+/// Merkle branch, from the leaf up to the root.
+pub fn branch_root(leaf: &SszChunk, branch: &[SszChunk], gindex: u64) -> SszChunk {
+    assert_eq!(
+        gindex_depth(gindex),
+        branch.len(),
+        "branch length does not match the generalized index"
+    );
+    branch.iter().enumerate().fold(*leaf, |node, (height, sibling)| {
+        if gindex >> height & 1 == 1 {
+            hash_pair(sibling, &node)
+        } else {
+            hash_pair(&node, sibling)
+        }
+    })
+}
+
+/// A Merkle branch of the node with generalized index `gindex`, from the leaf up to the root.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SszBranchInput {
+    pub leaf: H256,
+    pub branch: Vec<H256>,
+    pub gindex: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct AssignedSszBranch<'v, F: ScalarField> {
+    pub leaf: AssignedBytes<'v, F>,
+    pub branch: Vec<AssignedBytes<'v, F>>,
+    pub gindex: AssignedValue<'v, F>,
+}
+
+impl SszBranchInput {
+    pub fn root(&self) -> H256 {
+        let branch: Vec<_> = self.branch.iter().map(|node| node.0).collect();
+        H256(branch_root(&self.leaf.0, &branch, self.gindex))
+    }
+
+    pub fn assign<'v, F: ScalarField>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> AssignedSszBranch<'v, F> {
+        let mut assign_bytes = |bytes: &H256| {
+            gate.assign_witnesses(
+                ctx,
+                bytes.as_bytes().iter().map(|byte| Value::known(F::from(*byte as u64))),
+            )
+        };
+        let leaf = assign_bytes(&self.leaf);
+        let branch = self.branch.iter().map(&mut assign_bytes).collect();
+        let gindex = gate.load_witness(ctx, Value::known(F::from(self.gindex)));
+        AssignedSszBranch { leaf, branch, gindex }
+    }
+}
+
+impl<'v, F: ScalarField> Sha256Chip<'v, F> {
+    /// Returns the root of the Merkle tree in which `proof.leaf` is the node with generalized index `proof.gindex`
+    /// and `proof.branch` is its Merkle branch, from the leaf up to the root.
+    ///
+    /// Constrains `proof.gindex` to be at depth `proof.branch.len()`, i.e. `2^depth <= gindex < 2^(depth + 1)`,
+    /// and the leaf and branch cells to be bytes.
+    ///
+    /// Each level hashes 64 bytes, which costs about 600k cells, see [`crate::sha256`].
+    pub fn ssz_branch_root(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        gate: &impl GateInstructions<F>,
+        proof: &AssignedSszBranch<'v, F>,
+    ) -> AssignedBytes<'v, F> {
+        debug_assert_eq!(proof.leaf.len(), NUM_BYTES_SHA256);
+        let depth = proof.branch.len();
+        let gindex_bits = gate.num_to_bits(ctx, &proof.gindex, depth + 1);
+        gate.assert_is_const(ctx, &gindex_bits[depth], F::one());
+
+        let mut node = proof.leaf.clone();
+        for (sibling, is_right) in proof.branch.iter().zip(gindex_bits.iter()) {
+            debug_assert_eq!(sibling.len(), NUM_BYTES_SHA256);
+            // hash(sibling || node) if the node is a right child, otherwise hash(node || sibling)
+            let mut left = Vec::with_capacity(NUM_BYTES_SHA256);
+            let mut right = Vec::with_capacity(NUM_BYTES_SHA256);
+            for (sibling, node) in sibling.iter().zip(node.iter()) {
+                left.push(gate.select(ctx, Existing(sibling), Existing(node), Existing(is_right)));
+                right.push(gate.select(ctx, Existing(node), Existing(sibling), Existing(is_right)));
+            }
+            left.append(&mut right);
+            let idx = self.sha256_fixed_len(ctx, gate, left, None);
+            node = self.fixed_len_queries[idx].output_assigned.clone();
+        }
+        node
+    }
+
+    /// Constrains `proof` to be a Merkle branch of the tree with root `root`, see
+    /// [`ssz_branch_root`](Self::ssz_branch_root).
+    pub fn verify_ssz_branch(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        gate: &impl GateInstructions<F>,
+        proof: &AssignedSszBranch<'v, F>,
+        root: &[AssignedValue<'v, F>],
+    ) {
+        assert_eq!(root.len(), NUM_BYTES_SHA256);
+        let computed_root = self.ssz_branch_root(ctx, gate, proof);
+        for (a, b) in computed_root.iter().zip(root.iter()) {
+            ctx.constrain_equal(a, b);
+        }
+    }
+}
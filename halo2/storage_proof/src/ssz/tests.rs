@@ -0,0 +1,158 @@
+use super::*;
+use crate::halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner},
+    dev::MockProver,
+    halo2curves::bn256::Fr,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use halo2_base::{
+    gates::{
+        range::{RangeConfig, RangeStrategy},
+        RangeInstructions,
+    },
+    ContextParams, SKIP_FIRST_PASS,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::marker::PhantomData;
+
+const DEGREE: u32 = 18;
+const NUM_CHUNKS: usize = 5;
+const LIMIT: usize = 8;
+/// The deepest branch the tests prove. Each level costs about 600k of the `16 * 2^DEGREE` cells of the circuit.
+const MAX_DEPTH: usize = 3;
+
+/// Computes the root of `proof` in the circuit and constrains it to be `root`.
+#[derive(Clone, Debug)]
+pub struct SszBranchTestCircuit<F> {
+    proof: SszBranchInput,
+    root: SszChunk,
+    _marker: PhantomData<F>,
+}
+
+impl<F: ScalarField> Circuit<F> for SszBranchTestCircuit<F> {
+    type Config = RangeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let mut range = RangeConfig::configure(
+            meta,
+            RangeStrategy::Vertical,
+            &[16],
+            &[1],
+            1,
+            8,
+            0,
+            DEGREE as usize,
+        );
+        range.gate.max_rows = (1 << DEGREE) - meta.minimum_rows();
+        range
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load_lookup_table(&mut layouter).expect("load lookup table should not fail");
+
+        let mut first_pass = SKIP_FIRST_PASS;
+        layouter.assign_region(
+            || "ssz branch test",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let mut aux = Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: config.gate.max_rows,
+                        num_context_ids: 1,
+                        fixed_columns: config.gate.constants.clone(),
+                    },
+                );
+                let ctx = &mut aux;
+                let gate = config.gate();
+
+                let proof = self.proof.assign(ctx, gate);
+                let mut sha256_chip = Sha256Chip::new();
+                let root = sha256_chip.ssz_branch_root(ctx, gate, &proof);
+                for (byte, expected) in root.iter().zip(self.root) {
+                    gate.assert_is_const(ctx, byte, F::from(expected as u64));
+                }
+                config.finalize(ctx);
+                Ok(())
+            },
+        )
+    }
+}
+
+fn test_chunks() -> Vec<SszChunk> {
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    (0..NUM_CHUNKS).map(|_| rng.gen()).collect()
+}
+
+fn branch_circuit(idx: usize) -> SszBranchTestCircuit<Fr> {
+    let chunks = test_chunks();
+    let proof = SszBranchInput {
+        leaf: H256(chunks[idx]),
+        branch: merkle_branch(&chunks, LIMIT, idx).into_iter().map(H256).collect(),
+        gindex: (LIMIT + idx) as u64,
+    };
+    assert!(proof.branch.len() <= MAX_DEPTH);
+    SszBranchTestCircuit { proof, root: merkleize(&chunks, LIMIT), _marker: PhantomData }
+}
+
+#[test]
+pub fn test_native_ssz() {
+    assert_eq!(concat_gindices(&[2, 3]), 5);
+    assert_eq!(concat_gindices(&[3, 1, 6]), 14);
+    assert_eq!(gindex_depth(14), 3);
+
+    let zero = [0u8; NUM_BYTES_SHA256];
+    assert_eq!(merkleize(&[], 4), zero_hashes(2)[2]);
+    assert_eq!(merkleize(&[[1; 32]], 1), [1; 32]);
+    assert_eq!(
+        merkleize(&[[1; 32], [2; 32], [3; 32]], 3),
+        merkleize(&[[1; 32], [2; 32], [3; 32], zero], 4)
+    );
+
+    let chunks = test_chunks();
+    let root = merkleize(&chunks, LIMIT);
+    for idx in 0..NUM_CHUNKS {
+        let branch = merkle_branch(&chunks, LIMIT, idx);
+        assert_eq!(branch_root(&chunks[idx], &branch, (LIMIT + idx) as u64), root);
+    }
+}
+
+#[test]
+pub fn test_mock_ssz_branch() {
+    for idx in [0, 3, 4] {
+        let circuit = branch_circuit(idx);
+        assert_eq!(circuit.proof.root().0, circuit.root);
+        MockProver::run(DEGREE, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+}
+
+#[test]
+pub fn test_mock_ssz_branch_invalid() {
+    // the leaf at another generalized index of the same depth
+    let mut circuit = branch_circuit(3);
+    circuit.proof.gindex = (LIMIT + 2) as u64;
+    assert!(MockProver::run(DEGREE, &circuit, vec![]).unwrap().verify().is_err());
+
+    // a generalized index of the wrong depth with the same low bits
+    let mut circuit = branch_circuit(3);
+    circuit.proof.gindex = (2 * LIMIT + 3) as u64;
+    assert!(MockProver::run(DEGREE, &circuit, vec![]).unwrap().verify().is_err());
+
+    // a tampered sibling
+    let mut circuit = branch_circuit(3);
+    circuit.proof.branch[1].0[0] ^= 1;
+    assert!(MockProver::run(DEGREE, &circuit, vec![]).unwrap().verify().is_err());
+}
@@ -0,0 +1,575 @@
+//! Proofs of the `parentBeaconBlockRoot` (EIP-4788) of a post-Dencun block header, optionally checked against the
+//! storage of the beacon roots contract.
+//!
+//! At the start of each block, a system call stores the block's timestamp at slot
+//! `timestamp % HISTORY_BUFFER_LENGTH` of [`BEACON_ROOTS_ADDRESS`] and its `parentBeaconBlockRoot` at the slot
+//! `HISTORY_BUFFER_LENGTH` after it, so the state root of the block commits to both.
+use super::{
+    EthAccountTraceWitness, EthStorageChip, EthStorageInput, EthStorageTraceWitness,
+    ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN, STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
+};
+use crate::{
+    block_header::{
+        self, EthBlockHeaderChip, BLOCK_NUMBER_MAX_BYTES, GOERLI_BLOCK_HEADER_RLP_MAX_BYTES,
+        MAINNET_BLOCK_HEADER_RLP_MAX_BYTES, TIMESTAMP_MAX_BYTES,
+    },
+    error::InputError,
+    halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        plonk::{Circuit, ConstraintSystem, Error},
+    },
+    mpt::MPTFixedKeyProof,
+    sizing,
+    util::{
+        bytes_be_to_u128, bytes_be_to_uint, bytes_be_var_to_fixed, encode_h256_to_field,
+        uint_to_bytes_be, AssignedH256, EthConfigParams,
+    },
+    EthChip, EthConfig, Field, Network,
+};
+use ethers_core::{
+    types::{Address, H160, H256, U256},
+    utils::keccak256,
+};
+use halo2_base::{
+    gates::{GateInstructions, RangeInstructions},
+    utils::ScalarField,
+    AssignedValue, Context, ContextParams,
+    QuantumCell::{Constant, Existing},
+    SKIP_FIRST_PASS,
+};
+use itertools::Itertools;
+use rlp::Rlp;
+use snark_verifier_sdk::CircuitExt;
+use std::marker::PhantomData;
+
+#[cfg(test)]
+mod tests;
+
+/// `0x000F3df6D732807Ef1319fB7B8bB8522d0Beac02`
+pub const BEACON_ROOTS_ADDRESS: Address = H160([
+    0x00, 0x0f, 0x3d, 0xf6, 0xd7, 0x32, 0x80, 0x7e, 0xf1, 0x31, 0x9f, 0xb7, 0xb8, 0xbb, 0x85, 0x22,
+    0xd0, 0xbe, 0xac, 0x02,
+]);
+/// Number of timestamps, and of roots, kept by the beacon roots contract.
+pub const HISTORY_BUFFER_LENGTH: u64 = 8191;
+
+/// The slots of the beacon roots contract holding the timestamp and the `parentBeaconBlockRoot` of the block with
+/// `timestamp`.
+pub fn beacon_roots_slots(timestamp: u64) -> [H256; 2] {
+    let idx = timestamp % HISTORY_BUFFER_LENGTH;
+    [idx, idx + HISTORY_BUFFER_LENGTH].map(H256::from_low_u64_be)
+}
+
+fn mismatch(field: impl Into<String>, reason: String) -> InputError {
+    InputError::Mismatch { field: field.into(), reason }
+}
+
+/// Checks that `storage` proves two slots of [`BEACON_ROOTS_ADDRESS`], as the circuit expects.
+fn validate_beacon_roots_storage(storage: &EthStorageInput) -> Result<(), InputError> {
+    if storage.addr != BEACON_ROOTS_ADDRESS {
+        return Err(InputError::InvalidField {
+            field: "account.address".to_string(),
+            reason: format!("proof is for {:?}, not the beacon roots contract", storage.addr),
+        });
+    }
+    if storage.storage_pfs.len() != 2 {
+        return Err(InputError::InvalidField {
+            field: "storageProof".to_string(),
+            reason: format!(
+                "expected proofs of the timestamp and root slots, found {} storage proofs",
+                storage.storage_pfs.len()
+            ),
+        });
+    }
+    let (ts_pf, root_pf) = (&storage.storage_pfs[0].2, &storage.storage_pfs[1].2);
+    if ts_pf.max_depth != root_pf.max_depth {
+        return Err(InputError::InvalidField {
+            field: "storageProof[1]".to_string(),
+            reason: format!(
+                "max depth {} differs from the max depth {} of storageProof[0]",
+                root_pf.max_depth, ts_pf.max_depth
+            ),
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct EthBeaconRootInput {
+    pub block_number: u32,
+    pub block_hash: H256,
+    /// RLP encoded header, which must be from after Dencun
+    pub block_header: Vec<u8>,
+    pub timestamp: u32,
+    pub parent_beacon_block_root: H256,
+    /// Proof of [`BEACON_ROOTS_ADDRESS`] against the block's state root and of its [`beacon_roots_slots`], in that
+    /// order. `None` to only prove the header field.
+    pub storage: Option<EthStorageInput>,
+}
+
+impl EthBeaconRootInput {
+    /// Reads the block hash, number, timestamp and `parentBeaconBlockRoot` from the RLP encoded `block_header`.
+    /// Fails if the header is not valid RLP or is from before Dencun.
+    pub fn from_header(
+        block_header: Vec<u8>,
+        storage: Option<EthStorageInput>,
+    ) -> Result<Self, InputError> {
+        let invalid = |field: &str, reason: String| InputError::InvalidField {
+            field: format!("block.{field}"),
+            reason,
+        };
+        let header = Rlp::new(&block_header);
+        let block_number: u32 =
+            header.val_at(8).map_err(|e| invalid("number", format!("invalid header RLP: {e}")))?;
+        let timestamp: u32 = header
+            .val_at(11)
+            .map_err(|e| invalid("timestamp", format!("invalid header RLP: {e}")))?;
+        let parent_beacon_block_root = block_header::parent_beacon_block_root(&block_header)
+            .ok_or_else(|| {
+                invalid("parentBeaconBlockRoot", "header is from before Dencun".to_string())
+            })?;
+        Ok(Self {
+            block_number,
+            block_hash: H256(keccak256(&block_header)),
+            block_header,
+            timestamp,
+            parent_beacon_block_root,
+            storage,
+        })
+    }
+
+    /// Checks that the block header fits the circuit for `network` and, if given, that the proofs are of two slots
+    /// of [`BEACON_ROOTS_ADDRESS`] and can be assigned.
+    pub fn validate(&self, network: Network) -> Result<(), InputError> {
+        let max_len = match network {
+            Network::Goerli => GOERLI_BLOCK_HEADER_RLP_MAX_BYTES,
+            Network::Mainnet => MAINNET_BLOCK_HEADER_RLP_MAX_BYTES,
+        };
+        if self.block_header.len() > max_len {
+            return Err(InputError::HeaderTooLong { len: self.block_header.len(), max_len });
+        }
+        match &self.storage {
+            Some(storage) => {
+                validate_beacon_roots_storage(storage)?;
+                storage.validate()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks everything the circuit constrains: the header hashes to the block hash and has the claimed fields,
+    /// and, if given, the proofs are of the beacon roots contract slots for the timestamp and hold the timestamp
+    /// and the root. Returns the first mismatch.
+    pub fn verify_native(&self) -> Result<(), InputError> {
+        let expected = Self::from_header(self.block_header.clone(), None)?;
+        let fields = [
+            ("block.hash", format!("{:?}", self.block_hash), format!("{:?}", expected.block_hash)),
+            ("block.number", self.block_number.to_string(), expected.block_number.to_string()),
+            ("block.timestamp", self.timestamp.to_string(), expected.timestamp.to_string()),
+            (
+                "block.parentBeaconBlockRoot",
+                format!("{:?}", self.parent_beacon_block_root),
+                format!("{:?}", expected.parent_beacon_block_root),
+            ),
+        ];
+        for (field, claimed, expected) in fields {
+            if claimed != expected {
+                return Err(mismatch(field, format!("header has {expected}, not {claimed}")));
+            }
+        }
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return Ok(()),
+        };
+        validate_beacon_roots_storage(storage)?;
+        let slots = beacon_roots_slots(self.timestamp as u64);
+        let values = [U256::from(self.timestamp), U256::from(self.parent_beacon_block_root.0)];
+        for (i, ((slot, value, _), (expected_slot, expected_value))) in
+            storage.storage_pfs.iter().zip(slots.iter().zip(values.iter())).enumerate()
+        {
+            if slot != expected_slot || value != expected_value {
+                return Err(mismatch(
+                    format!("storageProof[{i}]"),
+                    format!("expected slot {expected_slot:?} with value {expected_value:?}"),
+                ));
+            }
+        }
+        let state_root = Rlp::new(&self.block_header)
+            .at(3)
+            .and_then(|field| field.data().map(|root| root.to_vec()))
+            .map_err(|e| mismatch("block.stateRoot", format!("invalid header RLP: {e}")))?;
+        storage.verify_native(&state_root)
+    }
+
+    pub fn assign<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> EthBeaconRootInputAssigned<'v, F> {
+        let block_hash = encode_h256_to_field(&self.block_hash)
+            .map(|block_hash| gate.load_witness(ctx, Value::known(block_hash)));
+        let storage = self.storage.as_ref().map(|storage| {
+            let acct_pf = storage.acct_pf.assign(ctx, gate);
+            let storage_pfs = storage.storage_pfs.iter().map(|(_, _, pf)| pf.assign(ctx, gate));
+            (acct_pf, storage_pfs.collect())
+        });
+        EthBeaconRootInputAssigned { block_hash, block_header: self.block_header.clone(), storage }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EthBeaconRootInputAssigned<'v, F: Field> {
+    pub block_hash: AssignedH256<'v, F>, // H256 as (u128, u128)
+    pub block_header: Vec<u8>,
+    /// account proof and the proofs of the timestamp and root slots
+    pub storage: Option<(MPTFixedKeyProof<'v, F>, Vec<MPTFixedKeyProof<'v, F>>)>,
+}
+
+/// The shape of an [`EthBeaconRootCircuit`]. Circuits with the same shape have the same verifying key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EthBeaconRootShape {
+    pub network: Network,
+    /// `(acct_pf_max_depth, storage_pf_max_depth)` if the root is checked against the beacon roots contract
+    pub max_depths: Option<(usize, usize)>,
+}
+
+impl EthBeaconRootShape {
+    /// The inputs must be valid, see [`EthBeaconRootInput::validate`]: the storage proof max depth is read from
+    /// the proof of the timestamp slot.
+    pub fn of(inputs: &EthBeaconRootInput, network: Network) -> Self {
+        let max_depths = inputs.storage.as_ref().map(|storage| {
+            let storage_pf_max_depth =
+                storage.storage_pfs.first().map_or(0, |(_, _, pf)| pf.max_depth);
+            (storage.acct_pf.max_depth, storage_pf_max_depth)
+        });
+        Self { network, max_depths }
+    }
+
+    /// Assigns inputs of unknown values with this shape, for keygen.
+    pub fn assign_unknown<'v, F: Field>(
+        &self,
+        ctx: &mut Context<'_, F>,
+        gate: &impl GateInstructions<F>,
+    ) -> EthBeaconRootInputAssigned<'v, F> {
+        // assign in the same order as `EthBeaconRootInput::assign`
+        let block_hash = [(); 2].map(|_| gate.load_witness(ctx, Value::unknown()));
+        let storage = self.max_depths.map(|(acct_pf_max_depth, storage_pf_max_depth)| {
+            let acct_pf = MPTFixedKeyProof::assign_unknown(
+                ctx,
+                gate,
+                ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
+                acct_pf_max_depth,
+            );
+            let storage_pfs = (0..2)
+                .map(|_| {
+                    MPTFixedKeyProof::assign_unknown(
+                        ctx,
+                        gate,
+                        STORAGE_PROOF_VALUE_MAX_BYTE_LEN,
+                        storage_pf_max_depth,
+                    )
+                })
+                .collect();
+            (acct_pf, storage_pfs)
+        });
+        let block_header_len = match self.network {
+            Network::Goerli => GOERLI_BLOCK_HEADER_RLP_MAX_BYTES,
+            Network::Mainnet => MAINNET_BLOCK_HEADER_RLP_MAX_BYTES,
+        };
+        EthBeaconRootInputAssigned { block_hash, block_header: vec![0; block_header_len], storage }
+    }
+}
+
+/// The public outputs of [`EthChip::parse_beacon_root_phase0`].
+#[derive(Clone, Debug)]
+pub struct EthBeaconRootDigest<'v, F: Field> {
+    pub block_hash: AssignedH256<'v, F>,
+    pub block_number: AssignedValue<'v, F>,
+    pub timestamp: AssignedValue<'v, F>,
+    pub parent_beacon_block_root: AssignedH256<'v, F>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EthBeaconRootTraceWitness<'v, F: Field> {
+    pub block_witness: block_header::EthBlockHeaderTraceWitness<'v, F>,
+    storage_witness: Option<(EthAccountTraceWitness<'v, F>, Vec<EthStorageTraceWitness<'v, F>>)>,
+    pub digest: EthBeaconRootDigest<'v, F>,
+}
+
+impl<'v, F: Field> EthChip<'v, F> {
+    /// Decomposes the block header, checks that it hashes to `input.block_hash` and reads its
+    /// `parentBeaconBlockRoot`, constraining the header to be from after Dencun. If `input.storage` is given, also
+    /// checks that the block's state stores the timestamp and the root in the [`beacon_roots_slots`] of the
+    /// timestamp.
+    pub fn parse_beacon_root_phase0(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        input: EthBeaconRootInputAssigned<'v, F>,
+        network: Network,
+    ) -> EthBeaconRootTraceWitness<'v, F> {
+        let block_hash = input.block_hash;
+        let block_hash_bytes0 =
+            block_hash.iter().map(|u128| uint_to_bytes_be(ctx, self.range(), u128, 16)).concat();
+        let mut block_header = input.block_header;
+        let max_len = match network {
+            Network::Goerli => GOERLI_BLOCK_HEADER_RLP_MAX_BYTES,
+            Network::Mainnet => MAINNET_BLOCK_HEADER_RLP_MAX_BYTES,
+        };
+        block_header.resize(max_len, 0);
+        let block_witness = self.decompose_block_header_phase0(ctx, &block_header, network);
+        let block_hash_bytes1 =
+            &self.keccak().var_len_queries[block_witness.block_hash_query_idx].output_assigned;
+        for (byte0, byte1) in block_hash_bytes0.iter().zip(block_hash_bytes1.iter()) {
+            ctx.constrain_equal(byte0, byte1);
+        }
+
+        // block number and timestamp from big-endian bytes
+        let [block_number, timestamp] = [(8, BLOCK_NUMBER_MAX_BYTES), (11, TIMESTAMP_MAX_BYTES)]
+            .map(|(idx, max_bytes)| {
+                let field = &block_witness.rlp_witness.field_witness[idx];
+                let bytes = bytes_be_var_to_fixed(
+                    ctx,
+                    self.gate(),
+                    &field.field_cells,
+                    &field.field_len,
+                    max_bytes,
+                );
+                bytes_be_to_uint(ctx, self.gate(), &bytes, max_bytes)
+            });
+        let root_bytes = block_witness.parent_beacon_block_root(ctx, self.gate()).to_vec();
+        let parent_beacon_block_root: AssignedH256<F> =
+            bytes_be_to_u128(ctx, self.gate(), &root_bytes).try_into().unwrap();
+
+        let storage_witness = input.storage.map(|(acct_pf, storage_pfs)| {
+            assert_eq!(storage_pfs.len(), 2, "expected proofs of the timestamp and root slots");
+            // timestamp = HISTORY_BUFFER_LENGTH * quotient + idx with idx < HISTORY_BUFFER_LENGTH
+            let ts = timestamp.value().map(|ts| ts.get_lower_32() as u64);
+            let quotient =
+                self.gate().load_witness(ctx, ts.map(|ts| F::from(ts / HISTORY_BUFFER_LENGTH)));
+            let idx =
+                self.gate().load_witness(ctx, ts.map(|ts| F::from(ts % HISTORY_BUFFER_LENGTH)));
+            self.range().range_check(ctx, &quotient, 8 * TIMESTAMP_MAX_BYTES);
+            self.range().check_less_than_safe(ctx, &idx, HISTORY_BUFFER_LENGTH);
+            let ts = self.gate().mul_add(
+                ctx,
+                Existing(&quotient),
+                Constant(F::from(HISTORY_BUFFER_LENGTH)),
+                Existing(&idx),
+            );
+            ctx.constrain_equal(&ts, &timestamp);
+            let root_idx =
+                self.gate().add(ctx, Existing(&idx), Constant(F::from(HISTORY_BUFFER_LENGTH)));
+
+            let state_root = &block_witness.rlp_witness.field_witness[3].field_cells;
+            let addr_bytes = BEACON_ROOTS_ADDRESS
+                .0
+                .iter()
+                .map(|byte| self.gate().load_constant(ctx, F::from(*byte as u64)))
+                .collect();
+            let acct_witness =
+                self.parse_account_proof_phase0(ctx, state_root, addr_bytes, acct_pf);
+            let storage_root = &acct_witness.array_witness.field_witness[2].field_cells;
+
+            let storage_witness = [idx, root_idx]
+                .into_iter()
+                .zip(storage_pfs)
+                .map(|(slot, storage_pf)| {
+                    // slots are < 2 * HISTORY_BUFFER_LENGTH < 2^16
+                    let mut slot_bytes = (0..30).map(|_| self.gate().load_zero(ctx)).collect_vec();
+                    slot_bytes.extend(uint_to_bytes_be(ctx, self.range(), &slot, 2));
+                    self.parse_storage_proof_phase0(ctx, storage_root, slot_bytes, storage_pf)
+                })
+                .collect_vec();
+            // the values are RLP encoded without leading zeros
+            let [ts_value, root_value] = [0, 1].map(|i| {
+                let value = &storage_witness[i].value_witness.witness;
+                bytes_be_var_to_fixed(ctx, self.gate(), &value.field_cells, &value.field_len, 32)
+            });
+            for byte in &ts_value[..32 - TIMESTAMP_MAX_BYTES] {
+                self.gate().assert_is_const(ctx, byte, F::zero());
+            }
+            let ts_value = bytes_be_to_uint(
+                ctx,
+                self.gate(),
+                &ts_value[32 - TIMESTAMP_MAX_BYTES..],
+                TIMESTAMP_MAX_BYTES,
+            );
+            ctx.constrain_equal(&ts_value, &timestamp);
+            for (value_byte, root_byte) in root_value.iter().zip(root_bytes.iter()) {
+                ctx.constrain_equal(value_byte, root_byte);
+            }
+            (acct_witness, storage_witness)
+        });
+
+        EthBeaconRootTraceWitness {
+            block_witness,
+            storage_witness,
+            digest: EthBeaconRootDigest {
+                block_hash,
+                block_number,
+                timestamp,
+                parent_beacon_block_root,
+            },
+        }
+    }
+
+    pub fn parse_beacon_root_phase1(
+        &mut self,
+        ctx: &mut Context<'v, F>,
+        witness: EthBeaconRootTraceWitness<'v, F>,
+    ) -> EthBeaconRootDigest<'v, F> {
+        self.decompose_block_header_phase1(ctx, witness.block_witness);
+        if let Some(storage_witness) = witness.storage_witness {
+            self.parse_eip1186_proofs_phase1(ctx, storage_witness);
+        }
+        witness.digest
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EthBeaconRootCircuit<F> {
+    /// `None` for the witness-free circuit used for keygen
+    pub inputs: Option<EthBeaconRootInput>,
+    shape: EthBeaconRootShape,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> EthBeaconRootCircuit<F> {
+    /// # Panics
+    /// If the inputs are invalid, see [`EthBeaconRootCircuit::try_new`].
+    pub fn new(inputs: EthBeaconRootInput, network: Network) -> Self {
+        Self::try_new(inputs, network).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Fails if the inputs do not fit the circuit, see [`EthBeaconRootInput::validate`].
+    pub fn try_new(inputs: EthBeaconRootInput, network: Network) -> Result<Self, InputError> {
+        inputs.validate(network)?;
+        let shape = EthBeaconRootShape::of(&inputs, network);
+        Ok(Self { inputs: Some(inputs), shape, _marker: PhantomData })
+    }
+
+    /// The witness-free circuit of `shape`, see [`Circuit::without_witnesses`].
+    pub fn from_shape(shape: EthBeaconRootShape) -> Self {
+        Self { inputs: None, shape, _marker: PhantomData }
+    }
+
+    pub fn shape(&self) -> EthBeaconRootShape {
+        self.shape
+    }
+
+    pub fn get_num_instance() -> usize {
+        6
+    }
+
+    // blockHash, blockNumber, timestamp, parentBeaconBlockRoot
+    // with H256 encoded as hi-lo (u128, u128)
+    pub fn instance(&self) -> Vec<F> {
+        let inputs = self.inputs.as_ref().expect("witness-free circuit has no inputs");
+        let mut instance = Vec::with_capacity(Self::get_num_instance());
+        instance.extend(encode_h256_to_field::<F>(&inputs.block_hash));
+        instance.push(F::from(inputs.block_number as u64));
+        instance.push(F::from(inputs.timestamp as u64));
+        instance.extend(encode_h256_to_field::<F>(&inputs.parent_beacon_block_root));
+        instance
+    }
+
+    /// The default configuration parameters, the same as the storage circuit's, see
+    /// [`EthBlockStorageCircuit::config_params`](super::EthBlockStorageCircuit::config_params).
+    pub fn config_params() -> EthConfigParams {
+        super::EthBlockStorageCircuit::<F>::config_params()
+    }
+}
+
+impl<F: Field> Circuit<F> for EthBeaconRootCircuit<F> {
+    type Config = EthConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::from_shape(self.shape)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let params = EthConfigParams::current()
+            .expect("configure must be called inside `EthConfigParams::scope`");
+        EthConfig::configure(meta, params, 0)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let gamma = layouter.get_challenge(config.rlc().gamma);
+        config.range().load_lookup_table(&mut layouter).expect("load range lookup table");
+        config.load_keccak_aux_tables(&mut layouter).expect("load keccak lookup tables");
+
+        let mut first_pass = SKIP_FIRST_PASS;
+        let mut instance = vec![];
+        layouter
+            .assign_region(
+                || "parentBeaconBlockRoot from blockHash",
+                |region| {
+                    if first_pass {
+                        first_pass = false;
+                        return Ok(());
+                    }
+                    let mut chip = EthChip::new(config.clone(), gamma);
+                    let mut aux = Context::new(
+                        region,
+                        ContextParams {
+                            max_rows: chip.gate().max_rows,
+                            num_context_ids: 2,
+                            fixed_columns: chip.gate().constants.clone(),
+                        },
+                    );
+                    let ctx = &mut aux;
+
+                    // ================= FIRST PHASE ================
+                    let input = match &self.inputs {
+                        Some(inputs) => inputs.assign(ctx, chip.gate()),
+                        None => self.shape.assign_unknown(ctx, chip.gate()),
+                    };
+                    let witness = chip.parse_beacon_root_phase0(ctx, input, self.shape.network);
+                    chip.assign_phase0(ctx);
+                    ctx.next_phase();
+
+                    // ================= SECOND PHASE ================
+                    chip.get_challenge(ctx);
+                    chip.keccak_assign_phase1(ctx);
+
+                    let EthBeaconRootDigest {
+                        block_hash,
+                        block_number,
+                        timestamp,
+                        parent_beacon_block_root,
+                    } = chip.parse_beacon_root_phase1(ctx, witness);
+                    sizing::record_phase(ctx);
+                    chip.range().finalize(ctx);
+
+                    instance.extend(
+                        block_hash
+                            .iter()
+                            .chain([block_number, timestamp].iter())
+                            .chain(parent_beacon_block_root.iter())
+                            .map(|acell| acell.cell().clone()),
+                    );
+                    Ok(())
+                },
+            )
+            .unwrap();
+        for (i, cell) in instance.into_iter().enumerate() {
+            layouter.constrain_instance(cell, config.instance, i);
+        }
+        Ok(())
+    }
+}
+
+impl<F: Field> CircuitExt<F> for EthBeaconRootCircuit<F> {
+    fn num_instance(&self) -> Vec<usize> {
+        vec![Self::get_num_instance()]
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        vec![self.instance()]
+    }
+}
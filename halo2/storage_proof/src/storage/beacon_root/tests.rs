@@ -0,0 +1,231 @@
+use super::*;
+use crate::{
+    halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr},
+    mpt::trie::Trie,
+    sizing::SizingOptions,
+};
+use hex::FromHex;
+use rlp::RlpStream;
+
+// mainnet block 15_655_494, from before Shanghai
+const LONDON_HEADER_HEX: &str = "f90201a0d7519abd494a823b2c9c28908eaf250fe4a6287d747f1cc53a5a193b6533a549a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347944675c7e5baafbffbca748158becba61ef3b0a263a025000d51f040ee5c473fed74eda9ace87d55a35187b11bcde6f5176025c395bfa0a5800a6de6d28d7425ff72714af2af769b9f8f9e1baf56fb42f793fbb40fde07a056e1062a3dc63791e8a8496837606b14062da70ee69178cea97d6eeb5047550cb9010000236420014dc00423903000840002280080282100004704018340c0241c20011211400426000f900001d8088000011006020002ce98bc00c0000020c9a02040000688040200348c3a0082b81402002814922008085d008008200802802c4000130000101703124801400400018008a6108002020420144011200070020bc0202681810804221304004800088600300000040463614a000e200201c00611c0008e800b014081608010a0218a0b410010082000428209080200f50260a00840006700100f40a000000400000448301008c4a00341040e343500800d06250020010215200c008018002c88350404000bc5000a8000210c00724a0d0a4010210a448083eee2468401c9c3808343107884633899e780a07980d8d1f15474c9185e4d1cef5f207167735009daad2eb6af6da37ffba213c28800000000000000008501e08469e6";
+
+fn london_header() -> Vec<u8> {
+    Vec::from_hex(LONDON_HEADER_HEX).unwrap()
+}
+
+/// The London header with the fields added by Shanghai and Dencun appended, so not a real mainnet header.
+fn dencun_header(parent_beacon_block_root: H256) -> Vec<u8> {
+    let london_header = london_header();
+    let mut stream = RlpStream::new_list(20);
+    for field in Rlp::new(&london_header).iter() {
+        stream.append_raw(field.as_raw(), 1);
+    }
+    stream.append(&H256::repeat_byte(0x56).as_bytes()); // withdrawalsRoot
+    stream.append(&0x20000u64); // blobGasUsed
+    stream.append(&0u64); // excessBlobGas
+    stream.append(&parent_beacon_block_root.as_bytes());
+    stream.out().to_vec()
+}
+
+/// `header` with its `stateRoot` replaced by `state_root`.
+fn with_state_root(header: &[u8], state_root: H256) -> Vec<u8> {
+    let fields = Rlp::new(header).iter().collect_vec();
+    let mut stream = RlpStream::new_list(fields.len());
+    for (i, field) in fields.into_iter().enumerate() {
+        if i == 3 {
+            stream.append(&state_root.as_bytes());
+        } else {
+            stream.append_raw(field.as_raw(), 1);
+        }
+    }
+    stream.out().to_vec()
+}
+
+/// A Dencun header whose state has the beacon roots contract storing `stored_root` for the header's timestamp,
+/// with the proofs of `proven_slots(beacon_roots_slots(timestamp))`. The state and storage tries have a few other
+/// accounts and slots so that the proofs go through branch nodes.
+fn storage_circuit(
+    stored_root: H256,
+    proven_slots: impl FnOnce([H256; 2]) -> [H256; 2],
+) -> EthBeaconRootCircuit<Fr> {
+    const MAX_DEPTH: usize = 4;
+    let root = H256::from_low_u64_be(0xbeac02);
+    let header = dencun_header(root);
+    let timestamp = EthBeaconRootInput::from_header(header.clone(), None).unwrap().timestamp as u64;
+    let [ts_slot, root_slot] = beacon_roots_slots(timestamp);
+
+    // the next slot also holds the timestamp, so that only the slot is wrong when it is proven instead
+    let mut slot_values =
+        vec![(ts_slot, U256::from(timestamp)), (root_slot, U256::from(stored_root.0))];
+    slot_values.extend((1..8).map(|i| {
+        let idx = timestamp % HISTORY_BUFFER_LENGTH + i;
+        (H256::from_low_u64_be(idx), U256::from(timestamp + 12 * (i - 1)))
+    }));
+    let mut storage = Trie::new();
+    for (slot, value) in &slot_values {
+        storage.insert(&keccak256(slot), rlp::encode(value).to_vec());
+    }
+    let storage_pfs = proven_slots([ts_slot, root_slot])
+        .into_iter()
+        .map(|slot| {
+            let (_, value) = slot_values.iter().find(|(s, _)| *s == slot).unwrap();
+            let pf = storage
+                .fixed_key_input(H256(keccak256(slot)), STORAGE_PROOF_VALUE_MAX_BYTE_LEN, MAX_DEPTH)
+                .unwrap();
+            (slot, *value, pf)
+        })
+        .collect();
+
+    let account = |storage_root: H256| {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&1u64).append(&0u64).append(&storage_root).append(&H256(keccak256(b"")));
+        stream.out().to_vec()
+    };
+    let mut state = Trie::new();
+    state.insert(&keccak256(BEACON_ROOTS_ADDRESS), account(storage.root()));
+    for i in 1..=16 {
+        state.insert(&keccak256(Address::from_low_u64_be(i)), account(Trie::new().root()));
+    }
+    let acct_pf = state
+        .fixed_key_input(
+            H256(keccak256(BEACON_ROOTS_ADDRESS)),
+            ACCOUNT_PROOF_VALUE_MAX_BYTE_LEN,
+            MAX_DEPTH,
+        )
+        .unwrap();
+
+    let storage = EthStorageInput::new(BEACON_ROOTS_ADDRESS, acct_pf, storage_pfs);
+    let header = with_state_root(&header, state.root());
+    let inputs = EthBeaconRootInput::from_header(header, Some(storage)).unwrap();
+    EthBeaconRootCircuit::new(inputs, Network::Mainnet)
+}
+
+/// Whether `circuit` is satisfied, with config params sized for it.
+fn is_satisfied(circuit: &EthBeaconRootCircuit<Fr>) -> bool {
+    let config = sizing::size_circuit(
+        circuit,
+        circuit.instances(),
+        EthBeaconRootCircuit::<Fr>::config_params(),
+        &SizingOptions::default(),
+    );
+    let _params = config.enter();
+    MockProver::run(config.degree, circuit, circuit.instances()).unwrap().verify().is_ok()
+}
+
+fn header_only_circuit() -> EthBeaconRootCircuit<Fr> {
+    let root = H256::from_low_u64_be(0xbeac02);
+    let inputs = EthBeaconRootInput::from_header(dencun_header(root), None).unwrap();
+    EthBeaconRootCircuit::new(inputs, Network::Mainnet)
+}
+
+#[test]
+pub fn test_beacon_root_native() {
+    let root = H256::repeat_byte(0xbe);
+    let inputs = EthBeaconRootInput::from_header(dencun_header(root), None).unwrap();
+    assert_eq!(inputs.parent_beacon_block_root, root);
+    assert_eq!((inputs.block_number, inputs.timestamp), (15_655_494, 0x633899e7));
+    assert!(inputs.verify_native().is_ok());
+
+    let wrong_root = EthBeaconRootInput { parent_beacon_block_root: H256::zero(), ..inputs };
+    assert!(matches!(wrong_root.verify_native(), Err(InputError::Mismatch { .. })));
+    assert!(matches!(
+        EthBeaconRootInput::from_header(london_header(), None),
+        Err(InputError::InvalidField { .. })
+    ));
+
+    let [ts_slot, root_slot] = beacon_roots_slots(2 * HISTORY_BUFFER_LENGTH + 5);
+    assert_eq!((ts_slot.to_low_u64_be(), root_slot.to_low_u64_be()), (5, 8196));
+}
+
+#[test]
+pub fn test_mock_beacon_root() {
+    let circuit = header_only_circuit();
+    let config = EthBeaconRootCircuit::<Fr>::config_params();
+    let _params = config.enter();
+    MockProver::run(config.degree, &circuit, circuit.instances()).unwrap().assert_satisfied();
+}
+
+#[test]
+pub fn test_mock_beacon_root_wrong_root() {
+    let mut circuit = header_only_circuit();
+    let inputs = circuit.inputs.as_mut().unwrap();
+    inputs.parent_beacon_block_root = H256::repeat_byte(0xbe);
+    let config = EthBeaconRootCircuit::<Fr>::config_params();
+    let _params = config.enter();
+    assert!(MockProver::run(config.degree, &circuit, circuit.instances())
+        .unwrap()
+        .verify()
+        .is_err());
+}
+
+#[test]
+pub fn test_mock_beacon_root_before_dencun() {
+    // the header has no `parentBeaconBlockRoot` field, which the circuit must reject whatever root is claimed
+    let dencun = EthBeaconRootInput::from_header(dencun_header(H256::zero()), None).unwrap();
+    let block_header = london_header();
+    let inputs =
+        EthBeaconRootInput { block_hash: H256(keccak256(&block_header)), block_header, ..dencun };
+    let circuit = EthBeaconRootCircuit::<Fr>::new(inputs, Network::Mainnet);
+    let config = EthBeaconRootCircuit::<Fr>::config_params();
+    let _params = config.enter();
+    assert!(MockProver::run(config.degree, &circuit, circuit.instances())
+        .unwrap()
+        .verify()
+        .is_err());
+}
+
+#[test]
+pub fn test_beacon_root_validate() {
+    let inputs = storage_circuit(H256::from_low_u64_be(0xbeac02), |slots| slots).inputs.unwrap();
+    assert!(inputs.verify_native().is_ok());
+
+    let mut wrong_addr = inputs.clone();
+    wrong_addr.storage.as_mut().unwrap().addr = Address::from_low_u64_be(1);
+    assert!(matches!(wrong_addr.validate(Network::Mainnet), Err(InputError::InvalidField { .. })));
+
+    let mut different_depths = inputs.clone();
+    different_depths.storage.as_mut().unwrap().storage_pfs[1].2.max_depth += 1;
+    assert!(matches!(
+        different_depths.validate(Network::Mainnet),
+        Err(InputError::InvalidField { .. })
+    ));
+    assert!(different_depths.verify_native().is_err());
+
+    let mut one_slot = inputs;
+    let storage = one_slot.storage.as_mut().unwrap();
+    storage.storage_pfs.truncate(1);
+    storage.num_slots = 1;
+    assert!(matches!(one_slot.validate(Network::Mainnet), Err(InputError::InvalidField { .. })));
+    assert!(EthBeaconRootCircuit::<Fr>::try_new(one_slot, Network::Mainnet).is_err());
+}
+
+#[test]
+pub fn test_mock_beacon_root_storage() {
+    let circuit = storage_circuit(H256::from_low_u64_be(0xbeac02), |slots| slots);
+    assert!(is_satisfied(&circuit));
+}
+
+#[test]
+pub fn test_mock_beacon_root_storage_wrong_slot() {
+    // the proof of the next slot, which holds the same timestamp
+    let circuit = storage_circuit(H256::from_low_u64_be(0xbeac02), |[ts_slot, root_slot]| {
+        [H256::from_low_u64_be(ts_slot.to_low_u64_be() + 1), root_slot]
+    });
+    assert!(matches!(
+        circuit.inputs.as_ref().unwrap().verify_native(),
+        Err(InputError::Mismatch { .. })
+    ));
+    assert!(!is_satisfied(&circuit));
+}
+
+#[test]
+pub fn test_mock_beacon_root_storage_wrong_root() {
+    // the contract stores a different root than the header's `parentBeaconBlockRoot`
+    let circuit = storage_circuit(H256::repeat_byte(0xbe), |slots| slots);
+    assert!(matches!(
+        circuit.inputs.as_ref().unwrap().verify_native(),
+        Err(InputError::Mismatch { .. })
+    ));
+    assert!(!is_satisfied(&circuit));
+}
@@ -0,0 +1,122 @@
+//! On-chain verification of storage proofs.
+//!
+//! The storage snark is wrapped in a `PublicAggregationCircuit` (as in the header chain pipeline) whose proof is
+//! verified by a generated Yul contract. [`deploy_and_call`] runs that contract in an embedded EVM, so the
+//! contract can be checked for gas and for accepting/rejecting proofs without a node.
+use super::EthBlockStorageCircuit;
+use crate::{
+    block_header::aggregation::PublicAggregationCircuitWithParams,
+    keystore::{CircuitFingerprint, KeyStore},
+    util::{with_circuit_params, EthConfigParams},
+};
+use ethers_core::utils::hex;
+use halo2_base::halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr},
+    plonk::Circuit,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use rand::Rng;
+use snark_verifier::loader::evm::{encode_calldata, Address, ExecutorBuilder};
+use snark_verifier_sdk::{
+    evm::{gen_evm_proof_shplonk, gen_evm_verifier_shplonk},
+    halo2::{
+        aggregation::{AggregationConfigParams, PublicAggregationCircuit},
+        gen_snark_shplonk,
+    },
+    CircuitExt,
+};
+use std::{fs, io, path::Path};
+
+#[derive(Clone, Debug)]
+pub struct StorageEvmArtifacts {
+    /// Bytecode that deploys the verifier contract.
+    pub deployment_code: Vec<u8>,
+    /// Public instances of the aggregation circuit: the accumulator limbs followed by the storage circuit instances.
+    pub instances: Vec<Fr>,
+    pub proof: Vec<u8>,
+    /// ABI-encoded `instances || proof`, to be sent to the verifier contract.
+    pub calldata: Vec<u8>,
+}
+
+impl StorageEvmArtifacts {
+    /// Writes `storage.yul` (done by [`gen_storage_evm_artifacts`]), `storage.bytecode` and `storage.calldata`
+    /// as hex strings to `dir`.
+    pub fn write(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("storage.bytecode"), hex::encode(&self.deployment_code))?;
+        fs::write(dir.join("storage.calldata"), hex::encode(&self.calldata))
+    }
+}
+
+/// Proves `circuit`, aggregates the proof into a `PublicAggregationCircuit` and generates the verifier contract
+/// for it, writing the Yul source to `yul_path`.
+///
+/// `storage_config` configures the storage circuit, see [`EthBlockStorageCircuit::sized_config_params`], and
+/// `storage_params` must be the params of its degree. `evm_params` must be those of `evm_config.degree`, where
+/// `evm_config` configures the aggregation circuit.
+#[allow(clippy::too_many_arguments)]
+pub fn gen_storage_evm_artifacts(
+    storage_params: &ParamsKZG<Bn256>,
+    storage_config: &EthConfigParams,
+    evm_params: &ParamsKZG<Bn256>,
+    evm_config: &AggregationConfigParams,
+    circuit: EthBlockStorageCircuit<Fr>,
+    keystore: &KeyStore,
+    yul_path: Option<&Path>,
+    rng: &mut (impl Rng + Send),
+) -> StorageEvmArtifacts {
+    assert_eq!(storage_params.k(), storage_config.degree);
+    let fingerprint = circuit.fingerprint(storage_config);
+    let snark = storage_config.scope(|| {
+        let pk = keystore.get_or_gen_pk(storage_params, &circuit.without_witnesses(), &fingerprint);
+        gen_snark_shplonk(storage_params, &pk, circuit, rng, None::<&str>)
+    });
+
+    with_circuit_params(evm_config, || {
+        let evm_circuit = PublicAggregationCircuitWithParams(PublicAggregationCircuit::new(
+            evm_params,
+            vec![snark],
+            false,
+            rng,
+        ));
+        let evm_fingerprint =
+            CircuitFingerprint::new(format!("{}_for_evm", fingerprint.id()), evm_params.k())
+                .config(evm_config);
+        let pk = keystore.get_or_gen_pk(evm_params, &evm_circuit, &evm_fingerprint);
+        let instances = evm_circuit.instances();
+        let num_instances = instances[0].len();
+        let proof = gen_evm_proof_shplonk(evm_params, &pk, evm_circuit, instances.clone(), rng);
+        let calldata = encode_calldata(&instances, &proof);
+        let deployment_code = gen_evm_verifier_shplonk::<PublicAggregationCircuitWithParams>(
+            evm_params,
+            pk.get_vk(),
+            vec![num_instances],
+            yul_path,
+        );
+        let instances = instances.into_iter().next().unwrap();
+        StorageEvmArtifacts { deployment_code, instances, proof, calldata }
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvmCallOutcome {
+    /// Whether the verifier rejected the calldata.
+    pub reverted: bool,
+    pub gas_used: u64,
+}
+
+/// Deploys `deployment_code` in a fresh embedded EVM and calls the deployed contract with `calldata`.
+///
+/// # Panics
+/// If the deployment itself fails.
+pub fn deploy_and_call(deployment_code: Vec<u8>, calldata: Vec<u8>) -> EvmCallOutcome {
+    let mut evm = ExecutorBuilder::default().with_gas_limit(u64::MAX.into()).build();
+    let caller = Address::from_low_u64_be(0xfe);
+    let verifier = evm
+        .deploy(caller, deployment_code.into(), 0.into())
+        .address
+        .expect("verifier contract deployment failed");
+    let result = evm.call_raw(caller, verifier, calldata.into(), 0.into());
+    EvmCallOutcome { reverted: result.reverted, gas_used: result.gas_used }
+}
@@ -0,0 +1,91 @@
+//! Out-of-circuit verification of [`EthBlockStorageInput`], so that inputs the circuit cannot prove are rejected in
+//! milliseconds instead of at the end of proving.
+use super::{EthBlockStorageInput, EthStorageInput};
+use crate::error::InputError;
+use ethers_core::{
+    types::{H256, U256},
+    utils::keccak256,
+};
+use rlp::{Encodable, Rlp};
+
+fn mismatch(field: impl Into<String>, reason: String) -> InputError {
+    InputError::Mismatch { field: field.into(), reason }
+}
+
+impl EthBlockStorageInput {
+    /// Checks everything the storage circuit constrains: the block hash is the hash of the header, the account
+    /// proof verifies against the header's state root, and each storage proof verifies against the account's
+    /// storage root. Returns the first mismatch.
+    pub fn verify_native(&self) -> Result<(), InputError> {
+        let block_hash = H256(keccak256(&self.block_header));
+        if block_hash != self.block_hash {
+            return Err(mismatch(
+                "block.hash",
+                format!("header hashes to {block_hash:?}, expected {:?}", self.block_hash),
+            ));
+        }
+        let header = Rlp::new(&self.block_header);
+        let header_field = |idx: usize, name: &str| {
+            header
+                .at(idx)
+                .and_then(|field| field.data())
+                .map_err(|e| mismatch(format!("block.{name}"), format!("invalid header RLP: {e}")))
+        };
+        let state_root = header_field(3, "stateRoot")?;
+        let number = header_field(8, "number")?;
+        if U256::from_big_endian(number) != U256::from(self.block_number) {
+            return Err(mismatch(
+                "block.number",
+                format!("header has block number {}", U256::from_big_endian(number)),
+            ));
+        }
+        self.storage.verify_native(state_root)
+    }
+}
+
+impl EthStorageInput {
+    /// Checks that the account proof verifies against `state_root` and that each storage proof verifies against
+    /// the account's storage root. Returns the first mismatch.
+    pub fn verify_native(&self, state_root: &[u8]) -> Result<(), InputError> {
+        let EthStorageInput { addr, acct_pf, storage_pfs, .. } = self;
+        if acct_pf.path != H256(keccak256(addr)) {
+            return Err(mismatch("account.address", format!("proof is not for address {addr:?}")));
+        }
+        if acct_pf.root_hash.as_bytes() != state_root {
+            return Err(mismatch(
+                "accountProof",
+                format!("proof is for state root {:?}, not the header's", acct_pf.root_hash),
+            ));
+        }
+        acct_pf.verify_native().map_err(|e| InputError::proof("accountProof", e))?;
+        // the account is RLP([nonce, balance, storage_root, code_hash])
+        let storage_root = Rlp::new(&acct_pf.value)
+            .at(2)
+            .and_then(|field| field.data())
+            .map_err(|e| mismatch("account.storageHash", format!("invalid account RLP: {e}")))?;
+
+        for (i, (slot, value, pf)) in storage_pfs.iter().enumerate() {
+            let proof = format!("storageProof[{i}]");
+            if pf.path != H256(keccak256(slot)) {
+                return Err(mismatch(
+                    format!("{proof}.key"),
+                    format!("proof is not for slot {slot:?}"),
+                ));
+            }
+            if pf.root_hash.as_bytes() != storage_root {
+                return Err(mismatch(
+                    proof,
+                    format!("proof is for storage root {:?}, not the account's", pf.root_hash),
+                ));
+            }
+            if pf.value != value.rlp_bytes().to_vec() {
+                return Err(mismatch(
+                    format!("{proof}.value"),
+                    format!("proof is not for value {value:?}"),
+                ));
+            }
+            pf.verify_native().map_err(|e| InputError::proof(proof, e))?;
+        }
+        Ok(())
+    }
+}
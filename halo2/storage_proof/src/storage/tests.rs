@@ -14,7 +14,9 @@ use crate::{
             Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
         },
     },
+    block_header::aggregation::PublicAggregationCircuitWithParams,
     providers::{GOERLI_PROVIDER_URL, MAINNET_PROVIDER_URL},
+    util::{enter_circuit_params, read_config},
     Network,
 };
 use ark_std::{end_timer, start_timer};
@@ -24,7 +26,6 @@ use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use snark_verifier_sdk::halo2::aggregation::AggregationConfigParams;
 use std::{
-    env::set_var,
     fs::{self, File},
     io::{BufReader, Write},
 };
@@ -75,14 +76,240 @@ fn get_test_circuit<F: Field>(network: Network, num_slots: usize) -> EthBlockSto
 
 #[test]
 pub fn test_mock_single_eip1186() -> Result<(), Box<dyn std::error::Error>> {
-    set_var("STORAGE_CONFIG", "configs/tests/storage.json");
-    let k = EthConfigParams::get_storage().degree;
+    let config = EthConfigParams::from_path("configs/tests/storage.json");
+    let _params = config.enter();
+    let k = config.degree;
 
     let circuit = get_test_circuit::<Fr>(Network::Mainnet, 1);
     MockProver::run(k, &circuit, vec![circuit.instance()]).unwrap().assert_satisfied();
     Ok(())
 }
 
+#[test]
+pub fn test_mock_sized_config_params() {
+    let circuit = get_test_circuit::<Fr>(Network::Mainnet, 4);
+    let config = circuit.sized_config_params(&SizingOptions::default());
+    let _params = config.enter();
+    MockProver::run(config.degree, &circuit, vec![circuit.instance()]).unwrap().assert_satisfied();
+}
+
+#[test]
+pub fn test_padded_slots_share_vk() {
+    let max_slots = 3;
+    let circuits = [1, 3].map(|num_slots| {
+        get_test_circuit::<Fr>(Network::Mainnet, num_slots).with_max_slots(max_slots).unwrap()
+    });
+    assert!(matches!(
+        circuits[1].clone().with_max_slots(max_slots - 1),
+        Err(InputError::TooManySlots { num_slots: 3, max_slots: 2 })
+    ));
+    let config = circuits[1].sized_config_params(&SizingOptions::default());
+    let _params = config.enter();
+    for circuit in &circuits {
+        let instance = circuit.instance();
+        assert_eq!(instance.len(), EthBlockStorageCircuit::<Fr>::get_num_instance(max_slots));
+        MockProver::run(config.degree, circuit, vec![instance]).unwrap().assert_satisfied();
+    }
+    // slots past `num_slots` are zeroed
+    assert!(circuits[0].instance()[9..].iter().all(|x| *x == Fr::from(0)));
+
+    let params = gen_srs(config.degree);
+    let [vk0, vk1] = circuits.map(|circuit| keygen_vk(&params, &circuit).unwrap());
+    assert_eq!(vk0.transcript_repr(), vk1.transcript_repr());
+}
+
+#[test]
+pub fn test_mock_storage_batch() {
+    let single = get_test_circuit::<Fr>(Network::Mainnet, 10);
+    let max_nodes = single.witness().storage.min_max_nodes();
+    assert!(matches!(
+        single.clone().with_storage_batch(max_nodes - 1),
+        Err(InputError::Proof { .. })
+    ));
+    let batch = single.clone().with_storage_batch(max_nodes).unwrap();
+    assert_eq!(batch.instance(), single.instance());
+
+    let [single_config, config] =
+        [&single, &batch].map(|circuit| circuit.sized_config_params(&SizingOptions::default()));
+    let [single_usage, batch_usage] =
+        [(&single, &single_config), (&batch, &config)].map(|(circuit, config)| {
+            sizing::dry_run(circuit, vec![circuit.instance()], config).unwrap()
+        });
+    println!("10 slots checked separately: {single_config:?}\n{single_usage:?}");
+    println!("10 slots checked as a batch of {max_nodes} nodes: {config:?}\n{batch_usage:?}");
+    assert!(batch_usage.num_keccak_f < single_usage.num_keccak_f);
+
+    let _params = config.enter();
+    MockProver::run(config.degree, &batch, vec![batch.instance()]).unwrap().assert_satisfied();
+    // claim a different value of the last slot
+    let mut instance = batch.instance();
+    *instance.last_mut().unwrap() += Fr::from(1);
+    assert!(MockProver::run(config.degree, &batch, vec![instance]).unwrap().verify().is_err());
+
+    let params = gen_srs(config.degree);
+    EthBlockStorageCircuit::assert_same_shape(&params, &config, &[("batch", &batch)]);
+}
+
+#[test]
+pub fn test_mock_zero_slots() {
+    let mut circuit = EthBlockStorageCircuit::<Fr>::from_json("proof_input_example.json")
+        .with_max_slots(2)
+        .unwrap();
+    let config = circuit.sized_config_params(&SizingOptions::default());
+    let _params = config.enter();
+    // the padding slot has a valid proof, but a proof must query at least one slot
+    circuit.inputs.as_mut().unwrap().storage.num_slots = 0;
+    assert!(matches!(
+        circuit.witness().validate(Network::Mainnet),
+        Err(InputError::InvalidField { .. })
+    ));
+    let instance = circuit.instance();
+    assert!(MockProver::run(config.degree, &circuit, vec![instance]).unwrap().verify().is_err());
+}
+
+#[test]
+pub fn test_storage_input_validate() {
+    let inputs = EthBlockStorageCircuit::<Fr>::from_json("proof_input_example.json")
+        .with_max_slots(2)
+        .unwrap()
+        .inputs
+        .unwrap();
+
+    let mut different_depths = inputs.clone();
+    different_depths.storage.storage_pfs[1].2.max_depth += 1;
+    assert!(matches!(
+        different_depths.validate(Network::Mainnet),
+        Err(InputError::InvalidField { .. })
+    ));
+    assert!(EthBlockStorageCircuit::<Fr>::try_new(different_depths, Network::Mainnet).is_err());
+
+    let mut no_slots = inputs.storage;
+    no_slots.num_slots = 0;
+    assert!(matches!(no_slots.pad_slots(2), Err(InputError::InvalidField { .. })));
+}
+
+#[test]
+pub fn test_storage_circuit_shapes() {
+    let config = EthBlockStorageCircuit::<Fr>::config_params();
+    let params = gen_srs(config.degree);
+    let default = EthBlockStorageCircuit::<Fr>::default();
+    let from_json = EthBlockStorageCircuit::<Fr>::from_json("proof_input_example.json");
+    // the test circuits use smaller max depths than the default shape
+    let from_provider = get_test_circuit::<Fr>(Network::Mainnet, 1)
+        .with_max_depths(DEFAULT_ACCOUNT_PROOF_MAX_DEPTH, DEFAULT_STORAGE_PROOF_MAX_DEPTH)
+        .unwrap();
+    EthBlockStorageCircuit::assert_same_shape(
+        &params,
+        &config,
+        &[("default", &default), ("from_json", &from_json), ("from_provider", &from_provider)],
+    );
+}
+
+#[test]
+pub fn test_input_errors() {
+    use crate::{error::InputError, providers::try_saved_block_storage_input_fromstr};
+
+    let input = fs::read_to_string("proof_input_example.json").unwrap();
+    let mut json: serde_json::Value = serde_json::from_str(&input).unwrap();
+    json["block"].as_object_mut().unwrap().remove("mixHash");
+    let err = try_saved_block_storage_input_fromstr(&json.to_string()).unwrap_err();
+    assert!(matches!(&err, InputError::MissingField(field) if field == "block.mixHash"), "{err}");
+
+    let inputs = try_saved_block_storage_input_fromstr(&input).unwrap();
+    let depth = inputs.storage.storage_pfs[0].2.proof.len();
+    let mut too_deep = inputs;
+    too_deep.storage.storage_pfs[0].2.max_depth = depth - 1;
+    let err = EthBlockStorageCircuit::<Fr>::try_new(too_deep, Network::Mainnet).unwrap_err();
+    assert!(matches!(&err, InputError::Proof { proof, .. } if proof == "storageProof[0]"), "{err}");
+}
+
+#[test]
+pub fn test_max_depths() {
+    use crate::error::{InputError, MPTProofError};
+
+    let circuit = EthBlockStorageCircuit::<Fr>::from_json("proof_input_example.json");
+    assert_eq!(circuit.shape().acct_pf_max_depth, DEFAULT_ACCOUNT_PROOF_MAX_DEPTH);
+    assert_eq!(circuit.shape().storage_pf_max_depth, DEFAULT_STORAGE_PROOF_MAX_DEPTH);
+
+    let (acct_depth, storage_depth) = circuit.inputs.as_ref().unwrap().storage.min_max_depths();
+    let fitted = circuit.clone().with_max_depths(acct_depth, storage_depth).unwrap();
+    assert_eq!(fitted.shape().acct_pf_max_depth, acct_depth);
+    assert_eq!(fitted.without_witnesses().shape(), fitted.shape());
+
+    let err = circuit.with_max_depths(acct_depth - 1, storage_depth).unwrap_err();
+    assert!(
+        matches!(&err, InputError::Proof { proof, source: MPTProofError::TooDeep { depth, .. } }
+            if proof == "accountProof" && *depth == acct_depth),
+        "{err}"
+    );
+    assert!(err.to_string().contains(&format!("at least {acct_depth}")), "{err}");
+}
+
+#[test]
+pub fn test_input_formats() {
+    use crate::{error::InputError, providers::try_saved_block_storage_input_fromstr};
+    use ethers_core::utils::hex;
+    use serde_json::json;
+
+    let input = fs::read_to_string("proof_input_example.json").unwrap();
+    let legacy = try_saved_block_storage_input_fromstr(&input).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&input).unwrap();
+    let rpc = |result: &serde_json::Value| json!({ "jsonrpc": "2.0", "id": 1, "result": result });
+    let header_hex = format!("0x{}", hex::encode(&legacy.block_header));
+
+    for block in [rpc(&json["block"]), json!(header_hex), rpc(&json!(header_hex))] {
+        let v1 = json!({ "version": 1, "block": block, "proof": rpc(&json["account"]) });
+        let inputs = try_saved_block_storage_input_fromstr(&v1.to_string()).unwrap();
+        assert_eq!(inputs.block_hash, legacy.block_hash);
+        assert_eq!(inputs.block_number, legacy.block_number);
+        assert_eq!(inputs.block_header, legacy.block_header);
+        assert_eq!(inputs.storage.acct_pf.root_hash, legacy.storage.acct_pf.root_hash);
+        inputs.verify_native().unwrap();
+    }
+
+    let v2 = json!({ "version": 2, "block": json["block"], "proof": json["account"] });
+    let err = try_saved_block_storage_input_fromstr(&v2.to_string()).unwrap_err();
+    assert!(matches!(err, InputError::UnsupportedVersion { version: 2, supported: 1 }), "{err}");
+
+    let rpc_error = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "error": { "code": -32000, "message": "missing trie node" }
+    });
+    let v1 = json!({ "version": 1, "block": json["block"], "proof": rpc_error });
+    let err = try_saved_block_storage_input_fromstr(&v1.to_string()).unwrap_err();
+    assert!(matches!(&err, InputError::Rpc { field, .. } if field == "proof"), "{err}");
+}
+
+#[test]
+pub fn test_verify_native() {
+    use crate::{
+        error::{InputError, MPTProofError},
+        providers::try_saved_block_storage_input_fromstr,
+    };
+
+    let input = fs::read_to_string("proof_input_example.json").unwrap();
+    let inputs = try_saved_block_storage_input_fromstr(&input).unwrap();
+    inputs.verify_native().unwrap();
+
+    let mut wrong_hash = inputs;
+    wrong_hash.block_hash.0[0] ^= 1;
+    let err = wrong_hash.verify_native().unwrap_err();
+    assert!(matches!(&err, InputError::Mismatch { field, .. } if field == "block.hash"), "{err}");
+
+    // the README's experiment: change a hex character of the storage value
+    let mut json: serde_json::Value = serde_json::from_str(&input).unwrap();
+    let value = &mut json["account"]["storageProof"][0]["value"];
+    let tampered = value.as_str().unwrap().replacen('e', "f", 1);
+    *value = tampered.into();
+    let inputs = try_saved_block_storage_input_fromstr(&json.to_string()).unwrap();
+    let err = inputs.verify_native().unwrap_err();
+    assert!(
+        matches!(&err, InputError::Proof { proof, source: MPTProofError::Mismatch { .. } }
+            if proof == "storageProof[0]"),
+        "{err}"
+    );
+}
 
 #[derive(Serialize, Deserialize)]
 struct BenchParams(EthConfigParams, usize);
@@ -102,12 +329,10 @@ pub fn bench_eip1186() -> Result<(), Box<dyn std::error::Error>> {
             bench_params.0.degree
         );
 
-        set_var("STORAGE_CONFIG", "configs/bench/storage_tmp.json");
-        let mut f = File::create("configs/bench/storage_tmp.json")?;
-        write!(f, "{}", serde_json::to_string(&bench_params.0).unwrap())?;
+        let _params = bench_params.0.enter();
         //let circuit = get_test_circuit::<Fr>(Network::Mainnet, bench_params.1);
         let circuit = EthBlockStorageCircuit::from_json("./full_block_proof.json");
-        println!("{:?}",&circuit.inputs.block_header);
+        println!("{:?}",&circuit.inputs.as_ref().unwrap().block_header);
         //let mut bh = &circuit.clone().inputs.block_header;
         //println!("{:?}",hex::serialize(bh, ethers_core::utils::__serde_json::value::Serializer));
 
@@ -149,7 +374,6 @@ pub fn bench_eip1186() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap();
         end_timer!(verify_time);
 
-        fs::remove_file("configs/bench/storage_tmp.json")?;
         let keccak_advice = std::env::var("KECCAK_ADVICE_COLUMNS")
             .unwrap_or_else(|_| "0".to_string())
             .parse::<usize>()
@@ -183,7 +407,7 @@ pub fn bench_evm_eip1186() -> Result<(), Box<dyn std::error::Error>> {
         evm::{evm_verify, gen_evm_proof_shplonk, gen_evm_verifier_shplonk, write_calldata},
         gen_pk,
         halo2::{
-            aggregation::{load_verify_circuit_degree, PublicAggregationCircuit},
+            aggregation::PublicAggregationCircuit,
             gen_snark_shplonk, 
         },
         CircuitExt,
@@ -204,13 +428,11 @@ pub fn bench_evm_eip1186() -> Result<(), Box<dyn std::error::Error>> {
             bench_params.0.degree
         );
 
-        set_var("STORAGE_CONFIG", "configs/bench/storage_tmp.json");
-        let mut f = File::create("configs/bench/storage_tmp.json")?;
-        write!(f, "{}", serde_json::to_string(&bench_params.0).unwrap())?;
         let mut rng = rand_chacha::ChaChaRng::from_seed([0; 32]);
 
         let (storage_snark, storage_proof_time) = {
-            let k = EthConfigParams::get_storage().degree;
+            let _params = bench_params.0.enter();
+            let k = bench_params.0.degree;
             let circuit = get_test_circuit::<Fr>(Network::Mainnet, bench_params.1);
             let params = gen_srs(k);
             let pk = gen_pk(&params, &circuit, None);
@@ -220,17 +442,14 @@ pub fn bench_evm_eip1186() -> Result<(), Box<dyn std::error::Error>> {
             (snark, storage_proof_time)
         };
 
-        set_var("VERIFY_CONFIG", "configs/bench/storage_evm_tmp.json");
-        let mut f = File::create("configs/bench/storage_evm_tmp.json")?;
-        write!(f, "{}", serde_json::to_string(&evm_params).unwrap())?;        
-        let k = load_verify_circuit_degree();
-        let params = gen_srs(k);
-        let evm_circuit = PublicAggregationCircuit::new(
+        let _evm_params = enter_circuit_params(evm_params);
+        let params = gen_srs(evm_params.degree);
+        let evm_circuit = PublicAggregationCircuitWithParams(PublicAggregationCircuit::new(
             &params,
             vec![storage_snark],
             false,
             &mut rng,
-        );
+        ));
         let pk = gen_pk(&params, &evm_circuit, None);
     
         let instances = evm_circuit.instances();
@@ -241,7 +460,7 @@ pub fn bench_evm_eip1186() -> Result<(), Box<dyn std::error::Error>> {
         fs::create_dir_all("data/storage").unwrap();
         write_calldata(&instances, &proof, Path::new("data/storage/test.calldata")).unwrap();
     
-        let deployment_code = gen_evm_verifier_shplonk::<PublicAggregationCircuit>(
+        let deployment_code = gen_evm_verifier_shplonk::<PublicAggregationCircuitWithParams>(
             &params,
             pk.get_vk(),
             vec![num_instances],
@@ -251,7 +470,6 @@ pub fn bench_evm_eip1186() -> Result<(), Box<dyn std::error::Error>> {
         // this verifies proof in EVM and outputs gas cost (if successful)
         evm_verify(deployment_code, instances, proof);
 
-        fs::remove_file("configs/bench/storage_tmp.json")?;
         let keccak_advice = std::env::var("KECCAK_ADVICE_COLUMNS")
             .unwrap_or_else(|_| "0".to_string())
             .parse::<usize>()
@@ -286,31 +504,32 @@ pub fn test_evm_single_eip1186() {
         evm::{evm_verify, gen_evm_proof_shplonk, gen_evm_verifier_shplonk, write_calldata},
         gen_pk,
         halo2::{
-            aggregation::{load_verify_circuit_degree, PublicAggregationCircuit},
+            aggregation::{AggregationConfigParams, PublicAggregationCircuit},
             gen_snark_shplonk,
         },
         CircuitExt,
     };
     let mut rng = rand_chacha::ChaChaRng::from_seed([0; 32]);
 
-    set_var("STORAGE_CONFIG", "configs/tests/storage.json");
     let storage_snark = {
-        let k = EthConfigParams::get_storage().degree;
+        let config = EthConfigParams::from_path("configs/tests/storage.json");
+        let _params = config.enter();
+        let k = config.degree;
         let circuit = get_test_circuit::<Fr>(Network::Mainnet, 1);
         let params = gen_srs(k);
         let pk = gen_pk(&params, &circuit, None);
         gen_snark_shplonk(&params, &pk, circuit, &mut rng, None::<&str>)
     };
 
-    std::env::set_var("VERIFY_CONFIG", "./configs/tests/storage_evm.json");
-    let k = load_verify_circuit_degree();
-    let params = gen_srs(k);
-    let evm_circuit = PublicAggregationCircuit::new(
+    let evm_config: AggregationConfigParams = read_config("./configs/tests/storage_evm.json");
+    let _evm_params = enter_circuit_params(&evm_config);
+    let params = gen_srs(evm_config.degree);
+    let evm_circuit = PublicAggregationCircuitWithParams(PublicAggregationCircuit::new(
         &params,
         vec![storage_snark],
         false,
         &mut rng,
-    );
+    ));
     let pk = gen_pk(&params, &evm_circuit, None);
 
     let instances = evm_circuit.instances();
@@ -319,7 +538,7 @@ pub fn test_evm_single_eip1186() {
     fs::create_dir_all("data/storage").unwrap();
     write_calldata(&instances, &proof, Path::new("data/storage/test.calldata")).unwrap();
 
-    let deployment_code = gen_evm_verifier_shplonk::<PublicAggregationCircuit>(
+    let deployment_code = gen_evm_verifier_shplonk::<PublicAggregationCircuitWithParams>(
         &params,
         pk.get_vk(),
         vec![num_instances],
@@ -330,3 +549,53 @@ pub fn test_evm_single_eip1186() {
     evm_verify(deployment_code, instances, proof);
 }
 
+
+#[cfg(feature = "evm")]
+#[test]
+pub fn test_evm_verifier_harness() {
+    use super::evm::{deploy_and_call, gen_storage_evm_artifacts};
+    use crate::keystore::KeyStore;
+    use rand::SeedableRng;
+    use snark_verifier::loader::evm::encode_calldata;
+    use std::path::Path;
+
+    // gas of a single aggregation verifier call is ~350k; anything far above indicates a regression
+    const MAX_VERIFY_GAS: u64 = 600_000;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0; 32]);
+    // the inputs are read from a file, so this test runs without a provider
+    let circuit = EthBlockStorageCircuit::<Fr>::from_json("proof_input_example.json");
+    let storage_config = EthBlockStorageCircuit::<Fr>::config_params();
+    let storage_params = gen_srs(storage_config.degree);
+    let evm_config: AggregationConfigParams = read_config("./configs/tests/storage_evm.json");
+    let evm_params = gen_srs(evm_config.degree);
+    fs::create_dir_all("data/storage").unwrap();
+    let artifacts = gen_storage_evm_artifacts(
+        &storage_params,
+        &storage_config,
+        &evm_params,
+        &evm_config,
+        circuit,
+        &KeyStore::new("data/tests/keys"),
+        Some(Path::new("data/storage/test.yul")),
+        &mut rng,
+    );
+
+    let outcome = deploy_and_call(artifacts.deployment_code.clone(), artifacts.calldata.clone());
+    assert!(!outcome.reverted, "valid proof should be accepted");
+    assert!(outcome.gas_used < MAX_VERIFY_GAS, "verification used {} gas", outcome.gas_used);
+
+    // claim a different storage value: the last instance is the low limb of the last slot value
+    let mut instances = artifacts.instances.clone();
+    *instances.last_mut().unwrap() += Fr::from(1u64);
+    let calldata = encode_calldata(&[instances], &artifacts.proof);
+    let outcome = deploy_and_call(artifacts.deployment_code.clone(), calldata);
+    assert!(outcome.reverted, "proof with wrong public instances should be rejected");
+
+    // corrupt the proof itself
+    let mut proof = artifacts.proof.clone();
+    proof[0] ^= 1;
+    let calldata = encode_calldata(&[artifacts.instances], &proof);
+    let outcome = deploy_and_call(artifacts.deployment_code, calldata);
+    assert!(outcome.reverted, "corrupted proof should be rejected");
+}
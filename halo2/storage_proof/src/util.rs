@@ -1,4 +1,4 @@
-use super::Field;
+use super::{keccak::KeccakParams, Field};
 use ethers_core::{
     types::{Address, H256, U256},
     utils::keccak256,
@@ -15,7 +15,14 @@ use halo2_base::{
 };
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::{env::var, fs::File};
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    marker::PhantomData,
+    path::Path,
+};
 
 pub(crate) const NUM_BYTES_IN_U128: usize = 16;
 
@@ -35,23 +42,101 @@ pub struct EthConfigParams {
 }
 
 impl EthConfigParams {
-    pub fn get_header() -> Self {
-        let path =
-            var("BLOCK_HEADER_CONFIG").unwrap_or_else(|_| "configs/block_header.json".to_string());
-        serde_json::from_reader(
-            File::open(&path).unwrap_or_else(|e| panic!("{path} does not exist. {e:?}")),
-        )
-        .unwrap()
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        read_config(path)
+    }
+
+    pub fn keccak_params(&self) -> KeccakParams {
+        KeccakParams {
+            degree: self.degree,
+            rows_per_round: self.keccak_rows_per_round,
+            unusable_rows: self.unusable_rows,
+        }
+    }
+
+    /// Sets `self` as the params used by `configure` of the circuits that read [`EthConfigParams`] until the
+    /// guard is dropped, see [`enter_circuit_params`].
+    pub fn enter(&self) -> CircuitParamsGuard<Self> {
+        enter_circuit_params(self)
+    }
+
+    /// Runs `f` with `self` as the params used by `configure` of the circuits that read [`EthConfigParams`].
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_circuit_params(self, f)
     }
-    pub fn get_storage() -> Self {
-        let path = var("STORAGE_CONFIG").unwrap_or_else(|_| "configs/storage.json".to_string());
-        serde_json::from_reader(
-            File::open(&path).unwrap_or_else(|e| panic!("{path} does not exist. {e:?}")),
-        )
-        .unwrap()
+
+    /// The params set by the innermost enclosing [`EthConfigParams::scope`] on this thread, if any.
+    pub fn current() -> Option<Self> {
+        circuit_params()
     }
 }
 
+/// Reads a json config file.
+///
+/// # Panics
+/// If the file does not exist or is not valid json for `T`.
+pub fn read_config<T: serde::de::DeserializeOwned>(path: impl AsRef<Path>) -> T {
+    let path = path.as_ref();
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path:?} does not exist. {e:?}")),
+    )
+    .unwrap_or_else(|e| panic!("{path:?} is not a valid config file. {e:?}"))
+}
+
+thread_local! {
+    static CIRCUIT_PARAMS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Guard returned by [`enter_circuit_params`]. Restores the previous params of type `P` when dropped, so scopes
+/// nest and are unwound on panic.
+#[must_use = "the params are unset as soon as the guard is dropped"]
+pub struct CircuitParamsGuard<P: 'static> {
+    prev: Option<P>,
+    // the params are thread local, so the guard must be dropped on the thread that created it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<P: 'static> Drop for CircuitParamsGuard<P> {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CIRCUIT_PARAMS.with(|params| {
+            let mut params = params.borrow_mut();
+            match prev {
+                Some(prev) => params.insert(TypeId::of::<P>(), Box::new(prev)),
+                None => params.remove(&TypeId::of::<P>()),
+            };
+        });
+    }
+}
+
+/// Makes `params` available to [`circuit_params`] on the current thread until the returned guard is dropped.
+///
+/// `Circuit::configure` does not take `self`, so circuits whose shape depends on runtime params read them with
+/// [`circuit_params`]. Params are scoped to the calling thread, so circuits with different params can be
+/// configured and proven concurrently, except for the keccak table params, see [`crate::keccak::KeccakParams`].
+/// Keygen, reading keys and proving all call `configure`, so they must all happen while the params are set.
+pub fn enter_circuit_params<P: Clone + 'static>(params: &P) -> CircuitParamsGuard<P> {
+    let prev = CIRCUIT_PARAMS.with(|cell| {
+        cell.borrow_mut()
+            .insert(TypeId::of::<P>(), Box::new(params.clone()))
+            .map(|prev| *prev.downcast::<P>().unwrap())
+    });
+    CircuitParamsGuard { prev, _not_send: PhantomData }
+}
+
+/// Runs `f` with `params` available to [`circuit_params`] on the current thread, see [`enter_circuit_params`].
+pub fn with_circuit_params<P: Clone + 'static, R>(params: &P, f: impl FnOnce() -> R) -> R {
+    let _guard = enter_circuit_params(params);
+    f()
+}
+
+/// The params of type `P` set by the innermost enclosing [`with_circuit_params`] on this thread, if any.
+pub fn circuit_params<P: Clone + 'static>() -> Option<P> {
+    CIRCUIT_PARAMS.with(|cell| {
+        cell.borrow().get(&TypeId::of::<P>()).map(|p| p.downcast_ref::<P>().unwrap().clone())
+    })
+}
+
 pub(crate) type AssignedH256<'v, F> = [AssignedValue<'v, F>; 2]; // H256 as hi-lo (u128, u128)
 
 pub fn get_merkle_mountain_range(leaves: &[H256], max_depth: usize) -> Vec<H256> {